@@ -0,0 +1,762 @@
+use crate::error::ArmError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Width {
+    Byte,
+    Half,
+    Word,
+}
+
+impl Width {
+    pub fn bytes(self) -> usize {
+        match self {
+            Width::Byte => 1,
+            Width::Half => 2,
+            Width::Word => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Branch {
+    Continue,
+    Return,
+    Exit,
+}
+
+pub trait ArmBus {
+    fn read(&mut self, address: u32, width: Width) -> Result<u32, ArmError>;
+
+    fn write(
+        &mut self,
+        address: u32,
+        width: Width,
+        data: u32,
+    ) -> Result<(), ArmError>;
+
+    fn wait_states(&self, _address: u32) -> u64 {
+        0
+    }
+
+    // Every BX (and POP into the PC) lands here first, so that the bus can
+    // stand in for code it does not want interpreted. Returning resumes at
+    // the link register, as if the callee had run; continuing runs the
+    // target as Thumb code. Only Thumb is emulated, so by default a switch
+    // to ARM state ends the run: that is how C code returns to a driver.
+    fn branch(&mut self, target: u32, _registers: &mut [u32; 16]) -> Branch {
+        if target & 1 != 0 {
+            Branch::Continue
+        } else {
+            Branch::Exit
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shift {
+    Lsl,
+    Lsr,
+    Asr,
+    Ror,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Thumb {
+    registers: [u32; 16],
+    negative: bool,
+    zero: bool,
+    carry: bool,
+    overflow: bool,
+    cycles: u64,
+}
+
+impl Thumb {
+    pub const SP: usize = 13;
+    pub const LR: usize = 14;
+    pub const PC: usize = 15;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn registers(&self) -> &[u32; 16] {
+        &self.registers
+    }
+
+    pub fn register(&self, index: usize) -> u32 {
+        self.registers[index]
+    }
+
+    pub fn set_register(&mut self, index: usize, value: u32) {
+        self.registers[index] = value;
+    }
+
+    pub fn status(&self) -> u32 {
+        let flags = [self.negative, self.zero, self.carry, self.overflow];
+        let bits =
+            flags.iter().fold(0, |bits, &flag| (bits << 1) | u32::from(flag));
+        (bits << 28) | 0x20
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn reset_cycles(&mut self) {
+        self.cycles = 0;
+    }
+
+    // Runs until the code leaves Thumb state, giving up once the cycle
+    // limit is exceeded so that a stuck routine cannot hang the caller.
+    pub fn run<B>(&mut self, bus: &mut B, limit: u64) -> Result<u64, ArmError>
+    where
+        B: ArmBus,
+    {
+        let start = self.cycles;
+        while self.step(bus)? {
+            let cycles = self.cycles - start;
+            if cycles > limit {
+                return Err(ArmError::Timeout { cycles });
+            }
+        }
+        Ok(self.cycles - start)
+    }
+
+    // Cycles follow the ARM7TDMI timings: one per bus access plus the
+    // wait states of the memory accessed, an internal cycle for loads and
+    // register shifts, the multiplier's early termination, and a pipeline
+    // refill of two fetches whenever the PC is written.
+    pub fn step<B>(&mut self, bus: &mut B) -> Result<bool, ArmError>
+    where
+        B: ArmBus,
+    {
+        let address = self.registers[Self::PC] & !1;
+        let instruction = bus.read(address, Width::Half)? as u16;
+        self.cycles += 1 + bus.wait_states(address);
+        self.registers[Self::PC] = address.wrapping_add(2);
+        self.execute(bus, address, instruction)
+    }
+
+    fn execute<B>(
+        &mut self,
+        bus: &mut B,
+        address: u32,
+        instruction: u16,
+    ) -> Result<bool, ArmError>
+    where
+        B: ArmBus,
+    {
+        let op = u32::from(instruction);
+        let rd = (op & 7) as usize;
+        let rs = ((op >> 3) & 7) as usize;
+        let rn = ((op >> 6) & 7) as usize;
+        let high = ((op >> 8) & 7) as usize;
+        let undefined = ArmError::Undefined { address, instruction };
+
+        match op >> 13 {
+            0b000 if (op >> 11) & 3 == 3 => {
+                let operand =
+                    if op & 0x400 != 0 { rn as u32 } else { self.get(rn) };
+                let value = self.get(rs);
+                self.registers[rd] = if op & 0x200 != 0 {
+                    self.add(value, !operand, true)
+                } else {
+                    self.add(value, operand, false)
+                };
+            },
+            0b000 => {
+                let amount = (op >> 6) & 0x1F;
+                let value = self.get(rs);
+                let result = match (op >> 11) & 3 {
+                    0 => self.shift(Shift::Lsl, value, amount),
+                    1 if amount == 0 => self.shift(Shift::Lsr, value, 32),
+                    1 => self.shift(Shift::Lsr, value, amount),
+                    _ if amount == 0 => self.shift(Shift::Asr, value, 32),
+                    _ => self.shift(Shift::Asr, value, amount),
+                };
+                self.set_nz(result);
+                self.registers[rd] = result;
+            },
+            0b001 => {
+                let immediate = op & 0xFF;
+                let value = self.registers[high];
+                match (op >> 11) & 3 {
+                    0 => {
+                        self.set_nz(immediate);
+                        self.registers[high] = immediate;
+                    },
+                    1 => {
+                        self.add(value, !immediate, true);
+                    },
+                    2 => {
+                        self.registers[high] = self.add(value, immediate, false)
+                    },
+                    _ => {
+                        self.registers[high] = self.add(value, !immediate, true)
+                    },
+                }
+            },
+            0b010 if op >> 10 == 0b01_0000 => self.alu(op, rd, rs),
+            0b010 if op >> 10 == 0b01_0001 => {
+                return self.high_register(bus, op, undefined)
+            },
+            0b010 if op >> 11 == 0b0_1001 => {
+                let base = self.get(Self::PC) & !3;
+                let value =
+                    self.load(bus, base + (op & 0xFF) * 4, Width::Word)?;
+                self.registers[high] = value;
+            },
+            0b010 => {
+                let target = self.get(rs).wrapping_add(self.get(rn));
+                let value = self.get(rd);
+                self.registers[rd] = match (op >> 9) & 7 {
+                    0b000 => {
+                        return self.store(bus, target, Width::Word, value)
+                    },
+                    0b010 => {
+                        return self.store(bus, target, Width::Byte, value)
+                    },
+                    0b001 => {
+                        return self.store(bus, target, Width::Half, value)
+                    },
+                    0b100 => self.load(bus, target, Width::Word)?,
+                    0b110 => self.load(bus, target, Width::Byte)?,
+                    0b101 => self.load(bus, target, Width::Half)?,
+                    0b011 => {
+                        self.load(bus, target, Width::Byte)? as u8 as i8 as u32
+                    },
+                    _ => self.load(bus, target, Width::Half)? as u16 as i16
+                        as u32,
+                };
+            },
+            0b011 => {
+                let width =
+                    if op & 0x1000 != 0 { Width::Byte } else { Width::Word };
+                let scale = if width == Width::Word { 4 } else { 1 };
+                let target =
+                    self.get(rs).wrapping_add(((op >> 6) & 0x1F) * scale);
+                if op & 0x800 != 0 {
+                    self.registers[rd] = self.load(bus, target, width)?;
+                } else {
+                    return self.store(bus, target, width, self.get(rd));
+                }
+            },
+            0b100 => {
+                let (base, index, offset, width) = if op & 0x1000 == 0 {
+                    (self.get(rs), rd, ((op >> 6) & 0x1F) * 2, Width::Half)
+                } else {
+                    (self.get(Self::SP), high, (op & 0xFF) * 4, Width::Word)
+                };
+                let target = base.wrapping_add(offset);
+                if op & 0x800 != 0 {
+                    self.registers[index] = self.load(bus, target, width)?;
+                } else {
+                    return self.store(bus, target, width, self.get(index));
+                }
+            },
+            0b101 if op & 0x1000 == 0 => {
+                let base = if op & 0x800 != 0 {
+                    self.get(Self::SP)
+                } else {
+                    self.get(Self::PC) & !3
+                };
+                self.registers[high] = base.wrapping_add((op & 0xFF) * 4);
+            },
+            0b101 if (op >> 8) & 0xF == 0 => {
+                let offset = (op & 0x7F) * 4;
+                let sp = self.registers[Self::SP];
+                self.registers[Self::SP] = if op & 0x80 != 0 {
+                    sp.wrapping_sub(offset)
+                } else {
+                    sp.wrapping_add(offset)
+                };
+            },
+            0b101 if (op >> 9) & 3 == 0b10 => return self.push_pop(bus, op),
+            0b110 if op & 0x1000 == 0 => {
+                return self.transfer_multiple(bus, op, high, undefined)
+            },
+            0b110 => {
+                let condition = (op >> 8) & 0xF;
+                if condition >= 0xE {
+                    return Err(undefined);
+                }
+                if self.condition(condition) {
+                    let offset = (op & 0xFF) as u8 as i8 as i32 as u32;
+                    let target = self.get(Self::PC).wrapping_add(offset << 1);
+                    self.jump(bus, target);
+                }
+            },
+            0b111 => match (op >> 11) & 3 {
+                0b00 => {
+                    let offset = (((op & 0x7FF) << 21) as i32 >> 20) as u32;
+                    let target = self.get(Self::PC).wrapping_add(offset);
+                    self.jump(bus, target);
+                },
+                0b10 => {
+                    let offset = (((op & 0x7FF) << 21) as i32 >> 9) as u32;
+                    self.registers[Self::LR] =
+                        self.get(Self::PC).wrapping_add(offset);
+                },
+                0b11 => {
+                    let target = self.registers[Self::LR]
+                        .wrapping_add((op & 0x7FF) << 1);
+                    self.registers[Self::LR] = address.wrapping_add(2) | 1;
+                    self.jump(bus, target);
+                },
+                _ => return Err(undefined),
+            },
+            _ => return Err(undefined),
+        }
+        Ok(true)
+    }
+
+    fn alu(&mut self, op: u32, rd: usize, rs: usize) {
+        let value = self.registers[rd];
+        let operand = self.registers[rs];
+        let result = match (op >> 6) & 0xF {
+            0x0 | 0x8 => value & operand,
+            0x1 => value ^ operand,
+            0x2 => self.shift_register(Shift::Lsl, value, operand),
+            0x3 => self.shift_register(Shift::Lsr, value, operand),
+            0x4 => self.shift_register(Shift::Asr, value, operand),
+            0x5 => self.add(value, operand, self.carry),
+            0x6 => self.add(value, !operand, self.carry),
+            0x7 => self.shift_register(Shift::Ror, value, operand),
+            0x9 => self.add(0, !operand, true),
+            0xA => self.add(value, !operand, true),
+            0xB => self.add(value, operand, false),
+            0xC => value | operand,
+            0xD => {
+                self.cycles += Self::multiplier_cycles(value);
+                value.wrapping_mul(operand)
+            },
+            0xE => value & !operand,
+            _ => !operand,
+        };
+        self.set_nz(result);
+        if !matches!((op >> 6) & 0xF, 0x8 | 0xA | 0xB) {
+            self.registers[rd] = result;
+        }
+    }
+
+    fn high_register<B>(
+        &mut self,
+        bus: &mut B,
+        op: u32,
+        undefined: ArmError,
+    ) -> Result<bool, ArmError>
+    where
+        B: ArmBus,
+    {
+        let rd = ((op & 7) | ((op >> 4) & 8)) as usize;
+        let rs = ((op >> 3) & 0xF) as usize;
+        let result = match (op >> 8) & 3 {
+            0 => self.get(rd).wrapping_add(self.get(rs)),
+            1 => {
+                self.add(self.get(rd), !self.get(rs), true);
+                return Ok(true);
+            },
+            2 => self.get(rs),
+            _ if op & 0x80 != 0 => return Err(undefined),
+            _ => return Ok(self.exchange(bus, self.get(rs))),
+        };
+        if rd == Self::PC {
+            self.jump(bus, result);
+        } else {
+            self.registers[rd] = result;
+        }
+        Ok(true)
+    }
+
+    fn push_pop<B>(&mut self, bus: &mut B, op: u32) -> Result<bool, ArmError>
+    where
+        B: ArmBus,
+    {
+        let extra = op & 0x100 != 0;
+        let count = (op & 0xFF).count_ones() + u32::from(extra);
+        let sp = self.registers[Self::SP];
+        if op & 0x800 == 0 {
+            let mut target = sp.wrapping_sub(count * 4);
+            self.registers[Self::SP] = target;
+            let lr = if extra { Some(Self::LR) } else { None };
+            for index in (0..8).filter(|index| op & (1 << index) != 0).chain(lr)
+            {
+                self.store(bus, target, Width::Word, self.registers[index])?;
+                target = target.wrapping_add(4);
+            }
+            return Ok(true);
+        }
+
+        let mut target = sp;
+        for index in (0..8).filter(|index| op & (1 << index) != 0) {
+            self.registers[index] = self.load(bus, target, Width::Word)?;
+            target = target.wrapping_add(4);
+        }
+        if !extra {
+            self.registers[Self::SP] = target;
+            return Ok(true);
+        }
+        let pc = self.load(bus, target, Width::Word)?;
+        self.registers[Self::SP] = target.wrapping_add(4);
+        Ok(self.exchange(bus, pc))
+    }
+
+    fn transfer_multiple<B>(
+        &mut self,
+        bus: &mut B,
+        op: u32,
+        base: usize,
+        undefined: ArmError,
+    ) -> Result<bool, ArmError>
+    where
+        B: ArmBus,
+    {
+        let list = op & 0xFF;
+        if list == 0 {
+            return Err(undefined);
+        }
+        let mut target = self.registers[base];
+        for index in (0..8).filter(|index| list & (1 << index) != 0) {
+            if op & 0x800 != 0 {
+                self.registers[index] = self.load(bus, target, Width::Word)?;
+            } else {
+                self.store(bus, target, Width::Word, self.registers[index])?;
+            }
+            target = target.wrapping_add(4);
+        }
+        if op & 0x800 == 0 || list & (1 << base) == 0 {
+            self.registers[base] = target;
+        }
+        Ok(true)
+    }
+
+    // The PC reads as the address of the instruction plus four, because of
+    // the pipeline.
+    fn get(&self, index: usize) -> u32 {
+        if index == Self::PC {
+            self.registers[Self::PC].wrapping_add(2)
+        } else {
+            self.registers[index]
+        }
+    }
+
+    fn jump<B>(&mut self, bus: &mut B, target: u32)
+    where
+        B: ArmBus,
+    {
+        let target = target & !1;
+        self.registers[Self::PC] = target;
+        self.cycles += 2 * (1 + bus.wait_states(target));
+    }
+
+    fn exchange<B>(&mut self, bus: &mut B, target: u32) -> bool
+    where
+        B: ArmBus,
+    {
+        match bus.branch(target, &mut self.registers) {
+            Branch::Continue => self.jump(bus, target),
+            Branch::Return => self.jump(bus, self.registers[Self::LR]),
+            Branch::Exit => return false,
+        }
+        true
+    }
+
+    // Misaligned words come back rotated, as the ARM7 does it.
+    fn load<B>(
+        &mut self,
+        bus: &mut B,
+        address: u32,
+        width: Width,
+    ) -> Result<u32, ArmError>
+    where
+        B: ArmBus,
+    {
+        self.cycles += 2 + bus.wait_states(address);
+        match width {
+            Width::Byte => bus.read(address, width),
+            Width::Half => bus.read(address & !1, width),
+            Width::Word => {
+                let word = bus.read(address & !3, width)?;
+                Ok(word.rotate_right((address & 3) * 8))
+            },
+        }
+    }
+
+    fn store<B>(
+        &mut self,
+        bus: &mut B,
+        address: u32,
+        width: Width,
+        data: u32,
+    ) -> Result<bool, ArmError>
+    where
+        B: ArmBus,
+    {
+        self.cycles += 1 + bus.wait_states(address);
+        let (address, data) = match width {
+            Width::Byte => (address, data & 0xFF),
+            Width::Half => (address & !1, data & 0xFFFF),
+            Width::Word => (address & !3, data),
+        };
+        bus.write(address, width, data)?;
+        Ok(true)
+    }
+
+    fn add(&mut self, left: u32, right: u32, carry: bool) -> u32 {
+        let wide = u64::from(left) + u64::from(right) + u64::from(carry);
+        let result = wide as u32;
+        self.carry = wide > u64::from(u32::MAX);
+        self.overflow = (!(left ^ right) & (left ^ result)) >> 31 != 0;
+        self.set_nz(result);
+        result
+    }
+
+    fn set_nz(&mut self, value: u32) {
+        self.negative = value >> 31 != 0;
+        self.zero = value == 0;
+    }
+
+    fn shift_register(&mut self, kind: Shift, value: u32, amount: u32) -> u32 {
+        self.cycles += 1;
+        self.shift(kind, value, amount & 0xFF)
+    }
+
+    // Shifting by zero leaves the carry alone, while shifting by 32 or more
+    // (possible with register amounts) pushes the last bit out or clears it.
+    fn shift(&mut self, kind: Shift, value: u32, amount: u32) -> u32 {
+        if amount == 0 {
+            return value;
+        }
+        let bit = |index: u32| (value >> index) & 1 != 0;
+        let (result, carry) = match (kind, amount) {
+            (Shift::Lsl, 1..=31) => (value << amount, bit(32 - amount)),
+            (Shift::Lsl, 32) => (0, bit(0)),
+            (Shift::Lsl, _) | (Shift::Lsr, 33..=255) => (0, false),
+            (Shift::Lsr, 32) => (0, bit(31)),
+            (Shift::Lsr, _) => (value >> amount, bit(amount - 1)),
+            (Shift::Asr, 1..=31) => {
+                (((value as i32) >> amount) as u32, bit(amount - 1))
+            },
+            (Shift::Asr, _) => (((value as i32) >> 31) as u32, bit(31)),
+            (Shift::Ror, _) => {
+                let result = value.rotate_right(amount & 31);
+                (result, result >> 31 != 0)
+            },
+        };
+        self.carry = carry;
+        result
+    }
+
+    fn condition(&self, condition: u32) -> bool {
+        match condition {
+            0x0 => self.zero,
+            0x1 => !self.zero,
+            0x2 => self.carry,
+            0x3 => !self.carry,
+            0x4 => self.negative,
+            0x5 => !self.negative,
+            0x6 => self.overflow,
+            0x7 => !self.overflow,
+            0x8 => self.carry && !self.zero,
+            0x9 => !self.carry || self.zero,
+            0xA => self.negative == self.overflow,
+            0xB => self.negative != self.overflow,
+            0xC => !self.zero && self.negative == self.overflow,
+            0xD => self.zero || self.negative != self.overflow,
+            _ => true,
+        }
+    }
+
+    // The multiplier stops early once the remaining bits of the operand
+    // are all zeros or all ones, a byte at a time.
+    fn multiplier_cycles(operand: u32) -> u64 {
+        let mut cycles = 1;
+        for mask in &[0xFFFF_FF00u32, 0xFFFF_0000, 0xFF00_0000] {
+            let top = operand & mask;
+            if top == 0 || top == *mask {
+                break;
+            }
+            cycles += 1;
+        }
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAM: u32 = 0x4000_0000;
+
+    // Code from address zero, with optional wait states, and a little RAM.
+    struct Memory {
+        code: Vec<u8>,
+        ram: [u8; 16],
+        wait_states: u64,
+    }
+
+    fn memory(code: &[u16]) -> Memory {
+        let code = code.iter().flat_map(|half| half.to_le_bytes()).collect();
+        Memory { code, ram: [0; 16], wait_states: 0 }
+    }
+
+    fn run(thumb: &mut Thumb, memory: &mut Memory, steps: usize) {
+        for _ in 0..steps {
+            assert_eq!(thumb.step(memory), Ok(true));
+        }
+    }
+
+    impl ArmBus for Memory {
+        fn read(
+            &mut self,
+            address: u32,
+            width: Width,
+        ) -> Result<u32, ArmError> {
+            let bytes = match address.checked_sub(RAM) {
+                Some(offset) => &self.ram[offset as usize..],
+                None => &self.code[address as usize..],
+            };
+            let mut word = [0; 4];
+            word[..width.bytes()].copy_from_slice(&bytes[..width.bytes()]);
+            Ok(u32::from_le_bytes(word))
+        }
+
+        fn write(
+            &mut self,
+            address: u32,
+            width: Width,
+            data: u32,
+        ) -> Result<(), ArmError> {
+            let offset =
+                address.checked_sub(RAM).ok_or(ArmError::Write { address })?;
+            let offset = offset as usize;
+            self.ram[offset..offset + width.bytes()]
+                .copy_from_slice(&data.to_le_bytes()[..width.bytes()]);
+            Ok(())
+        }
+
+        fn wait_states(&self, address: u32) -> u64 {
+            if address < RAM {
+                self.wait_states
+            } else {
+                0
+            }
+        }
+    }
+
+    #[test]
+    fn unassigned_misc_encodings_are_undefined() {
+        for &instruction in &[0xBE00, 0xB200, 0xB100] {
+            let mut thumb = Thumb::new();
+            let mut memory = memory(&[instruction, 0, 0, 0]);
+            assert_eq!(
+                thumb.step(&mut memory),
+                Err(ArmError::Undefined { address: 0, instruction })
+            );
+            assert_eq!(thumb.register(Thumb::LR), 0);
+        }
+    }
+
+    #[test]
+    fn long_branch_with_link() {
+        let mut thumb = Thumb::new();
+        let mut memory = memory(&[0xF000, 0xF802, 0, 0]);
+        run(&mut thumb, &mut memory, 2);
+        assert_eq!(thumb.register(Thumb::LR), 5);
+    }
+
+    // Status reads as NZCV in the top bits, with the Thumb bit set.
+    #[test]
+    fn alu_flags() {
+        let mut thumb = Thumb::new();
+        let mut memory = memory(&[
+            0x2001, // MOVS r0, #1
+            0x1E81, // SUBS r1, r0, #2
+            0x1C4A, // ADDS r2, r1, #1
+            0x07C3, // LSLS r3, r0, #31
+            0x2B01, // CMP r3, #1
+            0x4003, // ANDS r3, r0
+        ]);
+        let mut status = Vec::new();
+        for _ in 0..6 {
+            run(&mut thumb, &mut memory, 1);
+            status.push(thumb.status() >> 28);
+        }
+        assert_eq!(status, [0b0000, 0b1000, 0b0110, 0b1000, 0b0011, 0b0111]);
+        let registers = &thumb.registers()[..4];
+        assert_eq!(registers, [1, 0xFFFF_FFFF, 0, 0]);
+    }
+
+    #[test]
+    fn loads_and_stores() {
+        let mut thumb = Thumb::new();
+        let mut memory = memory(&[
+            0x4803, // LDR r0, [pc, #12]
+            0x21AB, // MOVS r1, #$AB
+            0x7041, // STRB r1, [r0, #1]
+            0x6802, // LDR r2, [r0]
+            0x2401, // MOVS r4, #1
+            0x5703, // LDRSB r3, [r0, r4]
+            0x8045, // STRH r5, [r0, #2]
+            0x0000, 0x0000, // .word RAM
+            0x4000,
+        ]);
+        thumb.set_register(5, 0x1234_5678);
+        run(&mut thumb, &mut memory, 7);
+        assert_eq!(thumb.register(0), RAM);
+        assert_eq!(thumb.register(2), 0x0000_AB00);
+        assert_eq!(thumb.register(3), 0xFFFF_FFAB);
+        assert_eq!(memory.ram[..4], [0x00, 0xAB, 0x78, 0x56]);
+    }
+
+    // One cycle per fetch, two more per load and one per store, plus the
+    // wait states of whatever memory is accessed.
+    #[test]
+    fn cycle_accounting() {
+        let code = [0x4801, 0x6001, 0x6802, 0x0000, 0x0000, 0x4000];
+        let mut thumb = Thumb::new();
+        let mut fast = memory(&code);
+        run(&mut thumb, &mut fast, 3);
+        assert_eq!(thumb.cycles(), 3 + 2 + 3);
+
+        let mut thumb = Thumb::new();
+        let mut slow = memory(&code);
+        slow.wait_states = 1;
+        run(&mut thumb, &mut slow, 3);
+        assert_eq!(thumb.cycles(), 3 + 2 + 3 + 4);
+    }
+
+    #[test]
+    fn branches_refill_the_pipeline() {
+        let mut thumb = Thumb::new();
+        let mut memory = memory(&[
+            0x2000, // MOVS r0, #0
+            0xD000, // BEQ to the next instruction but one
+            0x0000, 0xD1FE, // BNE to itself, not taken
+        ]);
+        run(&mut thumb, &mut memory, 3);
+        assert_eq!(thumb.register(Thumb::PC), 8);
+        assert_eq!(thumb.cycles(), 1 + 3 + 1);
+    }
+
+    // The multiplier takes one cycle for each byte of the first operand
+    // that is not just sign extension.
+    #[test]
+    fn multiply_terminates_early() {
+        for &(value, cycles) in &[
+            (3u32, 1),
+            (0xFFFF_FFFE, 1),
+            (0x1234, 2),
+            (0x12_3456, 3),
+            (0x1234_5678, 4),
+        ] {
+            let mut thumb = Thumb::new();
+            let mut memory = memory(&[0x4348]); // MULS r0, r1
+            thumb.set_register(0, value);
+            thumb.set_register(1, 5);
+            run(&mut thumb, &mut memory, 1);
+            assert_eq!(thumb.register(0), value.wrapping_mul(5));
+            assert_eq!(thumb.cycles(), 1 + cycles, "{:X}", value);
+        }
+    }
+}
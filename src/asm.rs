@@ -0,0 +1,527 @@
+pub mod dasm;
+mod expr;
+mod parse;
+
+use crate::{
+    addrmode::{self, AddrMode},
+    binary::{encode::VecEncoder, Encode},
+    error::{AsmError, AsmErrorKind},
+    instruction::{Instruction, Mnemonic, Opcode, Type},
+    memory::RomBank,
+};
+use expr::{Expr, Scope};
+use parse::{Data, Line, Operand, Statement};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+}
+
+impl Chunk {
+    pub fn end(&self) -> usize {
+        usize::from(self.origin) + self.bytes.len()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Assembly {
+    chunks: Vec<Chunk>,
+    symbols: BTreeMap<String, i64>,
+    instructions: Vec<(u16, Instruction)>,
+}
+
+impl Assembly {
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    pub fn symbols(&self) -> &BTreeMap<String, i64> {
+        &self.symbols
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<i64> {
+        self.symbols.get(name).copied()
+    }
+
+    pub fn instructions(&self) -> &[(u16, Instruction)] {
+        &self.instructions
+    }
+
+    // Gaps between chunks are filled with zeros, and later chunks win
+    // where they overlap earlier ones.
+    pub fn bytes(&self) -> Vec<u8> {
+        match self.span() {
+            Some((start, end)) => self.image(start, end),
+            None => Vec::new(),
+        }
+    }
+
+    // Programs that fit in a bank are placed where the cartridge would see
+    // them: aligned to their own (power of two) size and mirrored across
+    // the bank, as with 2K carts. Larger ones are cut into whole banks.
+    pub fn rom_banks(&self) -> Vec<RomBank> {
+        let (start, end) = match self.span() {
+            Some(span) => span,
+            None => return Vec::new(),
+        };
+        let mut size = (end - start).next_power_of_two();
+        while size <= RomBank::SIZE && start / size != (end - 1) / size {
+            size *= 2;
+        }
+        if size <= RomBank::SIZE {
+            let base = start / size * size;
+            return RomBank::mirrored(&self.image(base, base + size))
+                .into_iter()
+                .collect();
+        }
+
+        let banks = (end - start).div_ceil(RomBank::SIZE);
+        self.image(start, start + banks * RomBank::SIZE)
+            .chunks(RomBank::SIZE)
+            .filter_map(RomBank::try_new)
+            .collect()
+    }
+
+    fn span(&self) -> Option<(usize, usize)> {
+        let chunks = self.chunks.iter().filter(|chunk| !chunk.bytes.is_empty());
+        let start =
+            chunks.clone().map(|chunk| usize::from(chunk.origin)).min()?;
+        let end = chunks.map(Chunk::end).max()?;
+        Some((start, end))
+    }
+
+    fn image(&self, start: usize, end: usize) -> Vec<u8> {
+        let mut image = vec![0; end - start];
+        for chunk in &self.chunks {
+            let offset = usize::from(chunk.origin) - start;
+            image[offset..offset + chunk.bytes.len()]
+                .copy_from_slice(&chunk.bytes);
+        }
+        image
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Assembly, AsmError> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| {
+            parse::parse_line(index + 1, text)
+                .map_err(|kind| AsmError { line: index + 1, kind })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut assembler = Assembler::new(lines.len());
+    assembler.pass(&lines, false)?;
+    assembler.pass(&lines, true)?;
+    Ok(assembler.output)
+}
+
+pub fn instruction(text: &str) -> Result<Instruction, AsmError> {
+    let assembly = assemble(text)?;
+    match assembly.instructions() {
+        [(_, instruction)] if assembly.symbols.is_empty() => Ok(*instruction),
+        _ => Err(AsmError { line: 1, kind: AsmErrorKind::Syntax }),
+    }
+}
+
+// The first pass only sizes things: symbols that are still unknown count as
+// zero, and the addressing mode picked for each instruction is kept so that
+// the second pass lays out the exact same addresses.
+#[derive(Debug)]
+struct Assembler {
+    output: Assembly,
+    modes: Vec<Option<AddrMode>>,
+    defined: BTreeSet<String>,
+    location: i64,
+    final_pass: bool,
+}
+
+impl Scope for Assembler {
+    fn symbol(&self, name: &str) -> Option<i64> {
+        self.output.symbol(name)
+    }
+
+    fn location(&self) -> i64 {
+        self.location
+    }
+}
+
+impl Assembler {
+    const ADDRESSES: i64 = 0x10000;
+
+    fn new(lines: usize) -> Self {
+        Self {
+            output: Assembly::default(),
+            modes: vec![None; lines],
+            defined: BTreeSet::new(),
+            location: 0,
+            final_pass: false,
+        }
+    }
+
+    fn pass(
+        &mut self,
+        lines: &[Line],
+        final_pass: bool,
+    ) -> Result<(), AsmError> {
+        self.final_pass = final_pass;
+        self.location = 0;
+        self.defined.clear();
+        self.output.chunks = vec![Chunk { origin: 0, bytes: Vec::new() }];
+        self.output.instructions.clear();
+
+        for (index, line) in lines.iter().enumerate() {
+            self.line(index, line)
+                .map_err(|kind| AsmError { line: line.number, kind })?;
+        }
+        self.output.chunks.retain(|chunk| !chunk.bytes.is_empty());
+        Ok(())
+    }
+
+    fn line(&mut self, index: usize, line: &Line) -> Result<(), AsmErrorKind> {
+        if let Some(label) = &line.label {
+            match &line.statement {
+                Some(Statement::Assign(expr)) => {
+                    self.declare(label)?;
+                    if let Some(value) = self.eval(expr)? {
+                        self.output.symbols.insert(label.clone(), value);
+                    }
+                    return Ok(());
+                },
+                _ => {
+                    self.declare(label)?;
+                    self.output.symbols.insert(label.clone(), self.location);
+                },
+            }
+        }
+
+        match &line.statement {
+            None | Some(Statement::Assign(_)) => (),
+            Some(Statement::Instruction { mnemonic, operand }) => {
+                self.instruction(index, *mnemonic, operand)?
+            },
+            Some(Statement::Org(expr)) => {
+                let origin = self.known(expr)?;
+                if !(0..Self::ADDRESSES).contains(&origin) {
+                    return Err(AsmErrorKind::Range { value: origin });
+                }
+                self.location = origin;
+                self.output
+                    .chunks
+                    .push(Chunk { origin: origin as u16, bytes: Vec::new() });
+            },
+            Some(Statement::Byte(items)) => {
+                for item in items {
+                    match item {
+                        Data::Expr(expr) => {
+                            let value = self.value(expr)?;
+                            self.emit(&[byte(value)?])?;
+                        },
+                        Data::Text(text) => self.emit(text)?,
+                    }
+                }
+            },
+            Some(Statement::Word(items)) => {
+                for expr in items {
+                    let value = self.value(expr)?;
+                    self.emit(&word(value)?.to_le_bytes())?;
+                }
+            },
+            Some(Statement::Space { count, fill }) => {
+                let count = self.known(count)?;
+                if !(0..Self::ADDRESSES).contains(&count) {
+                    return Err(AsmErrorKind::Range { value: count });
+                }
+                let fill = match fill {
+                    Some(fill) => byte(self.value(fill)?)?,
+                    None => 0,
+                };
+                self.emit(&vec![fill; count as usize])?;
+            },
+        }
+        Ok(())
+    }
+
+    fn declare(&mut self, name: &str) -> Result<(), AsmErrorKind> {
+        if self.defined.insert(name.to_string()) {
+            Ok(())
+        } else {
+            Err(AsmErrorKind::Redefined(name.to_string()))
+        }
+    }
+
+    fn eval(&self, expr: &Expr) -> Result<Option<i64>, AsmErrorKind> {
+        match expr.eval(self)? {
+            None if self.final_pass => self.known(expr).map(Some),
+            value => Ok(value),
+        }
+    }
+
+    fn known(&self, expr: &Expr) -> Result<i64, AsmErrorKind> {
+        expr.eval(self)?.ok_or_else(|| {
+            let name = expr.first_undefined(self).unwrap_or_default();
+            AsmErrorKind::Undefined(name.to_string())
+        })
+    }
+
+    fn value(&self, expr: &Expr) -> Result<i64, AsmErrorKind> {
+        Ok(self.eval(expr)?.unwrap_or(0))
+    }
+
+    fn emit(&mut self, bytes: &[u8]) -> Result<(), AsmErrorKind> {
+        let end = self.location + bytes.len() as i64;
+        if end > Self::ADDRESSES {
+            return Err(AsmErrorKind::Range { value: end });
+        }
+        self.location = end;
+        if self.final_pass {
+            let chunk = self.output.chunks.last_mut().expect("chunk for pass");
+            chunk.bytes.extend_from_slice(bytes);
+        }
+        Ok(())
+    }
+
+    fn instruction(
+        &mut self,
+        index: usize,
+        mnemonic: Mnemonic,
+        operand: &Operand,
+    ) -> Result<(), AsmErrorKind> {
+        let mode = match self.modes[index] {
+            Some(mode) => mode,
+            None => {
+                let fits = match operand.expr() {
+                    Some(expr) => fits_zero_page(expr.eval(self)?),
+                    None => false,
+                };
+                let mode = addrmode(mnemonic, operand, fits)?;
+                self.modes[index] = Some(mode);
+                mode
+            },
+        };
+
+        let mut value = match operand.expr() {
+            Some(expr) => self.value(expr)?,
+            None => 0,
+        };
+        if mode == AddrMode::Rel {
+            value = match branch_offset(value, self.location) {
+                Ok(offset) => offset,
+                Err(error) if self.final_pass => return Err(error),
+                Err(_) => 0,
+            };
+        }
+
+        let (instruction, bytes) = encode(mnemonic, mode, value)?;
+        if self.final_pass {
+            self.output.instructions.push((self.location as u16, instruction));
+        }
+        self.emit(&bytes)
+    }
+}
+
+fn fits_zero_page(value: Option<i64>) -> bool {
+    value.is_some_and(|value| (0..=0xFF).contains(&value))
+}
+
+// Zero page is used whenever the operand is known to fit and the
+// instruction has such a mode; anything else is assumed to need the
+// absolute form.
+fn addrmode(
+    mnemonic: Mnemonic,
+    operand: &Operand,
+    zero_page: bool,
+) -> Result<AddrMode, AsmErrorKind> {
+    let valid = |addrmode| Opcode { mnemonic, addrmode }.to_bits().is_ok();
+    let sized = |small, large| {
+        if zero_page && valid(small) || !valid(large) {
+            small
+        } else {
+            large
+        }
+    };
+
+    let mode = match operand {
+        Operand::None if valid(AddrMode::Impl) => AddrMode::Impl,
+        Operand::None if valid(AddrMode::Acc) => AddrMode::Acc,
+        Operand::None => {
+            return Err(AsmErrorKind::AddrMode { mnemonic, mode: None })
+        },
+        Operand::Acc => AddrMode::Acc,
+        Operand::Imm(_) => AddrMode::Imm,
+        Operand::Direct(_) if mnemonic.instr_type() == Type::Bch => {
+            AddrMode::Rel
+        },
+        Operand::Direct(_) => sized(AddrMode::Zpg, AddrMode::Abs),
+        Operand::X(_) => sized(AddrMode::ZpgX, AddrMode::AbsX),
+        Operand::Y(_) => sized(AddrMode::ZpgY, AddrMode::AbsY),
+        Operand::Ind(_) => AddrMode::Ind,
+        Operand::XInd(_) => AddrMode::XInd,
+        Operand::IndY(_) => AddrMode::IndY,
+    };
+    if valid(mode) {
+        Ok(mode)
+    } else {
+        Err(AsmErrorKind::AddrMode { mnemonic, mode: Some(mode) })
+    }
+}
+
+fn branch_offset(target: i64, location: i64) -> Result<i64, AsmErrorKind> {
+    match target - (location + 2) {
+        offset @ -128..=127 => Ok(offset),
+        offset => Err(AsmErrorKind::BranchRange { offset }),
+    }
+}
+
+// Branch operands are expected to be offsets already.
+fn encode(
+    mnemonic: Mnemonic,
+    mode: AddrMode,
+    value: i64,
+) -> Result<(Instruction, Vec<u8>), AsmErrorKind> {
+    let instruction =
+        Instruction { mnemonic, operand: encode_operand(mode, value)? };
+    let mut bytes = Vec::new();
+    instruction
+        .encode(&mut VecEncoder::new(&mut bytes))
+        .map_err(|_| AsmErrorKind::AddrMode { mnemonic, mode: Some(mode) })?;
+    Ok((instruction, bytes))
+}
+
+fn byte(value: i64) -> Result<u8, AsmErrorKind> {
+    match value {
+        -0x80..=0xFF => Ok(value as u8),
+        _ => Err(AsmErrorKind::Range { value }),
+    }
+}
+
+fn word(value: i64) -> Result<u16, AsmErrorKind> {
+    match value {
+        -0x8000..=0xFFFF => Ok(value as u16),
+        _ => Err(AsmErrorKind::Range { value }),
+    }
+}
+
+fn encode_operand(
+    mode: AddrMode,
+    value: i64,
+) -> Result<addrmode::Operand, AsmErrorKind> {
+    use addrmode::*;
+
+    let operand = match mode {
+        AddrMode::Acc => Operand::Acc(Accumulator),
+        AddrMode::Impl => Operand::Impl(Implied),
+        AddrMode::Imm => Operand::Imm(Immediate { bits: byte(value)? }),
+        AddrMode::Abs => Operand::Abs(Absolute { address: word(value)? }),
+        AddrMode::AbsX => Operand::AbsX(AbsoluteX { address: word(value)? }),
+        AddrMode::AbsY => Operand::AbsY(AbsoluteY { address: word(value)? }),
+        AddrMode::Ind => Operand::Ind(Indirect { address: word(value)? }),
+        AddrMode::XInd => Operand::XInd(XIndirect { address: byte(value)? }),
+        AddrMode::IndY => Operand::IndY(IndirectY { address: byte(value)? }),
+        AddrMode::Rel => Operand::Rel(Relative { address: value as i8 }),
+        AddrMode::Zpg => Operand::Zpg(Zeropage { address: byte(value)? }),
+        AddrMode::ZpgX => Operand::ZpgX(ZeropageX { address: byte(value)? }),
+        AddrMode::ZpgY => Operand::ZpgY(ZeropageY { address: byte(value)? }),
+    };
+    Ok(operand)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::{decode::IoDecoder, Decoder};
+
+    const PROGRAM: &str = "
+        .org $F000
+Start:  sei
+        ldx #<Table
+        lda Table,x
+        sta $80
+        lda ($80),y
+        jsr Sub
+Loop:   dex
+        bne Loop
+        jmp (Vector)
+Sub:    rts
+Table:  .byte 1, 2, \"AB\"
+Vector: .word Start
+        .ds 3, $EA
+        .org $FFFC
+        .word Start, Start
+";
+
+    #[test]
+    fn assembles_program() {
+        let assembly = assemble(PROGRAM).unwrap();
+        let chunks = assembly.chunks();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].origin, 0xF000);
+        assert_eq!(
+            chunks[0].bytes,
+            [
+                0x78, 0xA2, 0x14, 0xBD, 0x14, 0xF0, 0x85, 0x80, 0xB1, 0x80,
+                0x20, 0x13, 0xF0, 0xCA, 0xD0, 0xFD, 0x6C, 0x18, 0xF0, 0x60,
+                0x01, 0x02, 0x41, 0x42, 0x00, 0xF0, 0xEA, 0xEA, 0xEA,
+            ]
+        );
+        assert_eq!(
+            chunks[1],
+            Chunk { origin: 0xFFFC, bytes: vec![0, 0xF0, 0, 0xF0] }
+        );
+
+        let symbols = ["Start", "Loop", "Sub", "Table", "Vector"]
+            .iter()
+            .map(|name| assembly.symbol(name))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            symbols,
+            [
+                Some(0xF000),
+                Some(0xF00D),
+                Some(0xF013),
+                Some(0xF014),
+                Some(0xF018)
+            ]
+        );
+    }
+
+    #[test]
+    fn instructions_decode_from_their_bytes() {
+        let assembly = assemble(PROGRAM).unwrap();
+        let bytes = assembly.bytes();
+        assert_eq!(bytes.len(), 0x1000);
+        assert_eq!(assembly.instructions().len(), 10);
+        for &(address, instruction) in assembly.instructions() {
+            let offset = usize::from(address - 0xF000);
+            let mut decoder = IoDecoder::new(&bytes[offset..]);
+            assert_eq!(decoder.decode::<Instruction>().unwrap(), instruction);
+        }
+
+        let banks = assembly.rom_banks();
+        assert_eq!(banks.len(), 1);
+        assert_eq!(banks[0].read(0x1FFD).unwrap(), 0xF0);
+    }
+
+    // Every documented opcode, printed and assembled again.
+    #[test]
+    fn display_round_trip() {
+        for bits in 0..=0xFF {
+            let bytes = [bits, 0x12, 0x34];
+            let mut decoder = IoDecoder::new(&bytes[..]);
+            let decoded = match decoder.decode::<Instruction>() {
+                Ok(decoded) => decoded,
+                Err(_) => continue,
+            };
+            let text = decoded.to_string();
+            let assembled = instruction(&text).unwrap();
+            assert_eq!(assembled, decoded, "{}", text);
+
+            let mut encoded = Vec::new();
+            assembled.encode(&mut VecEncoder::new(&mut encoded)).unwrap();
+            assert_eq!(encoded, bytes[..encoded.len()], "{}", text);
+        }
+    }
+}
@@ -0,0 +1,935 @@
+use crate::{
+    addrmode::AddrMode,
+    asm::{
+        addrmode, branch_offset, byte, encode,
+        expr::{is_identifier, Dialect, Expr, Scope},
+        fits_zero_page,
+        parse::{
+            parse_data, parse_operand, split_list, split_word, strip_comment,
+            Data, Operand,
+        },
+        word, Assembly, Chunk,
+    },
+    error::{AsmError, AsmErrorKind},
+    instruction::{Instruction, Mnemonic},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::Path,
+    rc::Rc,
+};
+
+const VCS_H: &str = include_str!("dasm/vcs.h");
+const MACRO_H: &str = include_str!("dasm/macro.h");
+
+// Includes are looked up through the given function first; vcs.h and
+// macro.h fall back to built-in copies, since most projects expect them to
+// come with the assembler.
+pub fn assemble<F>(source: &str, include: F) -> Result<Assembly, AsmError>
+where
+    F: FnMut(&str) -> Option<Vec<u8>>,
+{
+    let lines = source_lines(source);
+    let mut dasm = Dasm::new(include);
+    let mut settled = false;
+    let mut pass = 0;
+    loop {
+        pass += 1;
+        let strict = settled || pass > Dasm::<F>::PASSES;
+        dasm.pass(&lines, strict)?;
+        if strict {
+            break Ok(dasm.assembly());
+        }
+        settled = !dasm.changed && !dasm.unknown;
+    }
+}
+
+pub fn assemble_file<P>(path: P) -> io::Result<Assembly>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let source = fs::read(path)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let assembly = assemble(&String::from_utf8_lossy(&source), |name| {
+        fs::read(directory.join(name)).ok()
+    })?;
+    Ok(assembly)
+}
+
+#[derive(Debug, Clone)]
+struct SourceLine {
+    number: usize,
+    text: String,
+}
+
+fn source_lines(source: &str) -> Vec<SourceLine> {
+    source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| SourceLine {
+            number: index + 1,
+            text: text.into(),
+        })
+        .collect()
+}
+
+// Anything in the first column is a label, with or without a colon.
+#[derive(Debug, Clone, Copy)]
+struct Fields<'text> {
+    label: Option<&'text str>,
+    operation: &'text str,
+    operand: &'text str,
+}
+
+impl<'text> Fields<'text> {
+    fn new(text: &'text str) -> Self {
+        let text = strip_comment(text).trim_end();
+        let mut rest = text.trim_start();
+        let mut label = None;
+        if rest.len() == text.len() && !rest.is_empty() {
+            let end = rest
+                .find(|letter: char| letter.is_whitespace() || letter == '=')
+                .unwrap_or(rest.len());
+            label = Some(rest[..end].trim_end_matches(':'));
+            rest = rest[end..].trim_start();
+        }
+        let (operation, operand) = match rest.strip_prefix('=') {
+            Some(operand) => ("=", operand),
+            None => split_word(rest),
+        };
+        Self { label, operation, operand: operand.trim() }
+    }
+
+    // Directives may be written with a leading dot, as in `.byte`.
+    fn keyword(&self) -> String {
+        self.operation.trim_start_matches('.').to_ascii_lowercase()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    If,
+    Repeat,
+    Macro,
+}
+
+impl Block {
+    fn keywords(self) -> (&'static [&'static str], &'static [&'static str]) {
+        match self {
+            Block::If => (&["if", "ifconst", "ifnconst"], &["endif", "eif"]),
+            Block::Repeat => (&["repeat"], &["repend"]),
+            Block::Macro => (&["mac", "macro"], &["endm"]),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Block::If => "IF",
+            Block::Repeat => "REPEAT",
+            Block::Macro => "MAC",
+        }
+    }
+
+    // Returns the closing line of the block opened at `start`, along with
+    // an ELSE at the same depth if there is one.
+    fn end(
+        self,
+        lines: &[SourceLine],
+        start: usize,
+    ) -> Result<(Option<usize>, usize), AsmErrorKind> {
+        let (open, close) = self.keywords();
+        let mut depth = 0usize;
+        let mut middle = None;
+        for (index, line) in lines.iter().enumerate().skip(start + 1) {
+            let keyword = Fields::new(&line.text).keyword();
+            if open.contains(&keyword.as_str()) {
+                depth += 1;
+            } else if close.contains(&keyword.as_str()) {
+                if depth == 0 {
+                    return Ok((middle, index));
+                }
+                depth -= 1;
+            } else if depth == 0 && self == Block::If && keyword == "else" {
+                middle = middle.or(Some(index));
+            }
+        }
+        Err(AsmErrorKind::Unbalanced(self.name().to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Next,
+    Exit,
+    End,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Segment {
+    org: i64,
+    rorg: Option<i64>,
+    uninitialized: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Width {
+    Byte,
+    Word,
+    Long,
+}
+
+impl Width {
+    fn from_suffix(keyword: &str) -> Option<Self> {
+        match keyword.split('.').nth(1) {
+            None | Some("b") => Some(Width::Byte),
+            Some("w") => Some(Width::Word),
+            Some("l") => Some(Width::Long),
+            Some(_) => None,
+        }
+    }
+
+    fn bytes(self, value: i64) -> Result<Vec<u8>, AsmErrorKind> {
+        match self {
+            Width::Byte => Ok(vec![byte(value)?]),
+            Width::Word => Ok(word(value)?.to_le_bytes().to_vec()),
+            Width::Long => match value {
+                -0x8000_0000..=0xFFFF_FFFF => {
+                    Ok((value as u32).to_le_bytes().to_vec())
+                },
+                _ => Err(AsmErrorKind::Range { value }),
+            },
+        }
+    }
+}
+
+// Symbols keep their values from one pass to the next, so forward
+// references resolve on the next pass; passes repeat until no symbol
+// changes and nothing is left unknown, and a last strict pass produces the
+// output and reports whatever is still wrong.
+struct Dasm<F> {
+    include: F,
+    files: BTreeMap<String, Rc<[u8]>>,
+    macros: BTreeMap<String, Rc<[SourceLine]>>,
+    symbols: BTreeMap<String, i64>,
+    defined: BTreeSet<String>,
+    segments: BTreeMap<String, Segment>,
+    segment: String,
+    scope: usize,
+    scopes: usize,
+    expansions: usize,
+    fill: u8,
+    start: Option<i64>,
+    output: Vec<u8>,
+    instructions: Vec<(u16, Instruction)>,
+    strict: bool,
+    changed: bool,
+    unknown: bool,
+}
+
+impl<F> Scope for Dasm<F>
+where
+    F: FnMut(&str) -> Option<Vec<u8>>,
+{
+    fn symbol(&self, name: &str) -> Option<i64> {
+        self.symbols.get(&self.resolve(name)).copied()
+    }
+
+    fn location(&self) -> i64 {
+        let segment = &self.segments[&self.segment];
+        segment.rorg.unwrap_or(segment.org)
+    }
+}
+
+impl<F> Dasm<F>
+where
+    F: FnMut(&str) -> Option<Vec<u8>>,
+{
+    const PASSES: usize = 10;
+    const DEPTH: usize = 64;
+    const ORG_FILL: u8 = 0xFF;
+    const ADDRESSES: i64 = 0x10000;
+
+    fn new(include: F) -> Self {
+        Self {
+            include,
+            files: BTreeMap::new(),
+            macros: BTreeMap::new(),
+            symbols: BTreeMap::new(),
+            defined: BTreeSet::new(),
+            segments: BTreeMap::new(),
+            segment: String::new(),
+            scope: 0,
+            scopes: 0,
+            expansions: 0,
+            fill: Self::ORG_FILL,
+            start: None,
+            output: Vec::new(),
+            instructions: Vec::new(),
+            strict: false,
+            changed: false,
+            unknown: false,
+        }
+    }
+
+    fn pass(
+        &mut self,
+        lines: &[SourceLine],
+        strict: bool,
+    ) -> Result<(), AsmError> {
+        self.strict = strict;
+        self.changed = false;
+        self.unknown = false;
+        self.defined.clear();
+        self.segments.clear();
+        self.segment.clear();
+        self.segments.insert(String::new(), Segment::default());
+        self.scope = 0;
+        self.scopes = 0;
+        self.expansions = 0;
+        self.fill = Self::ORG_FILL;
+        self.start = None;
+        self.output.clear();
+        self.instructions.clear();
+        self.run(lines, 0)?;
+        Ok(())
+    }
+
+    // Local symbols (starting with a dot) are kept apart per scope.
+    fn resolve(&self, name: &str) -> String {
+        if name.starts_with('.') {
+            format!("{}@{}", name, self.scope)
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn assembly(&mut self) -> Assembly {
+        let mut assembly = Assembly::default();
+        if let Some(start) = self.start {
+            let bytes = std::mem::take(&mut self.output);
+            assembly.chunks.push(Chunk { origin: start as u16, bytes });
+        }
+        assembly.symbols = self
+            .symbols
+            .iter()
+            .filter(|(name, _)| !name.starts_with('.'))
+            .map(|(name, &value)| (name.clone(), value))
+            .collect();
+        assembly.instructions = std::mem::take(&mut self.instructions);
+        assembly
+    }
+
+    fn run(
+        &mut self,
+        lines: &[SourceLine],
+        depth: usize,
+    ) -> Result<Flow, AsmError> {
+        let mut index = 0;
+        while index < lines.len() {
+            let line = &lines[index];
+            let at = |kind| AsmError { line: line.number, kind };
+            let fields = Fields::new(&line.text);
+            let keyword = fields.keyword();
+            let flow = match keyword.as_str() {
+                "if" | "ifconst" | "ifnconst" => {
+                    let (middle, end) =
+                        Block::If.end(lines, index).map_err(at)?;
+                    self.label(&fields).map_err(at)?;
+                    let condition =
+                        self.condition(&keyword, fields.operand).map_err(at)?;
+                    let body = match (condition, middle) {
+                        (true, Some(middle)) => &lines[index + 1..middle],
+                        (true, None) => &lines[index + 1..end],
+                        (false, Some(middle)) => &lines[middle + 1..end],
+                        (false, None) => &[],
+                    };
+                    index = end;
+                    self.run(body, depth)?
+                },
+                "repeat" => {
+                    let (_, end) =
+                        Block::Repeat.end(lines, index).map_err(at)?;
+                    self.label(&fields).map_err(at)?;
+                    let count = self.value(fields.operand).map_err(at)?;
+                    let body = &lines[index + 1..end];
+                    index = end;
+                    let mut flow = Flow::Next;
+                    for _ in 0..count.max(0) {
+                        flow = self.run(body, depth)?;
+                        if flow != Flow::Next {
+                            break;
+                        }
+                    }
+                    flow
+                },
+                "mac" | "macro" => {
+                    let (_, end) =
+                        Block::Macro.end(lines, index).map_err(at)?;
+                    let name = fields.operand.to_ascii_lowercase();
+                    self.macros.insert(name, lines[index + 1..end].into());
+                    index = end;
+                    Flow::Next
+                },
+                "else" | "endif" | "eif" | "repend" | "endm" => {
+                    let directive = keyword.to_ascii_uppercase();
+                    return Err(at(AsmErrorKind::Unbalanced(directive)));
+                },
+                _ => self.statement(line, &fields, &keyword, depth)?,
+            };
+            if flow != Flow::Next {
+                return Ok(flow);
+            }
+            index += 1;
+        }
+        Ok(Flow::Next)
+    }
+
+    fn statement(
+        &mut self,
+        line: &SourceLine,
+        fields: &Fields,
+        keyword: &str,
+        depth: usize,
+    ) -> Result<Flow, AsmError> {
+        let at = |kind| AsmError { line: line.number, kind };
+        let operand = fields.operand;
+        match keyword {
+            "include" => {
+                self.label(fields).map_err(at)?;
+                if depth >= Self::DEPTH {
+                    return Err(at(AsmErrorKind::Nesting));
+                }
+                let source = self.load(operand).map_err(at)?;
+                let lines = source_lines(&String::from_utf8_lossy(&source));
+                match self.run(&lines, depth + 1)? {
+                    Flow::End => return Ok(Flow::End),
+                    _ => return Ok(Flow::Next),
+                }
+            },
+            "mexit" if self.expansions == 0 => {
+                return Err(at(AsmErrorKind::Unbalanced("MEXIT".to_string())));
+            },
+            "mexit" => return Ok(Flow::Exit),
+            "end" => return Ok(Flow::End),
+            _ => (),
+        }
+
+        if let Some(body) = self.macros.get(keyword).cloned() {
+            self.label(fields).map_err(at)?;
+            if depth >= Self::DEPTH {
+                return Err(at(AsmErrorKind::Nesting));
+            }
+            let lines = expand(&body, operand);
+            let scope = self.scope;
+            self.scopes += 1;
+            self.scope = self.scopes;
+            self.expansions += 1;
+            let flow = self.run(&lines, depth + 1)?;
+            self.expansions -= 1;
+            self.scope = scope;
+            return Ok(if flow == Flow::End { Flow::End } else { Flow::Next });
+        }
+
+        self.directive(fields, keyword).map_err(at)?;
+        Ok(Flow::Next)
+    }
+
+    fn directive(
+        &mut self,
+        fields: &Fields,
+        keyword: &str,
+    ) -> Result<(), AsmErrorKind> {
+        let operand = fields.operand;
+        match keyword {
+            "=" | "equ" | "set" => {
+                let label = fields.label.ok_or(AsmErrorKind::Syntax)?;
+                if let Some(value) = self.eval(operand)? {
+                    self.define(label, value, keyword == "set")?;
+                }
+                return Ok(());
+            },
+            "seg" | "seg.u" => {
+                self.segment = operand.to_string();
+                let segment = self.segments.entry(operand.into()).or_default();
+                segment.uninitialized = keyword == "seg.u";
+            },
+            "org" => {
+                let mut items = split_list(operand).into_iter();
+                let origin = self.address(items.next().unwrap_or(""))?;
+                if let Some(fill) = items.next() {
+                    self.fill = byte(self.value(fill)?)?;
+                }
+                self.current().org = origin;
+            },
+            "rorg" => {
+                let origin = self.address(operand)?;
+                self.current().rorg = Some(origin);
+            },
+            "rend" => self.current().rorg = None,
+            "align" => {
+                let mut items = split_list(operand).into_iter();
+                let boundary = self.value(items.next().unwrap_or(""))?;
+                let fill = match items.next() {
+                    Some(fill) => byte(self.value(fill)?)?,
+                    None => 0,
+                };
+                if boundary > 0 {
+                    let padding = (boundary - self.location() % boundary)
+                        .rem_euclid(boundary);
+                    self.generate(&vec![fill; padding as usize])?;
+                }
+            },
+            "subroutine" => {
+                self.scopes += 1;
+                self.scope = self.scopes;
+            },
+            _ => {
+                self.label(fields)?;
+                return self.operation(fields, keyword);
+            },
+        }
+        self.label(fields)
+    }
+
+    fn operation(
+        &mut self,
+        fields: &Fields,
+        keyword: &str,
+    ) -> Result<(), AsmErrorKind> {
+        let operand = fields.operand;
+        match keyword {
+            "" | "list" | "trace" | "echo" => (),
+            "processor" if operand == "6502" => (),
+            "processor" => return Err(AsmErrorKind::Syntax),
+            "err" if self.strict => return Err(AsmErrorKind::Stopped),
+            "err" => (),
+            "dc" | "dc.b" | "dc.w" | "dc.l" | "byte" | "word" | "long" => {
+                let width = match keyword {
+                    "word" => Width::Word,
+                    "long" => Width::Long,
+                    _ => Width::from_suffix(keyword)
+                        .ok_or(AsmErrorKind::Syntax)?,
+                };
+                for item in split_list(operand) {
+                    match parse_data(item, Dialect::Dasm)? {
+                        Data::Expr(expr) => {
+                            let value = self.eval_expr(&expr)?.unwrap_or(0);
+                            let bytes = self
+                                .lenient(width.bytes(value), || {
+                                    width.bytes(0)
+                                })?;
+                            self.generate(&bytes)?;
+                        },
+                        Data::Text(text) => {
+                            for &letter in &text {
+                                self.generate(&width.bytes(letter.into())?)?;
+                            }
+                        },
+                    }
+                }
+            },
+            "ds" | "ds.b" | "ds.w" | "ds.l" => {
+                let width =
+                    Width::from_suffix(keyword).ok_or(AsmErrorKind::Syntax)?;
+                let mut items = split_list(operand).into_iter();
+                let count = self.value(items.next().unwrap_or(""))?;
+                let fill = match items.next() {
+                    Some(fill) => self.value(fill)?,
+                    None => 0,
+                };
+                let item =
+                    self.lenient(width.bytes(fill), || width.bytes(0))?;
+                if !(0..Self::ADDRESSES).contains(&count) {
+                    return self.lenient(
+                        Err(AsmErrorKind::Range { value: count }),
+                        || Ok(()),
+                    );
+                }
+                self.generate(&item.repeat(count as usize))?;
+            },
+            "hex" => {
+                let digits: Vec<u8> = operand
+                    .bytes()
+                    .filter(|byte| !byte.is_ascii_whitespace())
+                    .collect();
+                let bytes = digits
+                    .chunks(2)
+                    .map(|pair| {
+                        std::str::from_utf8(pair)
+                            .ok()
+                            .filter(|pair| pair.len() == 2)
+                            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                            .ok_or(AsmErrorKind::Syntax)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.generate(&bytes)?;
+            },
+            "incbin" => {
+                let bytes = self.load(operand)?;
+                self.generate(&bytes)?;
+            },
+            _ => self.instruction(fields.operation, operand)?,
+        }
+        Ok(())
+    }
+
+    // A suffix on the mnemonic forces the operand size: `.b` or `.z` for
+    // zero page, `.w` or `.a` for absolute.
+    fn instruction(
+        &mut self,
+        operation: &str,
+        text: &str,
+    ) -> Result<(), AsmErrorKind> {
+        let unknown = || AsmErrorKind::UnknownMnemonic(operation.to_string());
+        let (name, suffix) = match operation.find('.') {
+            Some(dot) => (&operation[..dot], Some(&operation[dot + 1..])),
+            None => (operation, None),
+        };
+        let mnemonic = Mnemonic::from_name(name).ok_or_else(unknown)?;
+        let operand = parse_operand(mnemonic, text, Dialect::Dasm)?;
+        let value = match operand.expr() {
+            Some(expr) => self.eval_expr(expr)?,
+            None => Some(0),
+        };
+        let zero_page = match suffix.map(str::to_ascii_lowercase).as_deref() {
+            None => fits_zero_page(value),
+            Some("b") | Some("z") => true,
+            Some("w") | Some("a") => false,
+            Some(_) => return Err(unknown()),
+        };
+
+        let value = value.unwrap_or(0);
+        if mnemonic == Mnemonic::Nop && operand != Operand::None {
+            return self.undocumented_nop(&operand, value, zero_page);
+        }
+        let mode = addrmode(mnemonic, &operand, zero_page)?;
+        let location = self.location();
+        let value = if mode == AddrMode::Rel {
+            self.lenient(branch_offset(value, location), || Ok(0))?
+        } else {
+            value
+        };
+        let (instruction, bytes) = self
+            .lenient(encode(mnemonic, mode, value), || {
+                encode(mnemonic, mode, 0)
+            })?;
+        if self.strict && !self.current().uninitialized {
+            self.instructions.push((location as u16, instruction));
+        }
+        self.generate(&bytes)
+    }
+
+    // DASM takes NOP with an operand too, producing one of the undocumented
+    // NOPs; SLEEP in macro.h relies on that for odd cycle counts.
+    fn undocumented_nop(
+        &mut self,
+        operand: &Operand,
+        value: i64,
+        zero_page: bool,
+    ) -> Result<(), AsmErrorKind> {
+        let (opcode, width) = match operand {
+            Operand::Imm(_) => (0x80, Width::Byte),
+            Operand::Direct(_) if zero_page => (0x04, Width::Byte),
+            Operand::Direct(_) => (0x0C, Width::Word),
+            Operand::X(_) if zero_page => (0x14, Width::Byte),
+            Operand::X(_) => (0x1C, Width::Word),
+            _ => {
+                return Err(AsmErrorKind::AddrMode {
+                    mnemonic: Mnemonic::Nop,
+                    mode: None,
+                })
+            },
+        };
+        let mut bytes = vec![opcode];
+        bytes.extend(self.lenient(width.bytes(value), || width.bytes(0))?);
+        self.generate(&bytes)
+    }
+
+    fn condition(
+        &mut self,
+        keyword: &str,
+        operand: &str,
+    ) -> Result<bool, AsmErrorKind> {
+        let expr = Expr::parse(operand, Dialect::Dasm)?;
+        match keyword {
+            "ifconst" => Ok(expr.eval(&*self)?.is_some()),
+            "ifnconst" => Ok(expr.eval(&*self)?.is_none()),
+            _ => Ok(self.eval_expr(&expr)?.is_some_and(|value| value != 0)),
+        }
+    }
+
+    fn label(&mut self, fields: &Fields) -> Result<(), AsmErrorKind> {
+        match fields.label {
+            Some(label) => self.define(label, self.location(), false),
+            None => Ok(()),
+        }
+    }
+
+    fn define(
+        &mut self,
+        name: &str,
+        value: i64,
+        redefinable: bool,
+    ) -> Result<(), AsmErrorKind> {
+        let bytes = name.as_bytes();
+        if bytes.first().is_some_and(u8::is_ascii_digit)
+            || !bytes.iter().all(|&byte| is_identifier(byte))
+        {
+            return Err(AsmErrorKind::Syntax);
+        }
+        let key = self.resolve(name);
+        let previous = self.symbols.insert(key.clone(), value);
+        if redefinable {
+            return Ok(());
+        }
+        if !self.defined.insert(key) && previous != Some(value) {
+            return Err(AsmErrorKind::Redefined(name.to_string()));
+        }
+        if previous != Some(value) {
+            self.changed = true;
+        }
+        Ok(())
+    }
+
+    fn eval(&mut self, text: &str) -> Result<Option<i64>, AsmErrorKind> {
+        let expr = Expr::parse(text, Dialect::Dasm)?;
+        self.eval_expr(&expr)
+    }
+
+    fn eval_expr(&mut self, expr: &Expr) -> Result<Option<i64>, AsmErrorKind> {
+        match expr.eval(&*self)? {
+            Some(value) => Ok(Some(value)),
+            None if self.strict => {
+                let name = expr.first_undefined(&*self).unwrap_or_default();
+                Err(AsmErrorKind::Undefined(name.to_string()))
+            },
+            None => {
+                self.unknown = true;
+                Ok(None)
+            },
+        }
+    }
+
+    fn value(&mut self, text: &str) -> Result<i64, AsmErrorKind> {
+        Ok(self.eval(text)?.unwrap_or(0))
+    }
+
+    fn address(&mut self, text: &str) -> Result<i64, AsmErrorKind> {
+        let value = self.value(text)?;
+        let result = if (0..Self::ADDRESSES).contains(&value) {
+            Ok(value)
+        } else {
+            Err(AsmErrorKind::Range { value })
+        };
+        self.lenient(result, || Ok(value & 0xFFFF))
+    }
+
+    // Values that are out of range before the last pass may just come
+    // from symbols that have not settled yet.
+    fn lenient<T, G>(
+        &self,
+        result: Result<T, AsmErrorKind>,
+        fallback: G,
+    ) -> Result<T, AsmErrorKind>
+    where
+        G: FnOnce() -> Result<T, AsmErrorKind>,
+    {
+        match result {
+            Err(AsmErrorKind::Range { .. })
+            | Err(AsmErrorKind::BranchRange { .. })
+                if !self.strict =>
+            {
+                fallback()
+            },
+            result => result,
+        }
+    }
+
+    fn current(&mut self) -> &mut Segment {
+        self.segments.get_mut(&self.segment).expect("current segment")
+    }
+
+    // Output is a plain image, as with DASM's `-f3`: it starts at the first
+    // byte generated, and moving the origin forward fills the gap with the
+    // ORG fill value.
+    fn generate(&mut self, bytes: &[u8]) -> Result<(), AsmErrorKind> {
+        let strict = self.strict;
+        let segment = self.current();
+        let origin = segment.org;
+        let uninitialized = segment.uninitialized;
+        for start in segment.rorg.iter().chain(Some(&origin)) {
+            let end = start + bytes.len() as i64;
+            if strict && end > Self::ADDRESSES {
+                return Err(AsmErrorKind::Range { value: end });
+            }
+        }
+        segment.org += bytes.len() as i64;
+        if let Some(rorg) = &mut segment.rorg {
+            *rorg += bytes.len() as i64;
+        }
+        if !strict || uninitialized || bytes.is_empty() {
+            return Ok(());
+        }
+
+        let start = match self.start {
+            Some(start) => start,
+            None if (0..Self::ADDRESSES).contains(&origin) => {
+                self.start = Some(origin);
+                origin
+            },
+            None => return Err(AsmErrorKind::Range { value: origin }),
+        };
+        let offset = origin - start;
+        if offset < self.output.len() as i64 {
+            return Err(AsmErrorKind::Origin { origin });
+        }
+        self.output.resize(offset as usize, self.fill);
+        self.output.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn load(&mut self, operand: &str) -> Result<Rc<[u8]>, AsmErrorKind> {
+        let name = operand.trim().trim_matches('"');
+        if let Some(file) = self.files.get(name) {
+            return Ok(file.clone());
+        }
+        let builtin = match Path::new(name).file_name() {
+            Some(file) if file.eq_ignore_ascii_case("vcs.h") => Some(VCS_H),
+            Some(file) if file.eq_ignore_ascii_case("macro.h") => Some(MACRO_H),
+            _ => None,
+        };
+        let file: Rc<[u8]> = match (self.include)(name) {
+            Some(bytes) => bytes.into(),
+            None => builtin
+                .ok_or_else(|| AsmErrorKind::Include(name.to_string()))?
+                .as_bytes()
+                .into(),
+        };
+        self.files.insert(name.to_string(), file.clone());
+        Ok(file)
+    }
+}
+
+// `{0}` stands for the whole argument list, `{1}` onwards for each of its
+// items; arguments that were left out expand to nothing.
+fn expand(body: &[SourceLine], arguments: &str) -> Vec<SourceLine> {
+    let items = split_list(arguments);
+    body.iter()
+        .map(|line| {
+            let mut text = line.text.replace("{0}", arguments);
+            for index in 0..items.len().max(9) {
+                let item = items.get(index).copied().unwrap_or("");
+                text = text.replace(&format!("{{{}}}", index + 1), item);
+            }
+            SourceLine { number: line.number, text }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> AsmError {
+        assemble(source, |_| None).unwrap_err()
+    }
+
+    #[test]
+    fn origin_out_of_range() {
+        let source =
+            " processor 6502\n org $F000\n nop\n org $7FFFFFFF\n nop\n";
+        let kind = AsmErrorKind::Range { value: 0x7FFF_FFFF };
+        assert_eq!(error(source), AsmError { line: 4, kind });
+    }
+
+    #[test]
+    fn relocated_origin_out_of_range() {
+        let source = " org $F000\n rorg $10000\n nop\n";
+        let kind = AsmErrorKind::Range { value: 0x10000 };
+        assert_eq!(error(source), AsmError { line: 2, kind });
+    }
+
+    #[test]
+    fn code_past_end_of_memory() {
+        let source = " org $FFFE\n jmp $F000\n";
+        let kind = AsmErrorKind::Range { value: 0x10001 };
+        assert_eq!(error(source), AsmError { line: 2, kind });
+    }
+
+    #[test]
+    fn mexit_outside_macro() {
+        let source = " org $F000\n nop\n mexit\n nop\n";
+        let kind = AsmErrorKind::Unbalanced("MEXIT".to_string());
+        assert_eq!(error(source), AsmError { line: 3, kind });
+    }
+
+    #[test]
+    fn mexit_leaves_macro() {
+        let source =
+            " mac twice\n nop\n mexit\n nop\n endm\n org $F000\n twice\n rts\n";
+        assert_eq!(assemble(source, |_| None).unwrap().bytes(), &[0xEA, 0x60]);
+    }
+
+    // Images as DASM writes them with -f3: raw bytes from the first origin
+    // to the last byte, with the ORG fill value in the gaps.
+    fn image(size: usize, pieces: &[(usize, &[u8])]) -> Vec<u8> {
+        let mut image = vec![0xFF; size];
+        for &(offset, bytes) in pieces {
+            image[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+        image
+    }
+
+    #[test]
+    fn kernel_fixture() {
+        let source = include_str!("dasm/fixtures/kernel.asm");
+        let assembly = assemble(source, |_| None).unwrap();
+        let code: &[u8] = &[
+            0x78, 0xD8, 0xA2, 0x00, 0x8A, 0xA8, 0xCA, 0x9A, 0x48, 0xD0, 0xFB,
+            0xA9, 0x0E, 0x85, 0x02, 0x85, 0x00, 0x4A, 0xD0, 0xF9, 0xA9, 0x2B,
+            0x8D, 0x96, 0x02, 0xE6, 0x80, 0xAD, 0x84, 0x02, 0xD0, 0xFB, 0x85,
+            0x02, 0x85, 0x01, 0xA2, 0xC0, 0xA5, 0x80, 0x85, 0x81, 0x85, 0x02,
+            0x86, 0x09, 0x04, 0x00, 0xEA, 0xEA, 0xCA, 0xD0, 0xF5, 0xA9, 0x02,
+            0x85, 0x01, 0xA2, 0x1E, 0x85, 0x02, 0xCA, 0xD0, 0xFB, 0x4C, 0x0B,
+            0xF0,
+        ];
+        let vectors: &[u8] = &[0x00, 0xF0, 0x00, 0xF0];
+        assert_eq!(
+            assembly.bytes(),
+            image(0x1000, &[(0, code), (0xFFC, vectors)])
+        );
+        assert_eq!(assembly.symbol("StartFrame"), Some(0xF00B));
+        assert_eq!(assembly.symbol("Color"), Some(0x81));
+    }
+
+    #[test]
+    fn bank_switched_fixture() {
+        let source = include_str!("dasm/fixtures/bankswitch.asm");
+        let assembly = assemble(source, |_| None).unwrap();
+        let bank0: &[u8] = &[
+            0xAD, 0xF9, 0x1F, 0x20, 0x09, 0xF0, 0x4C, 0x00, 0xF0, 0xA2, 0x04,
+            0x85, 0x02, 0xCA, 0xD0, 0xFB, 0x60,
+        ];
+        let table: &[u8] = &[0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55];
+        let bank1: &[u8] =
+            &[0xAD, 0xF8, 0x1F, 0xA0, 0x02, 0x88, 0xD0, 0xFD, 0x4C, 0x00, 0xF0];
+        let vectors: &[u8] = &[0x00, 0xF0, 0x00, 0xF0];
+        // ALIGN pads with zeros, unlike ORG.
+        let expected = image(
+            0x2000,
+            &[
+                (0, bank0),
+                (bank0.len(), &[0; 0x100][bank0.len()..]),
+                (0x100, table),
+                (0xFFC, vectors),
+                (0x1000, bank1),
+                (0x1FFC, vectors),
+            ],
+        );
+        assert_eq!(assembly.bytes(), expected);
+        assert_eq!(assembly.symbol("Table0"), Some(0xF100));
+        assert_eq!(assembly.symbol("Kernel1"), Some(0xF003));
+    }
+}
@@ -0,0 +1,52 @@
+        processor 6502
+        include "vcs.h"
+
+BANKS   = 2
+
+        seg Bank0
+        org $0000
+        rorg $F000
+Reset0
+        lda $1FF9
+        jsr Kernel0
+        jmp Reset0
+
+Kernel0 SUBROUTINE
+        ldx #4
+.loop
+        sta WSYNC
+        dex
+        bne .loop
+        rts
+
+        ALIGN 256
+Table0
+        REPEAT 4
+        .byte $AA, $55
+        REPEND
+
+        org $0FFC
+        rorg $FFFC
+        .word Reset0
+        .word Reset0
+
+        seg Bank1
+        org $1000
+        rorg $F000
+Reset1
+        lda $1FF8
+Kernel1 SUBROUTINE
+        ldy #2
+.loop
+        dey
+        bne .loop
+        IF BANKS > 1
+        jmp Reset1
+        ELSE
+        brk
+        ENDIF
+
+        org $1FFC
+        rorg $FFFC
+        .word Reset1
+        .word Reset1
@@ -0,0 +1,44 @@
+        processor 6502
+        include "vcs.h"
+        include "macro.h"
+
+        seg.u Variables
+        org $80
+Frame   ds 1
+Color   ds 1
+
+        seg Code
+        org $F000
+Reset
+        CLEAN_START
+StartFrame
+        VERTICAL_SYNC
+        lda #43
+        sta TIM64T
+        inc Frame
+WaitVBlank
+        lda INTIM
+        bne WaitVBlank
+        sta WSYNC
+        sta VBLANK
+        ldx #192
+        lda Frame
+        sta Color
+Picture
+        sta WSYNC
+        stx COLUBK
+        SLEEP 7
+        dex
+        bne Picture
+        lda #2
+        sta VBLANK
+        ldx #30
+Overscan
+        sta WSYNC
+        dex
+        bne Overscan
+        jmp StartFrame
+
+        org $FFFC
+        .word Reset
+        .word Reset
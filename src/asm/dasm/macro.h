@@ -0,0 +1,57 @@
+; The usual helper macros, used when a project does not ship its own copy
+; of macro.h.
+
+VERSION_MACRO = 108
+
+            MAC SLEEP
+.CYCLES     SET {1}
+
+                IF .CYCLES < 2
+                    ECHO "MACRO ERROR: 'SLEEP': Duration must be > 1"
+                    ERR
+                ENDIF
+
+                IF .CYCLES & 1
+                    IFNCONST NO_ILLEGAL_OPCODES
+                        nop 0
+                    ELSE
+                        bit VSYNC
+                    ENDIF
+.CYCLES             SET .CYCLES - 3
+                ENDIF
+
+                REPEAT .CYCLES / 2
+                    nop
+                REPEND
+            ENDM
+
+            MAC VERTICAL_SYNC
+                lda #%1110
+.VSLP1          sta WSYNC
+                sta VSYNC
+                lsr
+                bne .VSLP1
+            ENDM
+
+            MAC CLEAN_START
+                sei
+                cld
+
+                ldx #0
+                txa
+                tay
+.CLEAR_STACK    dex
+                txs
+                pha
+                bne .CLEAR_STACK
+            ENDM
+
+            MAC SET_POINTER
+.POINTER    SET {1}
+.ADDRESS    SET {2}
+
+                lda #<.ADDRESS
+                sta .POINTER
+                lda #>.ADDRESS
+                sta .POINTER+1
+            ENDM
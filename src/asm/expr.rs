@@ -0,0 +1,314 @@
+use crate::error::AsmErrorKind;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unary {
+    Low,
+    High,
+    Negate,
+    Complement,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binary {
+    Multiply,
+    Divide,
+    Remainder,
+    Add,
+    Subtract,
+    ShiftLeft,
+    ShiftRight,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    And,
+    Xor,
+    Or,
+    LogicalAnd,
+    LogicalOr,
+}
+
+impl Binary {
+    // Longer operators come first so that `<<` is not taken for `<`.
+    const OPERATORS: [(&'static str, Binary); 18] = [
+        ("||", Binary::LogicalOr),
+        ("&&", Binary::LogicalAnd),
+        ("<<", Binary::ShiftLeft),
+        (">>", Binary::ShiftRight),
+        ("<=", Binary::LessEqual),
+        (">=", Binary::GreaterEqual),
+        ("==", Binary::Equal),
+        ("!=", Binary::NotEqual),
+        ("|", Binary::Or),
+        ("^", Binary::Xor),
+        ("&", Binary::And),
+        ("<", Binary::Less),
+        (">", Binary::Greater),
+        ("+", Binary::Add),
+        ("-", Binary::Subtract),
+        ("*", Binary::Multiply),
+        ("/", Binary::Divide),
+        ("%", Binary::Remainder),
+    ];
+
+    pub fn precedence(self) -> u8 {
+        match self {
+            Binary::LogicalOr => 1,
+            Binary::LogicalAnd => 2,
+            Binary::Or => 3,
+            Binary::Xor => 4,
+            Binary::And => 5,
+            Binary::Equal | Binary::NotEqual => 6,
+            Binary::Less
+            | Binary::Greater
+            | Binary::LessEqual
+            | Binary::GreaterEqual => 7,
+            Binary::ShiftLeft | Binary::ShiftRight => 8,
+            Binary::Add | Binary::Subtract => 9,
+            Binary::Multiply | Binary::Divide | Binary::Remainder => 10,
+        }
+    }
+
+    pub fn apply(self, left: i64, right: i64) -> Result<i64, AsmErrorKind> {
+        let shift = |value: i64| u32::try_from(value).unwrap_or(u32::MAX);
+        let value = match self {
+            Binary::Multiply => left.wrapping_mul(right),
+            Binary::Divide | Binary::Remainder if right == 0 => {
+                return Err(AsmErrorKind::DivisionByZero)
+            },
+            Binary::Divide => left.wrapping_div(right),
+            Binary::Remainder => left.wrapping_rem(right),
+            Binary::Add => left.wrapping_add(right),
+            Binary::Subtract => left.wrapping_sub(right),
+            Binary::ShiftLeft => left.checked_shl(shift(right)).unwrap_or(0),
+            Binary::ShiftRight => left.checked_shr(shift(right)).unwrap_or(0),
+            Binary::Less => i64::from(left < right),
+            Binary::Greater => i64::from(left > right),
+            Binary::LessEqual => i64::from(left <= right),
+            Binary::GreaterEqual => i64::from(left >= right),
+            Binary::Equal => i64::from(left == right),
+            Binary::NotEqual => i64::from(left != right),
+            Binary::And => left & right,
+            Binary::Xor => left ^ right,
+            Binary::Or => left | right,
+            Binary::LogicalAnd => i64::from(left != 0 && right != 0),
+            Binary::LogicalOr => i64::from(left != 0 || right != 0),
+        };
+        Ok(value)
+    }
+}
+
+// DASM reads numbers with a leading zero as octal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dialect {
+    Native,
+    Dasm,
+}
+
+pub trait Scope {
+    fn symbol(&self, name: &str) -> Option<i64>;
+
+    fn location(&self) -> i64;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Number(i64),
+    Symbol(String),
+    Location,
+    Unary(Unary, Box<Expr>),
+    Binary(Binary, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(text: &str, dialect: Dialect) -> Result<Self, AsmErrorKind> {
+        let mut parser = Parser { text: text.as_bytes(), position: 0, dialect };
+        let expr = parser.expression(1)?;
+        parser.skip_space();
+        if parser.position == text.len() {
+            Ok(expr)
+        } else {
+            Err(AsmErrorKind::Syntax)
+        }
+    }
+
+    // Symbols that are not known yet leave the whole expression unknown,
+    // which only the final pass turns into an error.
+    pub fn eval<S>(&self, scope: &S) -> Result<Option<i64>, AsmErrorKind>
+    where
+        S: Scope + ?Sized,
+    {
+        let value = match self {
+            Expr::Number(value) => Some(*value),
+            Expr::Symbol(name) => scope.symbol(name),
+            Expr::Location => Some(scope.location()),
+            Expr::Unary(operator, operand) => {
+                operand.eval(scope)?.map(|value| match operator {
+                    Unary::Low => value & 0xFF,
+                    Unary::High => (value >> 8) & 0xFF,
+                    Unary::Negate => value.wrapping_neg(),
+                    Unary::Complement => !value,
+                    Unary::Not => i64::from(value == 0),
+                })
+            },
+            Expr::Binary(operator, left, right) => {
+                match (left.eval(scope)?, right.eval(scope)?) {
+                    (Some(left), Some(right)) => {
+                        Some(operator.apply(left, right)?)
+                    },
+                    _ => None,
+                }
+            },
+        };
+        Ok(value)
+    }
+
+    pub fn first_undefined<S>(&self, scope: &S) -> Option<&str>
+    where
+        S: Scope + ?Sized,
+    {
+        match self {
+            Expr::Symbol(name) if scope.symbol(name).is_none() => Some(name),
+            Expr::Unary(_, operand) => operand.first_undefined(scope),
+            Expr::Binary(_, left, right) => left
+                .first_undefined(scope)
+                .or_else(|| right.first_undefined(scope)),
+            _ => None,
+        }
+    }
+}
+
+pub fn is_identifier_start(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == b'_' || byte == b'.'
+}
+
+pub fn is_identifier(byte: u8) -> bool {
+    is_identifier_start(byte) || byte.is_ascii_digit()
+}
+
+#[derive(Debug)]
+struct Parser<'text> {
+    text: &'text [u8],
+    position: usize,
+    dialect: Dialect,
+}
+
+impl<'text> Parser<'text> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.position).copied()
+    }
+
+    fn skip_space(&mut self) {
+        while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn expression(&mut self, precedence: u8) -> Result<Expr, AsmErrorKind> {
+        let mut left = self.unary()?;
+        loop {
+            self.skip_space();
+            let rest = &self.text[self.position..];
+            let found = Binary::OPERATORS
+                .iter()
+                .find(|(symbol, _)| rest.starts_with(symbol.as_bytes()));
+            let (symbol, operator) = match found {
+                Some(&(symbol, operator))
+                    if operator.precedence() >= precedence =>
+                {
+                    (symbol, operator)
+                },
+                _ => break Ok(left),
+            };
+            self.position += symbol.len();
+            let right = self.expression(operator.precedence() + 1)?;
+            left = Expr::Binary(operator, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, AsmErrorKind> {
+        self.skip_space();
+        let byte = self.peek().ok_or(AsmErrorKind::Syntax)?;
+        let operator = match byte {
+            b'<' => Some(Unary::Low),
+            b'>' => Some(Unary::High),
+            b'-' => Some(Unary::Negate),
+            b'~' => Some(Unary::Complement),
+            b'!' => Some(Unary::Not),
+            _ => None,
+        };
+        if let Some(operator) = operator {
+            self.position += 1;
+            return Ok(Expr::Unary(operator, Box::new(self.unary()?)));
+        }
+
+        self.position += 1;
+        match byte {
+            b'+' => self.unary(),
+            b'(' => self.group(b')'),
+            b'[' => self.group(b']'),
+            b'$' => self.number(16),
+            b'%' => self.number(2),
+            b'0' if matches!(self.peek(), Some(b'x') | Some(b'X')) => {
+                self.position += 1;
+                self.number(16)
+            },
+            b'0' if self.dialect == Dialect::Dasm => {
+                self.position -= 1;
+                self.number(8)
+            },
+            b'0'..=b'9' => {
+                self.position -= 1;
+                self.number(10)
+            },
+            b'\'' => {
+                let character = self.peek().ok_or(AsmErrorKind::Syntax)?;
+                self.position += 1;
+                if self.peek() == Some(b'\'') {
+                    self.position += 1;
+                }
+                Ok(Expr::Number(i64::from(character)))
+            },
+            b'*' => Ok(Expr::Location),
+            b'.' if !self.peek().is_some_and(is_identifier) => {
+                Ok(Expr::Location)
+            },
+            _ if is_identifier_start(byte) => {
+                let start = self.position - 1;
+                while self.peek().is_some_and(is_identifier) {
+                    self.position += 1;
+                }
+                let name = &self.text[start..self.position];
+                Ok(Expr::Symbol(String::from_utf8_lossy(name).into_owned()))
+            },
+            _ => Err(AsmErrorKind::Syntax),
+        }
+    }
+
+    fn group(&mut self, close: u8) -> Result<Expr, AsmErrorKind> {
+        let expr = self.expression(1)?;
+        self.skip_space();
+        if self.peek() == Some(close) {
+            self.position += 1;
+            Ok(expr)
+        } else {
+            Err(AsmErrorKind::Syntax)
+        }
+    }
+
+    fn number(&mut self, radix: u32) -> Result<Expr, AsmErrorKind> {
+        let start = self.position;
+        while self.peek().is_some_and(|byte| byte.is_ascii_alphanumeric()) {
+            self.position += 1;
+        }
+        let digits = std::str::from_utf8(&self.text[start..self.position])
+            .map_err(|_| AsmErrorKind::Syntax)?;
+        i64::from_str_radix(digits, radix)
+            .map(Expr::Number)
+            .map_err(|_| AsmErrorKind::Syntax)
+    }
+}
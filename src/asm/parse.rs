@@ -0,0 +1,292 @@
+use crate::{
+    asm::expr::{is_identifier, is_identifier_start, Dialect, Expr},
+    error::AsmErrorKind,
+    instruction::Mnemonic,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    None,
+    Acc,
+    Imm(Expr),
+    Direct(Expr),
+    X(Expr),
+    Y(Expr),
+    Ind(Expr),
+    XInd(Expr),
+    IndY(Expr),
+}
+
+impl Operand {
+    pub fn expr(&self) -> Option<&Expr> {
+        match self {
+            Operand::None | Operand::Acc => None,
+            Operand::Imm(expr)
+            | Operand::Direct(expr)
+            | Operand::X(expr)
+            | Operand::Y(expr)
+            | Operand::Ind(expr)
+            | Operand::XInd(expr)
+            | Operand::IndY(expr) => Some(expr),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Data {
+    Expr(Expr),
+    Text(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Instruction { mnemonic: Mnemonic, operand: Operand },
+    Assign(Expr),
+    Org(Expr),
+    Byte(Vec<Data>),
+    Word(Vec<Expr>),
+    Space { count: Expr, fill: Option<Expr> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub number: usize,
+    pub label: Option<String>,
+    pub statement: Option<Statement>,
+}
+
+pub fn parse_line(number: usize, text: &str) -> Result<Line, AsmErrorKind> {
+    let text = strip_comment(text);
+    let mut rest = text.trim_end();
+    let mut label = None;
+
+    // A label either starts in the first column or ends with a colon;
+    // anything else in the first column is taken for an instruction only
+    // if it names one.
+    let (first, after) = split_word(rest.trim_start());
+    if let Some(name) = first.strip_suffix(':') {
+        label = Some(identifier(name)?);
+        rest = after;
+    } else if !rest.starts_with(char::is_whitespace)
+        && !first.is_empty()
+        && Mnemonic::from_name(first).is_none()
+        && !is_directive(first)
+    {
+        label = Some(identifier(first)?);
+        rest = after;
+    }
+
+    let rest = rest.trim();
+    if let Some(value) = rest.strip_prefix('=') {
+        if label.is_none() {
+            return Err(AsmErrorKind::Syntax);
+        }
+        let statement =
+            Some(Statement::Assign(Expr::parse(value, Dialect::Native)?));
+        return Ok(Line { number, label, statement });
+    }
+    if rest.is_empty() {
+        return Ok(Line { number, label, statement: None });
+    }
+
+    let (word, operand) = split_word(rest);
+    let statement = match word.strip_prefix('.') {
+        Some(directive) => parse_directive(directive, operand)?,
+        None => {
+            let mnemonic = Mnemonic::from_name(word).ok_or_else(|| {
+                AsmErrorKind::UnknownMnemonic(word.to_string())
+            })?;
+            let operand = parse_operand(mnemonic, operand, Dialect::Native)?;
+            Statement::Instruction { mnemonic, operand }
+        },
+    };
+    Ok(Line { number, label, statement: Some(statement) })
+}
+
+fn is_directive(word: &str) -> bool {
+    word.strip_prefix('.').is_some_and(|name| {
+        ["org", "byte", "word", "ds"]
+            .iter()
+            .any(|directive| directive.eq_ignore_ascii_case(name))
+    })
+}
+
+fn parse_directive(
+    name: &str,
+    operand: &str,
+) -> Result<Statement, AsmErrorKind> {
+    let statement = match name.to_ascii_lowercase().as_str() {
+        "org" => Statement::Org(Expr::parse(operand, Dialect::Native)?),
+        "byte" => Statement::Byte(
+            split_list(operand)
+                .into_iter()
+                .map(|item| parse_data(item, Dialect::Native))
+                .collect::<Result<_, _>>()?,
+        ),
+        "word" => Statement::Word(
+            split_list(operand)
+                .into_iter()
+                .map(|item| Expr::parse(item, Dialect::Native))
+                .collect::<Result<_, _>>()?,
+        ),
+        "ds" => {
+            let mut items = split_list(operand).into_iter();
+            let count =
+                Expr::parse(items.next().unwrap_or(""), Dialect::Native)?;
+            let fill = items
+                .next()
+                .map(|item| Expr::parse(item, Dialect::Native))
+                .transpose()?;
+            if items.next().is_some() {
+                return Err(AsmErrorKind::Syntax);
+            }
+            Statement::Space { count, fill }
+        },
+        _ => return Err(AsmErrorKind::UnknownDirective(format!(".{}", name))),
+    };
+    Ok(statement)
+}
+
+pub fn parse_data(item: &str, dialect: Dialect) -> Result<Data, AsmErrorKind> {
+    let item = item.trim();
+    match item.strip_prefix('"') {
+        Some(text) => text
+            .strip_suffix('"')
+            .map(|text| Data::Text(text.as_bytes().to_vec()))
+            .ok_or(AsmErrorKind::Syntax),
+        None => Expr::parse(item, dialect).map(Data::Expr),
+    }
+}
+
+// Parentheses around the whole operand mean indirection only for JMP;
+// elsewhere they just group an expression.
+pub fn parse_operand(
+    mnemonic: Mnemonic,
+    text: &str,
+    dialect: Dialect,
+) -> Result<Operand, AsmErrorKind> {
+    let parse = |text| Expr::parse(text, dialect);
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(Operand::None);
+    }
+    if text.eq_ignore_ascii_case("a") {
+        return Ok(Operand::Acc);
+    }
+    if let Some(value) = text.strip_prefix('#') {
+        return Ok(Operand::Imm(parse(value)?));
+    }
+
+    let parts = split_list(text);
+    match parts.as_slice() {
+        [single] => match parenthesized(single) {
+            Some(inner) => match split_list(inner).as_slice() {
+                [base, index] if index.eq_ignore_ascii_case("x") => {
+                    Ok(Operand::XInd(parse(base)?))
+                },
+                [_] if mnemonic == Mnemonic::Jmp => {
+                    Ok(Operand::Ind(parse(inner)?))
+                },
+                [_] => Ok(Operand::Direct(parse(single)?)),
+                _ => Err(AsmErrorKind::Syntax),
+            },
+            None => Ok(Operand::Direct(parse(single)?)),
+        },
+        [base, index] if index.eq_ignore_ascii_case("x") => {
+            Ok(Operand::X(parse(base)?))
+        },
+        [base, index] if index.eq_ignore_ascii_case("y") => {
+            match parenthesized(base) {
+                Some(inner) => Ok(Operand::IndY(parse(inner)?)),
+                None => Ok(Operand::Y(parse(base)?)),
+            }
+        },
+        _ => Err(AsmErrorKind::Syntax),
+    }
+}
+
+fn parenthesized(text: &str) -> Option<&str> {
+    let inner = text.strip_prefix('(')?.strip_suffix(')')?;
+    let mut depth = 0usize;
+    for byte in inner.bytes() {
+        match byte {
+            b'(' => depth += 1,
+            b')' if depth == 0 => return None,
+            b')' => depth -= 1,
+            _ => (),
+        }
+    }
+    Some(inner)
+}
+
+pub fn identifier(name: &str) -> Result<String, AsmErrorKind> {
+    let bytes = name.as_bytes();
+    if bytes.first().is_some_and(|&byte| is_identifier_start(byte))
+        && bytes.iter().all(|&byte| is_identifier(byte))
+    {
+        Ok(name.to_string())
+    } else {
+        Err(AsmErrorKind::Syntax)
+    }
+}
+
+pub fn split_word(text: &str) -> (&str, &str) {
+    match text.find(char::is_whitespace) {
+        Some(end) => (&text[..end], text[end..].trim_start()),
+        None => (text, ""),
+    }
+}
+
+// Commas inside brackets or quotes do not separate items.
+pub fn split_list(text: &str) -> Vec<&str> {
+    let bytes = text.as_bytes();
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut start = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'"' => in_string = !in_string,
+            _ if in_string => (),
+            b'\'' => index = skip_character(bytes, index),
+            b'(' | b'[' => depth += 1,
+            b')' | b']' => depth = depth.saturating_sub(1),
+            b',' if depth == 0 => {
+                items.push(text[start..index].trim());
+                start = index + 1;
+            },
+            _ => (),
+        }
+        index += 1;
+    }
+    items.push(text[start..].trim());
+    items
+}
+
+pub fn strip_comment(text: &str) -> &str {
+    let bytes = text.as_bytes();
+    let mut in_string = false;
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'"' => in_string = !in_string,
+            _ if in_string => (),
+            b'\'' => index = skip_character(bytes, index),
+            b';' => return &text[..index],
+            _ => (),
+        }
+        index += 1;
+    }
+    text
+}
+
+// Character literals may leave out the closing quote, so the quote covers
+// exactly one character and then the closing quote if there is one.
+fn skip_character(bytes: &[u8], quote: usize) -> usize {
+    if bytes.get(quote + 2) == Some(&b'\'') {
+        quote + 2
+    } else {
+        quote + 1
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{error::MachineError, memory::Memory};
+use crate::{bus::Bus, error::MachineError};
 use std::io::{self, Read};
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -36,18 +36,28 @@ pub trait Decoder {
 }
 
 #[derive(Debug)]
-pub struct MemoryDecoder<'mem, 'pc> {
-    memory: &'mem Memory,
+pub struct MemoryDecoder<'bus, 'pc, B>
+where
+    B: Bus + ?Sized,
+{
+    bus: &'bus mut B,
     pc: &'pc mut u16,
 }
 
-impl<'mem, 'pc> MemoryDecoder<'mem, 'pc> {
-    pub fn new(memory: &'mem Memory, pc: &'pc mut u16) -> Self {
-        Self { memory, pc }
+impl<'bus, 'pc, B> MemoryDecoder<'bus, 'pc, B>
+where
+    B: Bus + ?Sized,
+{
+    pub fn new(bus: &'bus mut B, pc: &'pc mut u16) -> Self {
+        Self { bus, pc }
+    }
+
+    pub fn bus(&self) -> &B {
+        self.bus
     }
 
-    pub fn memory(&self) -> &'mem Memory {
-        self.memory
+    pub fn bus_mut(&mut self) -> &mut B {
+        self.bus
     }
 
     pub fn pc(&self) -> u16 {
@@ -59,12 +69,15 @@ impl<'mem, 'pc> MemoryDecoder<'mem, 'pc> {
     }
 }
 
-impl<'mem, 'pc> Decoder for MemoryDecoder<'mem, 'pc> {
+impl<'bus, 'pc, B> Decoder for MemoryDecoder<'bus, 'pc, B>
+where
+    B: Bus + ?Sized,
+{
     type Error = MachineError;
 
     fn read(&mut self, buf: &mut [u8]) -> Result<(), Self::Error> {
         for byte in buf {
-            let result = self.memory.read(*self.pc);
+            let result = self.bus.read(*self.pc);
             if result.is_ok() {
                 *self.pc = self.pc.wrapping_add(1);
             }
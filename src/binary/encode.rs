@@ -7,7 +7,7 @@ pub trait Encode {
         E: Encoder + ?Sized;
 }
 
-impl<'this, T> Encode for &'this T
+impl<T> Encode for &T
 where
     T: Encode + ?Sized,
 {
@@ -0,0 +1,43 @@
+use crate::error::{ReadError, WriteError};
+
+pub trait Bus {
+    fn read(&mut self, address: u16) -> Result<u8, ReadError>;
+
+    fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError>;
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError>;
+}
+
+impl<B> Bus for &mut B
+where
+    B: Bus + ?Sized,
+{
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        (**self).read(address)
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError> {
+        (**self).write(address, data)
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        (**self).peek(address)
+    }
+}
+
+impl<B> Bus for Box<B>
+where
+    B: Bus + ?Sized,
+{
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        (**self).read(address)
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError> {
+        (**self).write(address, data)
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        (**self).peek(address)
+    }
+}
@@ -0,0 +1,393 @@
+mod cdfj;
+mod detect;
+mod dpc;
+mod dpc_plus;
+mod e0;
+mod e7;
+mod fe;
+mod harmony;
+mod ram;
+mod standard;
+mod supercharger;
+mod tape;
+mod tigervision;
+
+pub use cdfj::Cdfj;
+pub use detect::{detect, Detection, Reason};
+pub use dpc::Dpc;
+pub use dpc_plus::DpcPlus;
+pub use e0::E0;
+pub use e7::E7;
+pub use fe::Fe;
+pub use harmony::Harmony;
+pub use ram::ExtraRam;
+pub use standard::Standard;
+pub use supercharger::{Load, Supercharger};
+pub use tape::Tape;
+pub use tigervision::Tigervision;
+
+use crate::{
+    error::{MachineError, ReadError, SchemeError, WriteError},
+    memory::{Rom, RomBank},
+};
+use std::{fmt, fs, io, path::Path};
+
+pub trait Mapper {
+    fn rom(&self) -> &Rom;
+
+    fn rom_mut(&mut self) -> &mut Rom;
+
+    fn read(&mut self, address: u16) -> Result<u8, ReadError>;
+
+    fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError>;
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError>;
+
+    fn snoop(&mut self, _address: u16, _data: u8) {}
+
+    fn snoop_read(&mut self, _address: u16, _data: u8) {}
+
+    fn clock(&mut self) {}
+
+    fn stall(&mut self) -> u64 {
+        0
+    }
+}
+
+impl Mapper for Rom {
+    fn rom(&self) -> &Rom {
+        self
+    }
+
+    fn rom_mut(&mut self) -> &mut Rom {
+        self
+    }
+
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        self.peek(address)
+    }
+
+    fn write(&mut self, _address: u16, _data: u8) -> Result<(), WriteError> {
+        Ok(())
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        Rom::read(self, address)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Scheme {
+    Rom2K,
+    Rom4K,
+    F8,
+    F6,
+    F4,
+    F8Sc,
+    F6Sc,
+    F4Sc,
+    Fa,
+    Ef,
+    EfSc,
+    E0,
+    Tv3F,
+    Tv3E,
+    E7,
+    Dpc,
+    Supercharger,
+    Fe,
+    DpcPlus,
+    Cdfj,
+}
+
+impl Scheme {
+    pub const ALL: [Scheme; 20] = [
+        Scheme::Rom2K,
+        Scheme::Rom4K,
+        Scheme::F8,
+        Scheme::F6,
+        Scheme::F4,
+        Scheme::F8Sc,
+        Scheme::F6Sc,
+        Scheme::F4Sc,
+        Scheme::Fa,
+        Scheme::Ef,
+        Scheme::EfSc,
+        Scheme::E0,
+        Scheme::Tv3F,
+        Scheme::Tv3E,
+        Scheme::E7,
+        Scheme::Dpc,
+        Scheme::Supercharger,
+        Scheme::Fe,
+        Scheme::DpcPlus,
+        Scheme::Cdfj,
+    ];
+
+    // Names are the ones Stella uses for its cartridge types.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|scheme| scheme.to_string().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scheme::Rom2K => write!(fmtr, "2K"),
+            Scheme::Rom4K => write!(fmtr, "4K"),
+            Scheme::F8 => write!(fmtr, "F8"),
+            Scheme::F6 => write!(fmtr, "F6"),
+            Scheme::F4 => write!(fmtr, "F4"),
+            Scheme::F8Sc => write!(fmtr, "F8SC"),
+            Scheme::F6Sc => write!(fmtr, "F6SC"),
+            Scheme::F4Sc => write!(fmtr, "F4SC"),
+            Scheme::Fa => write!(fmtr, "FA"),
+            Scheme::Ef => write!(fmtr, "EF"),
+            Scheme::EfSc => write!(fmtr, "EFSC"),
+            Scheme::E0 => write!(fmtr, "E0"),
+            Scheme::Tv3F => write!(fmtr, "3F"),
+            Scheme::Tv3E => write!(fmtr, "3E"),
+            Scheme::E7 => write!(fmtr, "E7"),
+            Scheme::Dpc => write!(fmtr, "DPC"),
+            Scheme::Supercharger => write!(fmtr, "AR"),
+            Scheme::Fe => write!(fmtr, "FE"),
+            Scheme::DpcPlus => write!(fmtr, "DPC+"),
+            Scheme::Cdfj => write!(fmtr, "CDFJ"),
+        }
+    }
+}
+
+macro_rules! dispatch {
+    ($self:expr, $mapper:ident => $body:expr) => {
+        match $self {
+            Cartridge::Rom($mapper) => $body,
+            Cartridge::Standard($mapper) => $body,
+            Cartridge::E0($mapper) => $body,
+            Cartridge::Tigervision($mapper) => $body,
+            Cartridge::E7($mapper) => $body,
+            Cartridge::Dpc($mapper) => $body,
+            Cartridge::Supercharger($mapper) => $body,
+            Cartridge::Fe($mapper) => $body,
+            Cartridge::DpcPlus($mapper) => $body,
+            Cartridge::Cdfj($mapper) => $body,
+        }
+    };
+}
+
+#[derive(Debug, Clone)]
+pub enum Cartridge {
+    Rom(Rom),
+    Standard(Standard),
+    E0(E0),
+    Tigervision(Tigervision),
+    E7(E7),
+    Dpc(Dpc),
+    Supercharger(Supercharger),
+    Fe(Fe),
+    DpcPlus(DpcPlus),
+    Cdfj(Cdfj),
+}
+
+impl Cartridge {
+    pub fn from_bytes(
+        image: &[u8],
+        scheme: Option<Scheme>,
+    ) -> Result<(Self, Detection), MachineError> {
+        let detection = match scheme {
+            Some(scheme) => Detection { scheme, reason: Reason::Override },
+            None => detect(image)?,
+        };
+        Ok((Self::build(image, detection.scheme)?, detection))
+    }
+
+    pub fn from_path<P>(
+        path: P,
+        scheme: Option<Scheme>,
+    ) -> io::Result<(Self, Detection)>
+    where
+        P: AsRef<Path>,
+    {
+        let image = fs::read(path)?;
+        Self::from_bytes(&image, scheme).map_err(io::Error::from)
+    }
+
+    fn build(image: &[u8], scheme: Scheme) -> Result<Self, SchemeError> {
+        let error = SchemeError { scheme, size: image.len() };
+        let rom = |image: &[u8]| {
+            let mut banks = image.chunks(RomBank::SIZE).map(RomBank::try_new);
+            let first = banks.next().flatten();
+            let rest = banks.collect::<Option<Vec<_>>>();
+            match (first, rest) {
+                (Some(first), Some(rest)) => Ok(Rom::new(first, rest)),
+                _ => Err(error.clone()),
+            }
+        };
+
+        let cartridge = match scheme {
+            Scheme::Rom2K | Scheme::Rom4K => {
+                let size = if scheme == Scheme::Rom2K { 2048 } else { 4096 };
+                if image.is_empty() || image.len() > size {
+                    return Err(error);
+                }
+                match RomBank::mirrored(&padded(image)) {
+                    Some(bank) => Rom::new(bank, None).into(),
+                    None => return Err(error),
+                }
+            },
+            Scheme::F8 => Standard::f8(rom(image)?)?.into(),
+            Scheme::F6 => Standard::f6(rom(image)?)?.into(),
+            Scheme::F4 => Standard::f4(rom(image)?)?.into(),
+            Scheme::F8Sc => Standard::f8(rom(image)?)?.with_superchip().into(),
+            Scheme::F6Sc => Standard::f6(rom(image)?)?.with_superchip().into(),
+            Scheme::F4Sc => Standard::f4(rom(image)?)?.with_superchip().into(),
+            Scheme::Fa => Standard::fa(rom(image)?)?.into(),
+            Scheme::Ef => Standard::ef(rom(image)?)?.into(),
+            Scheme::EfSc => Standard::ef(rom(image)?)?.with_superchip().into(),
+            Scheme::E0 => E0::new(rom(image)?)?.into(),
+            Scheme::Tv3F => Tigervision::t3f(rom(image)?)?.into(),
+            Scheme::Tv3E => Tigervision::t3e(rom(image)?)?.into(),
+            Scheme::E7 => E7::new(rom(image)?)?.into(),
+            Scheme::Fe => Fe::new(rom(image)?)?.into(),
+            // The graphics ROM follows the program banks, sometimes with a
+            // few bytes of padding after it.
+            Scheme::Dpc => {
+                let program = (Dpc::BANKS * RomBank::SIZE).min(image.len());
+                let (program, graphics) = image.split_at(program);
+                let graphics =
+                    graphics.get(..Dpc::GRAPHICS_SIZE).unwrap_or(graphics);
+                Dpc::new(rom(program)?, graphics)?.into()
+            },
+            Scheme::Supercharger => Supercharger::new(image)?.into(),
+            Scheme::DpcPlus => DpcPlus::new(image)?.into(),
+            Scheme::Cdfj => Cdfj::new(image)?.into(),
+        };
+        Ok(cartridge)
+    }
+}
+
+// Images that are not a power of two in size are padded to one with a copy
+// of their last bytes in front, so that the vectors stay at the top.
+fn padded(image: &[u8]) -> Vec<u8> {
+    let gap = image.len().next_power_of_two() - image.len();
+    let mut bytes = image[image.len() - gap..].to_vec();
+    bytes.extend_from_slice(image);
+    bytes
+}
+
+impl From<Rom> for Cartridge {
+    fn from(rom: Rom) -> Self {
+        Cartridge::Rom(rom)
+    }
+}
+
+impl From<Standard> for Cartridge {
+    fn from(mapper: Standard) -> Self {
+        Cartridge::Standard(mapper)
+    }
+}
+
+impl From<E0> for Cartridge {
+    fn from(mapper: E0) -> Self {
+        Cartridge::E0(mapper)
+    }
+}
+
+impl From<Tigervision> for Cartridge {
+    fn from(mapper: Tigervision) -> Self {
+        Cartridge::Tigervision(mapper)
+    }
+}
+
+impl From<E7> for Cartridge {
+    fn from(mapper: E7) -> Self {
+        Cartridge::E7(mapper)
+    }
+}
+
+impl From<Dpc> for Cartridge {
+    fn from(mapper: Dpc) -> Self {
+        Cartridge::Dpc(mapper)
+    }
+}
+
+impl From<Supercharger> for Cartridge {
+    fn from(mapper: Supercharger) -> Self {
+        Cartridge::Supercharger(mapper)
+    }
+}
+
+impl From<Fe> for Cartridge {
+    fn from(mapper: Fe) -> Self {
+        Cartridge::Fe(mapper)
+    }
+}
+
+impl From<DpcPlus> for Cartridge {
+    fn from(mapper: DpcPlus) -> Self {
+        Cartridge::DpcPlus(mapper)
+    }
+}
+
+impl From<Cdfj> for Cartridge {
+    fn from(mapper: Cdfj) -> Self {
+        Cartridge::Cdfj(mapper)
+    }
+}
+
+impl Mapper for Cartridge {
+    fn rom(&self) -> &Rom {
+        dispatch!(self, mapper => mapper.rom())
+    }
+
+    fn rom_mut(&mut self) -> &mut Rom {
+        dispatch!(self, mapper => mapper.rom_mut())
+    }
+
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        dispatch!(self, mapper => Mapper::read(mapper, address))
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError> {
+        dispatch!(self, mapper => mapper.write(address, data))
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        dispatch!(self, mapper => mapper.peek(address))
+    }
+
+    fn snoop(&mut self, address: u16, data: u8) {
+        dispatch!(self, mapper => mapper.snoop(address, data))
+    }
+
+    fn snoop_read(&mut self, address: u16, data: u8) {
+        dispatch!(self, mapper => mapper.snoop_read(address, data))
+    }
+
+    fn clock(&mut self) {
+        dispatch!(self, mapper => mapper.clock())
+    }
+
+    fn stall(&mut self) -> u64 {
+        dispatch!(self, mapper => mapper.stall())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_2k_is_mirrored() {
+        let image = (0..2048).map(|i| (i >> 4) as u8).collect::<Vec<_>>();
+        let (cartridge, _) =
+            Cartridge::from_bytes(&image, Some(Scheme::Rom2K)).unwrap();
+        for offset in [0x000, 0x123, 0x7FF] {
+            let expected = image[usize::from(offset)];
+            assert_eq!(cartridge.peek(0x1000 + offset).unwrap(), expected);
+            assert_eq!(cartridge.peek(0x1800 + offset).unwrap(), expected);
+        }
+    }
+}
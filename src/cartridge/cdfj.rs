@@ -0,0 +1,397 @@
+use crate::{
+    arm::{ArmBus, Branch, Thumb, Width},
+    cartridge::{
+        harmony::{Harmony, Oscillator},
+        Mapper, Scheme,
+    },
+    error::{ArmError, ReadError, SchemeError, WriteError},
+    memory::{Rom, RomBank},
+};
+
+#[derive(Debug, Clone, Copy)]
+struct Voice {
+    counter: u32,
+    frequency: u32,
+    waveform_shift: u32,
+}
+
+impl Default for Voice {
+    fn default() -> Self {
+        Self { counter: 0, frequency: 0, waveform_shift: 27 }
+    }
+}
+
+// The C code reaches the driver's music routines through fixed addresses,
+// which are answered here instead of running driver code.
+struct Bus<'cart> {
+    harmony: &'cart mut Harmony,
+    voices: &'cart mut [Voice; 3],
+}
+
+impl<'cart> ArmBus for Bus<'cart> {
+    fn read(&mut self, address: u32, width: Width) -> Result<u32, ArmError> {
+        self.harmony.read(address, width)
+    }
+
+    fn write(
+        &mut self,
+        address: u32,
+        width: Width,
+        data: u32,
+    ) -> Result<(), ArmError> {
+        self.harmony.write(address, width, data)
+    }
+
+    fn wait_states(&self, address: u32) -> u64 {
+        self.harmony.wait_states(address)
+    }
+
+    fn branch(&mut self, target: u32, registers: &mut [u32; 16]) -> Branch {
+        let voice = registers[2] as usize % self.voices.len();
+        let argument = registers[3];
+        match target & !1 {
+            Cdfj::SET_NOTE => self.voices[voice].frequency = argument,
+            Cdfj::RESET_WAVE => self.voices[voice].counter = 0,
+            Cdfj::GET_WAVE_POINTER => registers[2] = self.voices[voice].counter,
+            Cdfj::SET_WAVE_SIZE => {
+                self.voices[voice].waveform_shift =
+                    32u32.saturating_sub(argument)
+            },
+            _ if target & 1 != 0 => return Branch::Continue,
+            _ => return Branch::Exit,
+        }
+        Branch::Return
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cdfj {
+    rom: Rom,
+    harmony: Harmony,
+    arm: Thumb,
+    voices: [Voice; 3],
+    oscillator: Oscillator,
+    mode: u8,
+    immediate_operand: Option<u16>,
+    jump: Option<(u16, usize, u8)>,
+    stall: u64,
+    fault: Option<ArmError>,
+}
+
+impl Cdfj {
+    pub const BANKS: usize = 7;
+    pub const DRIVER_SIZE: usize = 0x0800;
+    pub const PROGRAM_OFFSET: usize = 0x1000;
+    pub const IMAGE_SIZE: usize = 0x8000;
+    pub const DISPLAY_OFFSET: usize = 0x0800;
+    pub const DATASTREAM_POINTERS: usize = 0x0098;
+    pub const DATASTREAM_INCREMENTS: usize = 0x0124;
+    pub const WAVEFORMS: usize = 0x01B0;
+    pub const FUNCTION_ENTRY: u32 = 0x0808;
+    pub const COMM_STREAM: usize = 0x20;
+    pub const JUMP_STREAM: usize = 0x21;
+    pub const AMPLITUDE: u8 = 0x23;
+    pub const DSWRITE: u16 = 0x1FF0;
+    pub const DSPTR: u16 = 0x1FF1;
+    pub const SETMODE: u16 = 0x1FF2;
+    pub const CALLFN: u16 = 0x1FF3;
+    pub const HOTSPOTS: u16 = 0x1FF5;
+
+    pub const SET_NOTE: u32 = 0x0752;
+    pub const RESET_WAVE: u32 = 0x0756;
+    pub const GET_WAVE_POINTER: u32 = 0x075A;
+    pub const SET_WAVE_SIZE: u32 = 0x075E;
+
+    const LDA_IMMEDIATE: u8 = 0xA9;
+    const JMP_ABSOLUTE: u8 = 0x4C;
+
+    pub fn new(image: &[u8]) -> Result<Self, SchemeError> {
+        let error = SchemeError { scheme: Scheme::Cdfj, size: image.len() };
+        if image.len() != Self::IMAGE_SIZE {
+            return Err(error);
+        }
+        let mut banks = image[Self::PROGRAM_OFFSET..]
+            .chunks(RomBank::SIZE)
+            .filter_map(RomBank::try_new);
+        let first = banks.next().ok_or(error)?;
+        let mut rom = Rom::new(first, banks);
+        rom.select_bank(Self::BANKS as u8 - 1).expect("bank in range");
+
+        // The driver runs from RAM, which is also where it keeps the
+        // datastream and waveform tables.
+        let mut harmony = Harmony::new(image);
+        harmony.ram_mut()[..Self::DRIVER_SIZE]
+            .copy_from_slice(&image[..Self::DRIVER_SIZE]);
+
+        Ok(Self {
+            rom,
+            harmony,
+            arm: Thumb::new(),
+            voices: [Voice::default(); 3],
+            oscillator: Oscillator::default(),
+            mode: 0xFF,
+            immediate_operand: None,
+            jump: None,
+            stall: 0,
+            fault: None,
+        })
+    }
+
+    pub fn harmony(&self) -> &Harmony {
+        &self.harmony
+    }
+
+    pub fn arm(&self) -> &Thumb {
+        &self.arm
+    }
+
+    pub fn fault(&self) -> Option<ArmError> {
+        self.fault
+    }
+
+    pub fn mode(&self) -> u8 {
+        self.mode
+    }
+
+    pub fn fast_fetch(&self) -> bool {
+        self.mode & 0x0F == 0
+    }
+
+    pub fn frequency(&self, voice: usize) -> u32 {
+        self.voices[voice].frequency
+    }
+
+    pub fn digital_audio(&self) -> bool {
+        self.mode & 0xF0 == 0
+    }
+
+    // Pointers are 12.20 fixed point offsets into display RAM and advance
+    // by their 8.8 increment (scaled to match) on every read.
+    pub fn datastream_pointer(&self, stream: usize) -> u32 {
+        self.harmony.ram_word(Self::DATASTREAM_POINTERS + stream * 4)
+    }
+
+    fn set_datastream_pointer(&mut self, stream: usize, pointer: u32) {
+        self.harmony
+            .set_ram_word(Self::DATASTREAM_POINTERS + stream * 4, pointer);
+    }
+
+    fn display_index(pointer: u32) -> usize {
+        Self::DISPLAY_OFFSET + (pointer >> 20) as usize
+    }
+
+    fn read_stream(&mut self, stream: usize, increment: u32) -> u8 {
+        let pointer = self.datastream_pointer(stream);
+        let data = self.harmony.ram()[Self::display_index(pointer)];
+        self.set_datastream_pointer(stream, pointer.wrapping_add(increment));
+        data
+    }
+
+    fn read_datastream(&mut self, stream: usize) -> u8 {
+        let increment =
+            self.harmony.ram_word(Self::DATASTREAM_INCREMENTS + stream * 4);
+        self.read_stream(stream, increment << 12)
+    }
+
+    // Digital audio plays packed 4-bit samples from the address in the
+    // first waveform slot, high nibble first.
+    fn amplitude(&mut self) -> u8 {
+        if self.digital_audio() {
+            let counter = self.voices[0].counter;
+            let address = self
+                .harmony
+                .ram_word(Self::WAVEFORMS)
+                .wrapping_add(counter >> 21);
+            let sample =
+                self.harmony.read(address, Width::Byte).unwrap_or(0) as u8;
+            return if counter & (1 << 20) == 0 {
+                sample >> 4
+            } else {
+                sample & 0x0F
+            };
+        }
+
+        let mut sum = 0u8;
+        for (index, voice) in self.voices.iter().enumerate() {
+            let waveform = self.harmony.ram_word(Self::WAVEFORMS + index * 4);
+            let address = waveform.wrapping_add(
+                voice.counter.checked_shr(voice.waveform_shift).unwrap_or(0),
+            );
+            let sample = self.harmony.read(address, Width::Byte).unwrap_or(0);
+            sum = sum.wrapping_add(sample as u8);
+        }
+        sum
+    }
+
+    fn access(&mut self, address: u16) {
+        let bank = address.wrapping_sub(Self::HOTSPOTS);
+        if bank < Self::BANKS as u16 {
+            let _ = self.rom.select_bank(bank as u8);
+        }
+    }
+
+    fn call_function(&mut self) -> Result<(), ArmError> {
+        let mut bus =
+            Bus { harmony: &mut self.harmony, voices: &mut self.voices };
+        self.stall +=
+            Harmony::call(&mut bus, &mut self.arm, Self::FUNCTION_ENTRY)?;
+        Ok(())
+    }
+}
+
+impl Mapper for Cdfj {
+    fn rom(&self) -> &Rom {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut Rom {
+        &mut self.rom
+    }
+
+    // Fast fetch swaps the operand of LDA immediate for the next byte of
+    // the datastream it names, and fast jumps take both operand bytes of a
+    // JMP to $0000 or $0001 from a jump stream. Only the read right after
+    // the opcode, at the operand's address, is replaced.
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        let data = self.rom.read(address)?;
+        let load_immediate = self.immediate_operand.take() == Some(address);
+
+        if let Some((operand, stream, remaining)) = self.jump.take() {
+            if operand == address {
+                if remaining > 1 {
+                    self.jump = Some((operand + 1, stream, remaining - 1));
+                }
+                return Ok(self.read_stream(stream, 1 << 20));
+            }
+        }
+
+        if self.fast_fetch() && load_immediate && data <= Self::AMPLITUDE {
+            return Ok(if data == Self::AMPLITUDE {
+                self.amplitude()
+            } else {
+                self.read_datastream(usize::from(data))
+            });
+        }
+
+        self.access(address);
+        if self.fast_fetch() {
+            if data == Self::LDA_IMMEDIATE {
+                self.immediate_operand = Some(address.wrapping_add(1));
+            }
+            if data == Self::JMP_ABSOLUTE {
+                let low = self.rom.read(address.wrapping_add(1));
+                let high = self.rom.read(address.wrapping_add(2));
+                if let (Ok(low @ 0..=1), Ok(0)) = (low, high) {
+                    let stream = Self::JUMP_STREAM + usize::from(low);
+                    self.jump = Some((address + 1, stream, 2));
+                }
+            }
+        }
+        Ok(data)
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError> {
+        match address {
+            Self::DSWRITE => {
+                let pointer = self.datastream_pointer(Self::COMM_STREAM);
+                self.harmony.ram_mut()[Self::display_index(pointer)] = data;
+                self.set_datastream_pointer(
+                    Self::COMM_STREAM,
+                    pointer.wrapping_add(1 << 20),
+                );
+            },
+            Self::DSPTR => {
+                let pointer = self.datastream_pointer(Self::COMM_STREAM);
+                let pointer =
+                    ((pointer << 8) & 0xF000_0000) | (u32::from(data) << 20);
+                self.set_datastream_pointer(Self::COMM_STREAM, pointer);
+            },
+            Self::SETMODE => self.mode = data,
+            Self::CALLFN if data >= 0xFE => {
+                if let Err(error) = self.call_function() {
+                    self.fault = Some(error);
+                    return Err(WriteError { address });
+                }
+            },
+            _ => self.access(address),
+        }
+        Ok(())
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        self.rom.read(address)
+    }
+
+    fn clock(&mut self) {
+        for _ in 0..self.oscillator.clock() {
+            for voice in &mut self.voices {
+                voice.counter = voice.counter.wrapping_add(voice.frequency);
+            }
+        }
+    }
+
+    fn stall(&mut self) -> u64 {
+        let stall = self.stall;
+        self.stall = 0;
+        stall
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The program goes at $1000 of the bank CDFJ starts in, with fast fetch
+    // on and every stream pointing at a display byte of $77.
+    fn cdfj(program: &[u8]) -> Cdfj {
+        let mut image = vec![0; Cdfj::IMAGE_SIZE];
+        let bank = Cdfj::PROGRAM_OFFSET + (Cdfj::BANKS - 1) * RomBank::SIZE;
+        image[bank..bank + program.len()].copy_from_slice(program);
+        let mut cdfj = Cdfj::new(&image).unwrap();
+        cdfj.write(Cdfj::SETMODE, 0).unwrap();
+        for stream in 0..=Cdfj::JUMP_STREAM + 1 {
+            cdfj.set_datastream_pointer(stream, 5 << 20);
+            cdfj.harmony
+                .set_ram_word(Cdfj::DATASTREAM_INCREMENTS + stream * 4, 1 << 8);
+        }
+        cdfj.harmony.ram_mut()[Cdfj::DISPLAY_OFFSET + 5] = 0x77;
+        cdfj
+    }
+
+    #[test]
+    fn last_jump_stream_is_not_amplitude() {
+        let mut cdfj = cdfj(&[0xA9, 0x22]);
+        let stream = Cdfj::JUMP_STREAM + 1;
+        assert_eq!(cdfj.read(0x1000).unwrap(), 0xA9);
+        assert_eq!(cdfj.read(0x1001).unwrap(), 0x77);
+        assert_eq!(cdfj.datastream_pointer(stream), 6 << 20);
+    }
+
+    #[test]
+    fn only_lda_immediate_is_fast_fetched() {
+        // LDX #$00; LDY #$10
+        let mut cdfj = cdfj(&[0xA2, 0x00, 0xA0, 0x10]);
+        for address in 0x1000..0x1004 {
+            let expected = cdfj.peek(address).unwrap();
+            assert_eq!(cdfj.read(address).unwrap(), expected);
+        }
+        assert_eq!(cdfj.datastream_pointer(0), 5 << 20);
+    }
+
+    // A table byte of $A9 read by LDA abs,X must not turn the following
+    // opcode fetch, a JSR here, into a datastream read.
+    #[test]
+    fn data_reads_do_not_arm_fast_fetch() {
+        // LDA $1010,X; JSR $1000 ... $1010: .byte $A9
+        let mut program = vec![0xBD, 0x10, 0x10, 0x20, 0x00, 0x10];
+        program.resize(0x11, 0);
+        program[0x10] = 0xA9;
+        let mut cdfj = cdfj(&program);
+        for address in 0x1000..0x1003 {
+            cdfj.read(address).unwrap();
+        }
+        assert_eq!(cdfj.read(0x1010).unwrap(), 0xA9);
+        assert_eq!(cdfj.read(0x1003).unwrap(), 0x20);
+        assert_eq!(cdfj.datastream_pointer(Cdfj::COMM_STREAM), 5 << 20);
+    }
+}
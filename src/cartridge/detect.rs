@@ -0,0 +1,222 @@
+use crate::{
+    cartridge::{Cdfj, DpcPlus, Load, Scheme},
+    error::DetectError,
+    memory::RomBank,
+};
+use std::{fmt, ops::RangeInclusive};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    Override,
+    Database,
+    Size,
+    Signature(&'static str),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::Override => write!(fmtr, "requested explicitly"),
+            Reason::Database => {
+                write!(fmtr, "listed in the properties database")
+            },
+            Reason::Size => write!(fmtr, "default for the image size"),
+            Reason::Signature(signature) => write!(fmtr, "found {}", signature),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detection {
+    pub scheme: Scheme,
+    pub reason: Reason,
+}
+
+impl fmt::Display for Detection {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "{} ({})", self.scheme, self.reason)
+    }
+}
+
+pub const DPC_SIZE: usize = 10240;
+pub const DPC_PADDED_SIZE: usize = 10495;
+
+// Opcodes of the absolute-mode instructions games use to touch a hotspot.
+const ACCESS_OPCODES: [u8; 6] = [0xAD, 0x8D, 0x2C, 0x0C, 0xCD, 0xAE];
+
+const FE_SIGNATURES: [&[u8]; 4] = [
+    &[0x20, 0x00, 0xD0, 0xC6, 0xC5],
+    &[0x20, 0xC3, 0xF8, 0xA5, 0x82],
+    &[0xD0, 0xFB, 0x20, 0x73, 0xFE],
+    &[0x20, 0x00, 0xF0, 0x84, 0xD6],
+];
+
+// Harmony drivers carry their name in their code, DPC+ more than once.
+const DPC_PLUS_NAME: &[u8] = b"DPC+";
+const CDFJ_NAME: &[u8] = b"CDFJ";
+
+const STA_3F: [u8; 2] = [0x85, 0x3F];
+const STA_3E: [u8; 2] = [0x85, 0x3E];
+
+pub fn detect(image: &[u8]) -> Result<Detection, DetectError> {
+    let size = image.len();
+    let by_size = |scheme| Ok(Detection { scheme, reason: Reason::Size });
+    let signature = |scheme, signature| {
+        Ok(Detection { scheme, reason: Reason::Signature(signature) })
+    };
+
+    if size != 0 && size.is_multiple_of(Load::SIZE) {
+        return by_size(Scheme::Supercharger);
+    }
+    if size == DPC_SIZE || size == DPC_PADDED_SIZE {
+        return by_size(Scheme::Dpc);
+    }
+    if size == DpcPlus::IMAGE_SIZE
+        || size == DpcPlus::IMAGE_SIZE - DpcPlus::DRIVER_SIZE
+    {
+        if count(image, DPC_PLUS_NAME) >= 2 {
+            return signature(Scheme::DpcPlus, "the DPC+ driver name");
+        }
+        if size == Cdfj::IMAGE_SIZE && contains(image, CDFJ_NAME) {
+            return signature(Scheme::Cdfj, "the CDFJ driver name");
+        }
+    }
+    if size == 0 {
+        return Err(DetectError { size });
+    }
+    if size <= 2048 {
+        return by_size(Scheme::Rom2K);
+    }
+    if size <= 4096 {
+        return by_size(Scheme::Rom4K);
+    }
+
+    let (standard, superchip, hotspots) = match size {
+        0x2000 => (Scheme::F8, Scheme::F8Sc, 0x1FF8..=0x1FF9),
+        0x3000 => return by_size(Scheme::Fa),
+        0x4000 => (Scheme::F6, Scheme::F6Sc, 0x1FF6..=0x1FF9),
+        0x8000 => (Scheme::F4, Scheme::F4Sc, 0x1FF4..=0x1FFB),
+        0x10000 => (Scheme::Ef, Scheme::EfSc, 0x1FE0..=0x1FEF),
+        // Tigervision carts come in any number of banks, but only the
+        // stores to their hotspots tell them apart from garbage.
+        _ if size.is_multiple_of(RomBank::SIZE) => {
+            return tigervision(image).ok_or(DetectError { size })
+        },
+        _ => return Err(DetectError { size }),
+    };
+
+    if is_superchip(image) {
+        return signature(superchip, "RAM area repeated in every bank");
+    }
+    if size == 0x2000 {
+        if accesses(image, 0x1FE0..=0x1FF7) {
+            return signature(Scheme::E0, "accesses to E0 hotspots");
+        }
+        if FE_SIGNATURES.iter().any(|pattern| contains(image, pattern)) {
+            return signature(Scheme::Fe, "FE subroutine call sequences");
+        }
+    }
+    if size == 0x4000 && accesses(image, 0x1FE0..=0x1FEB) {
+        return signature(Scheme::E7, "accesses to E7 hotspots");
+    }
+    if let Some(detection) = tigervision(image) {
+        return Ok(detection);
+    }
+    if accesses(image, hotspots) {
+        signature(standard, "accesses to its hotspots")
+    } else {
+        by_size(standard)
+    }
+}
+
+// Superchip carts cannot use the first 256 bytes of a bank for ROM, and
+// most images fill the write port with a copy of the read port area.
+fn is_superchip(image: &[u8]) -> bool {
+    image.chunks(4096).all(|bank| bank[..128] == bank[128..256])
+}
+
+fn tigervision(image: &[u8]) -> Option<Detection> {
+    let writes_3f = count(image, &STA_3F);
+    if writes_3f < 2 {
+        None
+    } else if count(image, &STA_3E) > 0 {
+        Some(Detection {
+            scheme: Scheme::Tv3E,
+            reason: Reason::Signature("stores to both $3E and $3F"),
+        })
+    } else {
+        Some(Detection {
+            scheme: Scheme::Tv3F,
+            reason: Reason::Signature("stores to $3F"),
+        })
+    }
+}
+
+fn accesses(image: &[u8], hotspots: RangeInclusive<u16>) -> bool {
+    image.windows(3).any(|window| {
+        let address = u16::from_le_bytes([window[1], window[2]]) & 0x1FFF;
+        ACCESS_OPCODES.contains(&window[0]) && hotspots.contains(&address)
+    })
+}
+
+fn count(image: &[u8], pattern: &[u8]) -> usize {
+    image.windows(pattern.len()).filter(|window| *window == pattern).count()
+}
+
+fn contains(image: &[u8], pattern: &[u8]) -> bool {
+    count(image, pattern) > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::{Cartridge, Mapper};
+
+    fn scheme(image: &[u8]) -> Scheme {
+        let (_, detection) = Cartridge::from_bytes(image, None).unwrap();
+        detection.scheme
+    }
+
+    #[test]
+    fn odd_small_image_keeps_vectors_at_the_top() {
+        let mut image = vec![0xEA; 3072];
+        image[3068..].copy_from_slice(&[0x00, 0xF4, 0x00, 0xF4]);
+        let (cartridge, detection) =
+            Cartridge::from_bytes(&image, None).unwrap();
+        assert_eq!(detection.scheme, Scheme::Rom4K);
+        assert_eq!(cartridge.peek(0x1FFC).unwrap(), 0x00);
+        assert_eq!(cartridge.peek(0x1FFD).unwrap(), 0xF4);
+    }
+
+    #[test]
+    fn odd_tiny_image_is_mirrored() {
+        let mut image = vec![0xEA; 1536];
+        image[1532..].copy_from_slice(&[0x00, 0xF8, 0x00, 0xF8]);
+        let (cartridge, detection) =
+            Cartridge::from_bytes(&image, None).unwrap();
+        assert_eq!(detection.scheme, Scheme::Rom2K);
+        assert_eq!(cartridge.peek(0x17FD).unwrap(), 0xF8);
+        assert_eq!(cartridge.peek(0x1FFD).unwrap(), 0xF8);
+    }
+
+    #[test]
+    fn sizes_with_their_own_schemes() {
+        assert_eq!(scheme(&[0; 0x3000]), Scheme::Fa);
+        let image = (0..0x10000).map(|index| index as u8).collect::<Vec<_>>();
+        assert_eq!(scheme(&image), Scheme::Ef);
+    }
+
+    #[test]
+    fn unknown_sizes_are_rejected() {
+        for &size in &[0, 0x2C00, 0x5000, 0x20000] {
+            assert!(detect(&vec![1; size]).is_err(), "size 0x{:x}", size);
+        }
+    }
+
+    #[test]
+    fn tigervision_by_signature() {
+        let mut image = vec![1; 0x5000];
+        image[..4].copy_from_slice(&[0x85, 0x3F, 0x85, 0x3F]);
+        assert_eq!(detect(&image).unwrap().scheme, Scheme::Tv3F);
+    }
+}
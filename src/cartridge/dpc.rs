@@ -0,0 +1,283 @@
+use crate::{
+    cartridge::{Mapper, Scheme},
+    error::{ReadError, SchemeError, WriteError},
+    memory::{Rom, RomBank},
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Fetcher {
+    top: u8,
+    bottom: u8,
+    counter: u16,
+    flag: bool,
+}
+
+impl Fetcher {
+    // The flag is set when the low byte of the counter crosses the top and
+    // cleared when it crosses the bottom, which is how kernels window a
+    // sprite into the right lines without any comparisons.
+    fn flag(&self) -> bool {
+        let low = self.counter as u8;
+        if low == self.top {
+            true
+        } else if low == self.bottom {
+            false
+        } else {
+            self.flag
+        }
+    }
+
+    fn decrement(&mut self) {
+        self.counter = self.counter.wrapping_sub(1) & Dpc::COUNTER_MASK;
+    }
+
+    // In music mode the low byte counts down at the oscillator rate and
+    // reloads from the top, producing a square wave on the flag.
+    fn oscillate(&mut self) {
+        let low = match self.counter as u8 {
+            _ if self.top == 0 => 0,
+            0 => self.top,
+            low => low - 1,
+        };
+        self.counter = (self.counter & 0x0700) | u16::from(low);
+        if low <= self.bottom {
+            self.flag = false;
+        } else if low <= self.top {
+            self.flag = true;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Dpc {
+    rom: Rom,
+    graphics: Box<[u8; Dpc::GRAPHICS_SIZE]>,
+    fetchers: [Fetcher; 8],
+    music_mode: [bool; 3],
+    random: u8,
+    oscillator: u32,
+}
+
+impl Dpc {
+    pub const BANKS: usize = 2;
+    pub const GRAPHICS_SIZE: usize = 2048;
+    pub const HOTSPOT: u16 = 0x1FF8;
+    pub const READ_REGISTERS: u16 = 0x1000;
+    pub const WRITE_REGISTERS: u16 = 0x1040;
+    pub const REGISTERS_END: u16 = 0x107F;
+    pub const COUNTER_MASK: u16 = 0x07FF;
+    pub const OSCILLATOR_RATE: u32 = 20_000;
+    pub const NTSC_COLOR_CLOCK_RATE: u32 = 3_579_545;
+
+    const MUSIC_AMPLITUDES: [u8; 8] =
+        [0x00, 0x04, 0x05, 0x09, 0x06, 0x0A, 0x0B, 0x0F];
+
+    pub fn new(mut rom: Rom, graphics: &[u8]) -> Result<Self, SchemeError> {
+        if rom.banks() != Self::BANKS || graphics.len() != Self::GRAPHICS_SIZE {
+            return Err(SchemeError {
+                scheme: Scheme::Dpc,
+                size: rom.banks() * RomBank::SIZE + graphics.len(),
+            });
+        }
+        rom.select_bank(1).expect("bank in range");
+        let mut buffer = [0; Self::GRAPHICS_SIZE];
+        buffer.copy_from_slice(graphics);
+        Ok(Self {
+            rom,
+            graphics: Box::new(buffer),
+            fetchers: [Fetcher::default(); 8],
+            music_mode: [false; 3],
+            random: 1,
+            oscillator: 0,
+        })
+    }
+
+    pub fn graphics(&self) -> &[u8] {
+        &self.graphics[..]
+    }
+
+    pub fn random(&self) -> u8 {
+        self.random
+    }
+
+    pub fn counter(&self, fetcher: usize) -> u16 {
+        self.fetchers[fetcher].counter
+    }
+
+    pub fn music_mode(&self, voice: usize) -> bool {
+        self.music_mode[voice]
+    }
+
+    fn access(&mut self, address: u16) {
+        let bank = address.wrapping_sub(Self::HOTSPOT);
+        if bank < Self::BANKS as u16 {
+            let _ = self.rom.select_bank(bank as u8);
+        }
+    }
+
+    // The shift register feeds back the inverted XOR of bits 7, 5, 4 and 3.
+    fn clock_random(&mut self) {
+        let taps = self.random & 0xB8;
+        let bit = taps.count_ones() & 1 == 0;
+        self.random = (self.random << 1) | u8::from(bit);
+    }
+
+    fn register(&self, address: u16, flag: bool) -> u8 {
+        let index = usize::from(address & 0x07);
+        let fetcher = &self.fetchers[index];
+        let image = self.graphics
+            [Self::GRAPHICS_SIZE - 1 - usize::from(fetcher.counter)];
+        let flag_bits = if flag { 0xFF } else { 0x00 };
+        match (address >> 3) & 0x07 {
+            0 if index < 4 => self.random,
+            0 => {
+                let mut amplitude = 0;
+                for voice in 0..3 {
+                    if self.music_mode[voice] && self.fetchers[5 + voice].flag {
+                        amplitude |= 1 << voice;
+                    }
+                }
+                Self::MUSIC_AMPLITUDES[amplitude]
+            },
+            1 => image,
+            2 => image & flag_bits,
+            7 => flag_bits,
+            _ => 0,
+        }
+    }
+
+    // A music fetcher restarts its period from the top whatever is written
+    // to the low byte of its counter.
+    fn write_register(&mut self, address: u16, data: u8) {
+        let index = usize::from(address & 0x07);
+        let music = self.in_music_mode(index);
+        let fetcher = &mut self.fetchers[index];
+        match (address >> 3) & 0x07 {
+            0 => {
+                fetcher.top = data;
+                fetcher.flag = false;
+            },
+            1 => fetcher.bottom = data,
+            2 => {
+                let low = if music { fetcher.top } else { data };
+                fetcher.counter = (fetcher.counter & 0x0700) | u16::from(low);
+            },
+            3 => {
+                fetcher.counter =
+                    (u16::from(data & 0x07) << 8) | (fetcher.counter & 0x00FF);
+                if index >= 5 {
+                    self.music_mode[index - 5] = data & 0x10 != 0;
+                }
+            },
+            6 => self.random = 1,
+            _ => (),
+        }
+    }
+
+    fn in_music_mode(&self, index: usize) -> bool {
+        index >= 5 && self.music_mode[index - 5]
+    }
+}
+
+impl Mapper for Dpc {
+    fn rom(&self) -> &Rom {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut Rom {
+        &mut self.rom
+    }
+
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        self.clock_random();
+        self.access(address);
+        if !(Self::READ_REGISTERS..Self::WRITE_REGISTERS).contains(&address) {
+            return self.rom.read(address);
+        }
+
+        let index = usize::from(address & 0x07);
+        let flag = self.fetchers[index].flag();
+        self.fetchers[index].flag = flag;
+        let data = self.register(address, flag);
+        if !self.in_music_mode(index) {
+            self.fetchers[index].decrement();
+        }
+        Ok(data)
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError> {
+        self.clock_random();
+        self.access(address);
+        if (Self::WRITE_REGISTERS..=Self::REGISTERS_END).contains(&address) {
+            self.write_register(address, data);
+        }
+        Ok(())
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        if (Self::READ_REGISTERS..Self::WRITE_REGISTERS).contains(&address) {
+            let index = usize::from(address & 0x07);
+            Ok(self.register(address, self.fetchers[index].flag()))
+        } else {
+            self.rom.read(address)
+        }
+    }
+
+    // The music oscillator runs off its own 20 kHz clock, so its ticks are
+    // spread over CPU cycles (a third of the colour clock rate) by keeping
+    // the remainder around.
+    fn clock(&mut self) {
+        self.oscillator += 3 * Self::OSCILLATOR_RATE;
+        while self.oscillator >= Self::NTSC_COLOR_CLOCK_RATE {
+            self.oscillator -= Self::NTSC_COLOR_CLOCK_RATE;
+            for voice in 0..3 {
+                if self.music_mode[voice] {
+                    self.fetchers[5 + voice].oscillate();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each bank is filled with its own number.
+    fn dpc() -> Dpc {
+        let bank = |n| RomBank::new([n; RomBank::SIZE]);
+        Dpc::new(Rom::new(bank(0), Some(bank(1))), &[0; Dpc::GRAPHICS_SIZE])
+            .unwrap()
+    }
+
+    #[test]
+    fn bank_hotspots() {
+        let mut dpc = dpc();
+        assert_eq!(dpc.peek(0x1800).unwrap(), 1);
+        dpc.read(0x1FF8).unwrap();
+        assert_eq!(dpc.peek(0x1800).unwrap(), 0);
+        dpc.write(0x1FF9, 0).unwrap();
+        assert_eq!(dpc.peek(0x1800).unwrap(), 1);
+        dpc.read(0x1FFA).unwrap();
+        dpc.read(0x1FF7).unwrap();
+        assert_eq!(dpc.peek(0x1800).unwrap(), 1);
+    }
+
+    #[test]
+    fn counter_low_write_loads_data() {
+        let mut dpc = dpc();
+        dpc.write(0x1040 + 5, 0x30).unwrap();
+        dpc.write(0x1050 + 5, 0x12).unwrap();
+        assert_eq!(dpc.counter(5), 0x12);
+    }
+
+    #[test]
+    fn counter_low_write_in_music_mode_reloads_top() {
+        let mut dpc = dpc();
+        dpc.write(0x1040 + 5, 0x30).unwrap();
+        dpc.write(0x1058 + 5, 0x10).unwrap();
+        assert!(dpc.music_mode(0));
+        dpc.write(0x1050 + 5, 0x12).unwrap();
+        assert_eq!(dpc.counter(5), 0x30);
+    }
+}
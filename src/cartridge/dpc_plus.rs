@@ -0,0 +1,500 @@
+use crate::{
+    arm::Thumb,
+    cartridge::{
+        harmony::{Harmony, Oscillator},
+        Mapper, Scheme,
+    },
+    error::{ArmError, ReadError, SchemeError, WriteError},
+    memory::{Rom, RomBank},
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Fetcher {
+    top: u8,
+    bottom: u8,
+    counter: u16,
+    fraction: u32,
+    increment: u8,
+}
+
+impl Fetcher {
+    // Unlike the DPC, the flag is a window comparison made on every read,
+    // so no state needs to be kept between reads.
+    fn flag(&self) -> u8 {
+        let low = self.counter as u8;
+        let window = self.top.wrapping_sub(self.bottom);
+        if self.top.wrapping_sub(low) > window {
+            0xFF
+        } else {
+            0x00
+        }
+    }
+
+    fn advance(&mut self) {
+        self.counter = (self.counter + 1) & DpcPlus::COUNTER_MASK;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Voice {
+    counter: u32,
+    frequency: u32,
+    waveform: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct DpcPlus {
+    rom: Rom,
+    harmony: Harmony,
+    arm: Thumb,
+    fetchers: [Fetcher; 8],
+    voices: [Voice; 3],
+    oscillator: Oscillator,
+    random: u32,
+    parameters: [u8; 8],
+    parameter_count: usize,
+    fast_fetch: bool,
+    immediate_operand: Option<u16>,
+    stall: u64,
+    fault: Option<ArmError>,
+}
+
+impl DpcPlus {
+    pub const BANKS: usize = 6;
+    pub const DRIVER_SIZE: usize = 0x0C00;
+    pub const IMAGE_SIZE: usize = 0x8000;
+    pub const DISPLAY_OFFSET: usize = 0x0C00;
+    pub const DISPLAY_SIZE: usize = 0x1000;
+    pub const FREQUENCY_OFFSET: usize = 0x1C00;
+    pub const FUNCTION_ENTRY: u32 = 0x0C08;
+    pub const HOTSPOTS: u16 = 0x1FF6;
+    pub const READ_REGISTERS_END: u16 = 0x1028;
+    pub const REGISTERS_END: u16 = 0x107F;
+    pub const COUNTER_MASK: u16 = 0x0FFF;
+    pub const RANDOM_SEED: u32 = 0x2B43_5044;
+
+    const RANDOM_TAPS: u32 = 0x10AD_AB1E;
+    const LDA_IMMEDIATE: u8 = 0xA9;
+
+    // Images built without the driver are accepted too, since the driver
+    // is emulated anyway; its space is then left blank.
+    pub fn new(image: &[u8]) -> Result<Self, SchemeError> {
+        let error = SchemeError { scheme: Scheme::DpcPlus, size: image.len() };
+        let mut flash = vec![0; Self::IMAGE_SIZE];
+        match image.len() {
+            Self::IMAGE_SIZE => flash.copy_from_slice(image),
+            size if size == Self::IMAGE_SIZE - Self::DRIVER_SIZE => {
+                flash[Self::DRIVER_SIZE..].copy_from_slice(image)
+            },
+            _ => return Err(error),
+        }
+
+        let program_end = Self::DRIVER_SIZE + Self::BANKS * RomBank::SIZE;
+        let mut banks = flash[Self::DRIVER_SIZE..program_end]
+            .chunks(RomBank::SIZE)
+            .filter_map(RomBank::try_new);
+        let first = banks.next().ok_or(error)?;
+        let mut rom = Rom::new(first, banks);
+        rom.select_bank(Self::BANKS as u8 - 1).expect("bank in range");
+
+        // The driver copies the display and frequency data that follow the
+        // banks into RAM, where both the 6507 and the ARM can change them.
+        let mut harmony = Harmony::new(&flash);
+        harmony.ram_mut()[Self::DISPLAY_OFFSET..]
+            .copy_from_slice(&flash[program_end..]);
+
+        Ok(Self {
+            rom,
+            harmony,
+            arm: Thumb::new(),
+            fetchers: [Fetcher::default(); 8],
+            voices: [Voice::default(); 3],
+            oscillator: Oscillator::default(),
+            random: Self::RANDOM_SEED,
+            parameters: [0; 8],
+            parameter_count: 0,
+            fast_fetch: false,
+            immediate_operand: None,
+            stall: 0,
+            fault: None,
+        })
+    }
+
+    pub fn harmony(&self) -> &Harmony {
+        &self.harmony
+    }
+
+    pub fn arm(&self) -> &Thumb {
+        &self.arm
+    }
+
+    pub fn fault(&self) -> Option<ArmError> {
+        self.fault
+    }
+
+    pub fn random(&self) -> u32 {
+        self.random
+    }
+
+    pub fn counter(&self, fetcher: usize) -> u16 {
+        self.fetchers[fetcher].counter
+    }
+
+    pub fn fast_fetch(&self) -> bool {
+        self.fast_fetch
+    }
+
+    fn display(&self, offset: u16) -> u8 {
+        self.harmony.ram()[Self::DISPLAY_OFFSET + usize::from(offset)]
+    }
+
+    fn display_mut(&mut self, offset: u16) -> &mut u8 {
+        let index = Self::DISPLAY_OFFSET + usize::from(offset & 0x0FFF);
+        &mut self.harmony.ram_mut()[index]
+    }
+
+    fn access(&mut self, address: u16) {
+        let bank = address.wrapping_sub(Self::HOTSPOTS);
+        if bank < Self::BANKS as u16 {
+            let _ = self.rom.select_bank(bank as u8);
+        }
+    }
+
+    fn next_random(&mut self) {
+        let taps =
+            if self.random & (1 << 10) != 0 { Self::RANDOM_TAPS } else { 0 };
+        self.random = taps ^ self.random.rotate_right(11);
+    }
+
+    fn prior_random(&mut self) {
+        let taps =
+            if self.random & (1 << 31) != 0 { Self::RANDOM_TAPS } else { 0 };
+        self.random = (taps ^ self.random).rotate_left(11);
+    }
+
+    // The waveforms live in display RAM as 32-byte tables, indexed by the
+    // top five bits of each voice's counter.
+    fn amplitude(&self) -> u8 {
+        self.voices.iter().fold(0u8, |sum, voice| {
+            let offset =
+                (u16::from(voice.waveform) << 5) + (voice.counter >> 27) as u16;
+            sum.wrapping_add(self.display(offset & 0x0FFF))
+        })
+    }
+
+    fn read_register(&mut self, register: u16) -> u8 {
+        let index = usize::from(register & 0x07);
+        let flag = self.fetchers[index].flag();
+        match register >> 3 {
+            0 => match index {
+                0 => {
+                    self.next_random();
+                    self.random as u8
+                },
+                1 => {
+                    self.prior_random();
+                    self.random as u8
+                },
+                2..=4 => (self.random >> (8 * (index - 1))) as u8,
+                5 => self.amplitude(),
+                _ => 0,
+            },
+            1 | 2 => {
+                let data = self.display(self.fetchers[index].counter);
+                self.fetchers[index].advance();
+                if register >> 3 == 2 {
+                    data & flag
+                } else {
+                    data
+                }
+            },
+            3 => {
+                let fetcher = &mut self.fetchers[index];
+                let offset = (fetcher.fraction >> 8) as u16;
+                fetcher.fraction = (fetcher.fraction
+                    + u32::from(fetcher.increment))
+                    & 0x000F_FFFF;
+                self.display(offset)
+            },
+            4 if index < 4 => flag,
+            _ => 0,
+        }
+    }
+
+    fn write_register(
+        &mut self,
+        register: u16,
+        data: u8,
+    ) -> Result<(), ArmError> {
+        let index = usize::from(register & 0x07);
+        let fetcher = &mut self.fetchers[index];
+        match register {
+            0x28..=0x2F => {
+                fetcher.fraction =
+                    (fetcher.fraction & 0x000F_0000) | (u32::from(data) << 8)
+            },
+            0x30..=0x37 => {
+                fetcher.fraction = (u32::from(data & 0x0F) << 16)
+                    | (fetcher.fraction & 0x0000_FFFF)
+            },
+            0x38..=0x3F => {
+                fetcher.increment = data;
+                fetcher.fraction &= 0x000F_FF00;
+            },
+            0x40..=0x47 => fetcher.top = data,
+            0x48..=0x4F => fetcher.bottom = data,
+            0x50..=0x57 => {
+                fetcher.counter = (fetcher.counter & 0x0F00) | u16::from(data)
+            },
+            0x58 => self.fast_fetch = data == 0,
+            0x59 if self.parameter_count < self.parameters.len() => {
+                self.parameters[self.parameter_count] = data;
+                self.parameter_count += 1;
+            },
+            0x5A => return self.call_function(data),
+            0x5D..=0x5F => self.voices[index - 5].waveform = data & 0x7F,
+            0x60..=0x67 => {
+                fetcher.counter =
+                    fetcher.counter.wrapping_sub(1) & Self::COUNTER_MASK;
+                let counter = fetcher.counter;
+                *self.display_mut(counter) = data;
+            },
+            0x68..=0x6F => {
+                fetcher.counter =
+                    (u16::from(data & 0x0F) << 8) | (fetcher.counter & 0x00FF)
+            },
+            0x70 => self.random = Self::RANDOM_SEED,
+            0x71..=0x74 => {
+                let shift = 8 * (register - 0x71);
+                self.random = (self.random & !(0xFF << shift))
+                    | (u32::from(data) << shift);
+            },
+            0x75..=0x77 => {
+                let offset = Self::FREQUENCY_OFFSET + usize::from(data) * 4;
+                self.voices[index - 5].frequency =
+                    self.harmony.ram_word(offset);
+            },
+            0x78..=0x7F => {
+                let counter = fetcher.counter;
+                fetcher.advance();
+                *self.display_mut(counter) = data;
+            },
+            _ => (),
+        }
+        Ok(())
+    }
+
+    // Functions 1 and 2 are the driver's block copies into a fetcher's
+    // data, taking the source address (or fill value), fetcher and length
+    // from the parameters. The last two run the game's own ARM code.
+    fn call_function(&mut self, function: u8) -> Result<(), ArmError> {
+        let [low, high, fetcher, length, ..] = self.parameters;
+        let counter = self.fetchers[usize::from(fetcher & 0x07)].counter;
+        match function {
+            1 => {
+                let source = usize::from(u16::from_le_bytes([low, high]));
+                for offset in 0..u16::from(length) {
+                    let data = self.harmony.flash()[Self::DRIVER_SIZE
+                        + (source + usize::from(offset))
+                            % (Self::BANKS * RomBank::SIZE)];
+                    *self.display_mut(counter + offset) = data;
+                }
+            },
+            2 => {
+                for offset in 0..u16::from(length) {
+                    *self.display_mut(counter + offset) = low;
+                }
+            },
+            254 | 255 => {
+                self.stall += Harmony::call(
+                    &mut self.harmony,
+                    &mut self.arm,
+                    Self::FUNCTION_ENTRY,
+                )?;
+            },
+            _ => (),
+        }
+        self.parameter_count = 0;
+        Ok(())
+    }
+}
+
+impl Mapper for DpcPlus {
+    fn rom(&self) -> &Rom {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut Rom {
+        &mut self.rom
+    }
+
+    // With fast fetch on, the operand of an immediate LDA that names a read
+    // register is replaced by that register, saving the kernel the four
+    // cycles of an absolute load. Only the read right after the opcode, at
+    // the operand's address, is replaced.
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        let data = self.rom.read(address)?;
+        let registers = Self::READ_REGISTERS_END - RomBank::OFFSET;
+        let load_immediate = self.immediate_operand.take() == Some(address);
+        let register =
+            if self.fast_fetch && load_immediate && u16::from(data) < registers
+            {
+                u16::from(data)
+            } else {
+                address - RomBank::OFFSET
+            };
+
+        if register < registers {
+            return Ok(self.read_register(register));
+        }
+        self.access(address);
+        if self.fast_fetch && data == Self::LDA_IMMEDIATE {
+            self.immediate_operand = Some(address.wrapping_add(1));
+        }
+        Ok(data)
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError> {
+        if (Self::READ_REGISTERS_END..=Self::REGISTERS_END).contains(&address) {
+            let register = address - RomBank::OFFSET;
+            if let Err(error) = self.write_register(register, data) {
+                self.fault = Some(error);
+                return Err(WriteError { address });
+            }
+        }
+        self.access(address);
+        Ok(())
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        self.rom.read(address)
+    }
+
+    fn clock(&mut self) {
+        for _ in 0..self.oscillator.clock() {
+            for voice in &mut self.voices {
+                voice.counter = voice.counter.wrapping_add(voice.frequency);
+            }
+        }
+    }
+
+    fn stall(&mut self) -> u64 {
+        let stall = self.stall;
+        self.stall = 0;
+        stall
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The program goes at $1080, past the registers, in the bank DPC+
+    // starts in, and display data at the start of display RAM.
+    fn dpc_plus(program: &[u8], display: &[u8]) -> DpcPlus {
+        let mut image = vec![0; DpcPlus::IMAGE_SIZE];
+        let bank = DpcPlus::DRIVER_SIZE + (DpcPlus::BANKS - 1) * RomBank::SIZE;
+        image[bank + 0x80..bank + 0x80 + program.len()]
+            .copy_from_slice(program);
+        let data = DpcPlus::DRIVER_SIZE + DpcPlus::BANKS * RomBank::SIZE;
+        image[data..data + display.len()].copy_from_slice(display);
+        DpcPlus::new(&image).unwrap()
+    }
+
+    #[test]
+    fn fast_fetch_replaces_lda_immediate_operand() {
+        // LDA #<DF0DATA
+        let mut dpc_plus = dpc_plus(&[0xA9, 0x08], &[0x77]);
+        dpc_plus.write(0x1058, 0).unwrap();
+        assert_eq!(dpc_plus.read(0x1080).unwrap(), 0xA9);
+        assert_eq!(dpc_plus.read(0x1081).unwrap(), 0x77);
+        assert_eq!(dpc_plus.counter(0), 1);
+    }
+
+    // A table byte of $A9 read by LDA abs,X must not turn the following
+    // opcode fetch, a PHP here, into a register read.
+    #[test]
+    fn data_reads_do_not_arm_fast_fetch() {
+        // LDA $1090,X; PHP ... $1090: .byte $A9
+        let mut program = vec![0xBD, 0x90, 0x10, 0x08];
+        program.resize(0x11, 0);
+        program[0x10] = 0xA9;
+        let mut dpc_plus = dpc_plus(&program, &[0x77]);
+        dpc_plus.write(0x1058, 0).unwrap();
+        for address in 0x1080..0x1083 {
+            dpc_plus.read(address).unwrap();
+        }
+        assert_eq!(dpc_plus.read(0x1090).unwrap(), 0xA9);
+        assert_eq!(dpc_plus.read(0x1083).unwrap(), 0x08);
+        assert_eq!(dpc_plus.counter(0), 0);
+    }
+
+    fn set_counter(dpc_plus: &mut DpcPlus, fetcher: u16, counter: u16) {
+        dpc_plus.write(0x1050 + fetcher, counter as u8).unwrap();
+        dpc_plus.write(0x1068 + fetcher, (counter >> 8) as u8).unwrap();
+    }
+
+    #[test]
+    fn fetcher_reads_advance_the_counter() {
+        let mut dpc_plus = dpc_plus(&[], &[10, 11, 12, 13]);
+        set_counter(&mut dpc_plus, 3, 0x002);
+        assert_eq!(dpc_plus.read(0x1008 + 3).unwrap(), 12);
+        assert_eq!(dpc_plus.read(0x1008 + 3).unwrap(), 13);
+        assert_eq!(dpc_plus.counter(3), 4);
+        set_counter(&mut dpc_plus, 3, 0xFFF);
+        dpc_plus.read(0x1008 + 3).unwrap();
+        assert_eq!(dpc_plus.counter(3), 0);
+    }
+
+    // The flag is clear while the low counter byte is between the bottom
+    // and the top of the window, and masks the windowed data.
+    #[test]
+    fn windowed_flags() {
+        let mut dpc_plus = dpc_plus(&[], &[0x55; 8]);
+        dpc_plus.write(0x1040 + 1, 5).unwrap();
+        dpc_plus.write(0x1048 + 1, 2).unwrap();
+        let mut flags = Vec::new();
+        for counter in 0..8 {
+            set_counter(&mut dpc_plus, 1, counter);
+            flags.push(dpc_plus.read(0x1020 + 1).unwrap());
+            assert_eq!(
+                dpc_plus.read(0x1010 + 1).unwrap(),
+                flags[flags.len() - 1] & 0x55
+            );
+        }
+        assert_eq!(flags, [0xFF, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn fractional_increments() {
+        let mut dpc_plus = dpc_plus(&[], &[10, 11, 12, 13]);
+        dpc_plus.write(0x1028 + 2, 1).unwrap();
+        dpc_plus.write(0x1030 + 2, 0).unwrap();
+        dpc_plus.write(0x1038 + 2, 0x80).unwrap();
+        let reads = (0..5)
+            .map(|_| dpc_plus.read(0x1018 + 2).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(reads, [11, 11, 12, 12, 13]);
+    }
+
+    // Function 1 copies from the program banks, function 2 fills.
+    #[test]
+    fn call_function_copies_and_fills() {
+        let mut dpc_plus = dpc_plus(&[1, 2, 3, 4], &[]);
+        set_counter(&mut dpc_plus, 1, 0x010);
+        for &parameter in &[0x80, 0x50, 1, 4] {
+            dpc_plus.write(0x1059, parameter).unwrap();
+        }
+        dpc_plus.write(0x105A, 1).unwrap();
+        set_counter(&mut dpc_plus, 2, 0x020);
+        for &parameter in &[0x5A, 0, 2, 3] {
+            dpc_plus.write(0x1059, parameter).unwrap();
+        }
+        dpc_plus.write(0x105A, 2).unwrap();
+
+        let display = &dpc_plus.harmony().ram()[DpcPlus::DISPLAY_OFFSET..];
+        assert_eq!(display[0x10..0x15], [1, 2, 3, 4, 0]);
+        assert_eq!(display[0x20..0x24], [0x5A, 0x5A, 0x5A, 0]);
+        assert_eq!(dpc_plus.counter(1), 0x010);
+    }
+}
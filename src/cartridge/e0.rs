@@ -0,0 +1,105 @@
+use crate::{
+    cartridge::{Mapper, Scheme},
+    error::{ReadError, SchemeError, WriteError},
+    memory::{Rom, RomBank, SegmentSize},
+};
+
+#[derive(Debug, Clone)]
+pub struct E0 {
+    rom: Rom,
+}
+
+impl E0 {
+    pub const BANKS: usize = 2;
+    pub const HOTSPOT: u16 = 0x1FE0;
+    pub const HOTSPOT_END: u16 = 0x1FF7;
+
+    // The image is cut in eight 1K chunks. The first three slices of the
+    // window are switchable and the last one always shows the last chunk.
+    pub fn new(mut rom: Rom) -> Result<Self, SchemeError> {
+        if rom.banks() != Self::BANKS {
+            return Err(SchemeError {
+                scheme: Scheme::E0,
+                size: rom.banks() * RomBank::SIZE,
+            });
+        }
+        rom.set_segment_size(SegmentSize::Quarter);
+        let last = rom.chunks() - 1;
+        for segment in 0..4 {
+            rom.select_chunk(segment, (last - 3 + segment) as u8)
+                .expect("chunk in range");
+        }
+        Ok(Self { rom })
+    }
+
+    fn access(&mut self, address: u16) {
+        if (Self::HOTSPOT..=Self::HOTSPOT_END).contains(&address) {
+            let hotspot = address - Self::HOTSPOT;
+            let segment = usize::from(hotspot >> 3);
+            let _ = self.rom.select_chunk(segment, (hotspot & 0x7) as u8);
+        }
+    }
+}
+
+impl Mapper for E0 {
+    fn rom(&self) -> &Rom {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut Rom {
+        &mut self.rom
+    }
+
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        self.access(address);
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: u16, _data: u8) -> Result<(), WriteError> {
+        self.access(address);
+        Ok(())
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        self.rom.read(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every 1K chunk is filled with its own number.
+    fn e0() -> E0 {
+        let bank = |first: u8| {
+            let mut bytes = [0; RomBank::SIZE];
+            for (chunk, slice) in bytes.chunks_mut(1024).enumerate() {
+                slice.fill(first + chunk as u8);
+            }
+            RomBank::new(bytes)
+        };
+        E0::new(Rom::new(bank(0), Some(bank(4)))).unwrap()
+    }
+
+    fn slices(e0: &E0) -> [u8; 4] {
+        let slice = |n: u16| e0.peek(0x1000 + n * 0x400).unwrap();
+        [slice(0), slice(1), slice(2), slice(3)]
+    }
+
+    #[test]
+    fn starts_with_the_last_chunks() {
+        assert_eq!(slices(&e0()), [4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn hotspots_select_each_slice() {
+        let mut e0 = e0();
+        e0.read(0x1FE2).unwrap();
+        e0.write(0x1FE8 + 7, 0).unwrap();
+        e0.read(0x1FF0).unwrap();
+        assert_eq!(slices(&e0), [2, 7, 0, 7]);
+        e0.read(0x1FF8).unwrap();
+        e0.read(0x1FDF).unwrap();
+        assert_eq!(slices(&e0), [2, 7, 0, 7]);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{
+    cartridge::{ExtraRam, Mapper, Scheme},
+    error::{ReadError, SchemeError, WriteError},
+    memory::{Rom, RomBank, SegmentSize},
+};
+
+#[derive(Debug, Clone)]
+pub struct E7 {
+    rom: Rom,
+    ram: ExtraRam,
+    ram_selected: bool,
+    banked_ram: ExtraRam,
+    data_bus: u8,
+}
+
+impl E7 {
+    pub const BANKS: usize = 4;
+    pub const HOTSPOT: u16 = 0x1FE0;
+    pub const RAM_HOTSPOT: u16 = 0x1FE7;
+    pub const BANKED_RAM_HOTSPOT: u16 = 0x1FE8;
+    pub const HOTSPOT_END: u16 = 0x1FEB;
+    pub const RAM_SIZE: u16 = 0x400;
+    pub const RAM_WRITE_PORT: u16 = 0x1000;
+    pub const RAM_READ_PORT: u16 = 0x1400;
+    pub const BANKED_RAM_WINDOW: u16 = 0x100;
+    pub const BANKED_RAM_WRITE_PORT: u16 = 0x1800;
+    pub const BANKED_RAM_READ_PORT: u16 = 0x1900;
+
+    // The window is cut in two 2K halves. The lower one shows one of the
+    // first seven ROM slices or the 1K RAM, the upper one is fixed to the
+    // last slice with its first 512 bytes taken by a 256-byte RAM bank.
+    pub fn new(mut rom: Rom) -> Result<Self, SchemeError> {
+        if rom.banks() != Self::BANKS {
+            return Err(SchemeError {
+                scheme: Scheme::E7,
+                size: rom.banks() * RomBank::SIZE,
+            });
+        }
+        rom.set_segment_size(SegmentSize::Half);
+        let last = (rom.chunks() - 1) as u8;
+        rom.select_chunk(0, 0).expect("chunk in range");
+        rom.select_chunk(1, last).expect("chunk in range");
+        let ram = ExtraRam::new(
+            usize::from(Self::RAM_SIZE),
+            Self::RAM_SIZE,
+            Self::RAM_WRITE_PORT,
+            Self::RAM_READ_PORT,
+        );
+        let banked_ram = ExtraRam::new(
+            usize::from(Self::RAM_SIZE),
+            Self::BANKED_RAM_WINDOW,
+            Self::BANKED_RAM_WRITE_PORT,
+            Self::BANKED_RAM_READ_PORT,
+        );
+        Ok(Self { rom, ram, ram_selected: false, banked_ram, data_bus: 0 })
+    }
+
+    pub fn ram(&self) -> &ExtraRam {
+        &self.ram
+    }
+
+    pub fn ram_selected(&self) -> bool {
+        self.ram_selected
+    }
+
+    pub fn banked_ram(&self) -> &ExtraRam {
+        &self.banked_ram
+    }
+
+    fn access(&mut self, address: u16) {
+        match address {
+            Self::HOTSPOT..=0x1FE6 => {
+                let chunk = (address - Self::HOTSPOT) as u8;
+                let _ = self.rom.select_chunk(0, chunk);
+                self.ram_selected = false;
+            },
+            Self::RAM_HOTSPOT => self.ram_selected = true,
+            Self::BANKED_RAM_HOTSPOT..=Self::HOTSPOT_END => {
+                let bank = (address - Self::BANKED_RAM_HOTSPOT) as u8;
+                let _ = self.banked_ram.select_bank(bank);
+            },
+            _ => (),
+        }
+    }
+}
+
+impl Mapper for E7 {
+    fn rom(&self) -> &Rom {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut Rom {
+        &mut self.rom
+    }
+
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        self.access(address);
+        let data_bus = self.data_bus;
+        let mut data = self.banked_ram.read(address, data_bus);
+        if data.is_none() && self.ram_selected {
+            data = self.ram.read(address, data_bus);
+        }
+        let data = match data {
+            Some(data) => data,
+            None => self.rom.read(address)?,
+        };
+        self.data_bus = data;
+        Ok(data)
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError> {
+        self.access(address);
+        if !self.banked_ram.write(address, data) && self.ram_selected {
+            self.ram.write(address, data);
+        }
+        self.data_bus = data;
+        Ok(())
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        let mut data = self.banked_ram.peek(address);
+        if data.is_none() && self.ram_selected {
+            data = self.ram.peek(address);
+        }
+        match data {
+            Some(data) => Ok(data),
+            None => self.rom.read(address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Eight 2K chunks, each filled with its own number.
+    fn e7() -> E7 {
+        let bank = |first: u8| {
+            let mut bytes = [first; RomBank::SIZE];
+            bytes[2048..].fill(first + 1);
+            RomBank::new(bytes)
+        };
+        E7::new(Rom::new(bank(0), [bank(2), bank(4), bank(6)])).unwrap()
+    }
+
+    #[test]
+    fn rom_hotspots() {
+        let mut e7 = e7();
+        assert_eq!(e7.peek(0x1000).unwrap(), 0);
+        assert_eq!(e7.peek(0x1A00).unwrap(), 7);
+        e7.read(0x1FE5).unwrap();
+        assert_eq!(e7.peek(0x1000).unwrap(), 5);
+        e7.write(0x1FE6, 0).unwrap();
+        assert_eq!(e7.peek(0x17FF).unwrap(), 6);
+        assert_eq!(e7.peek(0x1FFF).unwrap(), 7);
+    }
+
+    #[test]
+    fn ram_replaces_the_lower_half() {
+        let mut e7 = e7();
+        e7.read(0x1FE7).unwrap();
+        assert!(e7.ram_selected());
+        e7.write(0x1010, 0x42).unwrap();
+        assert_eq!(e7.peek(0x1410).unwrap(), 0x42);
+        e7.read(0x1FE1).unwrap();
+        assert!(!e7.ram_selected());
+        assert_eq!(e7.peek(0x1410).unwrap(), 1);
+        e7.write(0x1010, 0x43).unwrap();
+        e7.read(0x1FE7).unwrap();
+        assert_eq!(e7.peek(0x1410).unwrap(), 0x42);
+    }
+
+    #[test]
+    fn banked_ram_hotspots() {
+        let mut e7 = e7();
+        e7.read(0x1FE9).unwrap();
+        e7.write(0x1820, 0x55).unwrap();
+        assert_eq!(e7.peek(0x1920).unwrap(), 0x55);
+        e7.read(0x1FEA).unwrap();
+        assert_eq!(e7.peek(0x1920).unwrap(), 0);
+        e7.read(0x1FE9).unwrap();
+        assert_eq!(e7.peek(0x1920).unwrap(), 0x55);
+        assert_eq!(e7.banked_ram().selected_index(), 1);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{
+    cartridge::{Mapper, Scheme},
+    error::{ReadError, SchemeError, WriteError},
+    memory::{Rom, RomBank},
+};
+
+#[derive(Debug, Clone)]
+pub struct Fe {
+    rom: Rom,
+    data_bus: u8,
+    armed: bool,
+}
+
+impl Fe {
+    pub const BANKS: usize = 2;
+    pub const LOW_STACK: u16 = 0x01FE;
+    pub const HIGH_STACK: u16 = 0x01FF;
+
+    pub fn new(rom: Rom) -> Result<Self, SchemeError> {
+        if rom.banks() != Self::BANKS {
+            return Err(SchemeError {
+                scheme: Scheme::Fe,
+                size: rom.banks() * RomBank::SIZE,
+            });
+        }
+        Ok(Self { rom, data_bus: 0, armed: false })
+    }
+
+    // The bank comes from D5 of the high byte of the address being jumped
+    // to: set for the $F000 bank and clear for the $D000 one.
+    fn select(&mut self, high: u8) {
+        let bank = if high & 0x20 != 0 { 0 } else { 1 };
+        let _ = self.rom.select_bank(bank);
+    }
+}
+
+impl Mapper for Fe {
+    fn rom(&self) -> &Rom {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut Rom {
+        &mut self.rom
+    }
+
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        let data = self.rom.read(address)?;
+        self.data_bus = data;
+        Ok(data)
+    }
+
+    fn write(&mut self, _address: u16, data: u8) -> Result<(), WriteError> {
+        self.data_bus = data;
+        Ok(())
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        self.rom.read(address)
+    }
+
+    // The cart watches for the stack access at $01FE that JSR and RTS both
+    // make with the stack pointer at $FF, and takes the byte on the data bus
+    // right after it. For JSR that is the high byte of the target, which
+    // the CPU here has already fetched from the cartridge by the time it
+    // pushes the return address.
+    fn snoop(&mut self, address: u16, _data: u8) {
+        self.armed = false;
+        if address == Self::LOW_STACK {
+            self.select(self.data_bus);
+        }
+    }
+
+    // For RTS it is the high byte of the return address, pulled from $01FF
+    // right after $01FE.
+    fn snoop_read(&mut self, address: u16, data: u8) {
+        if self.armed && address == Self::HIGH_STACK {
+            self.select(data);
+        }
+        self.armed = address == Self::LOW_STACK;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bus::Bus,
+        machine::Machine,
+        memory::{Memory, Ram},
+    };
+
+    // Bank 0 lives at $F000 and calls a subroutine in bank 1 at $D000.
+    fn machine() -> Machine<Memory> {
+        let mut high = [0xEA; RomBank::SIZE];
+        // ldx #$FF; txs; jsr $D100
+        high[..6].copy_from_slice(&[0xA2, 0xFF, 0x9A, 0x20, 0x00, 0xD1]);
+        high[0xFFC..].copy_from_slice(&[0x00, 0xF0, 0x00, 0xF0]);
+        let mut low = [0xEA; RomBank::SIZE];
+        // rts
+        low[0x100] = 0x60;
+        let rom = Rom::new(RomBank::new(high), Some(RomBank::new(low)));
+        Machine::new(Memory::new(Ram::new(), Fe::new(rom).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn stack_access_selects_bank_from_d5() {
+        let mut machine = machine();
+        for _ in 0..2 {
+            machine.step().unwrap();
+        }
+        assert_eq!(machine.bus().selected_bank(), 0);
+
+        // JSR pushes $F005 past $01FE while the bus still holds $D1.
+        machine.step().unwrap();
+        assert_eq!(machine.pc(), 0xD100);
+        assert_eq!(machine.bus().selected_bank(), 1);
+        assert_eq!(machine.bus().peek(0x01FE).unwrap(), 0x05);
+
+        // RTS pulls $F0 from $01FF right after reading $01FE.
+        machine.step().unwrap();
+        assert_eq!(machine.pc(), 0xF006);
+        assert_eq!(machine.bus().selected_bank(), 0);
+    }
+
+    #[test]
+    fn other_stack_addresses_are_ignored() {
+        let mut fe = Fe::new(Rom::new(
+            RomBank::new([0; RomBank::SIZE]),
+            Some(RomBank::new([0; RomBank::SIZE])),
+        ))
+        .unwrap();
+        fe.write(0x1000, 0xD0).unwrap();
+        fe.snoop(0x01FD, 0x00);
+        assert_eq!(fe.rom().selected_index(), 0);
+        fe.snoop(0x01FE, 0x00);
+        assert_eq!(fe.rom().selected_index(), 1);
+        fe.snoop_read(0x01FF, 0xF0);
+        assert_eq!(fe.rom().selected_index(), 1);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{
+    arm::{ArmBus, Thumb, Width},
+    cartridge::Dpc,
+    error::ArmError,
+};
+use std::sync::Arc;
+
+// The LPC2103 on Harmony and Melody boards: flash holding the whole image
+// at the bottom of the address space, 8K of static RAM and the on-chip
+// peripherals at the top.
+#[derive(Debug, Clone)]
+pub struct Harmony {
+    flash: Arc<[u8]>,
+    ram: Box<[u8; Harmony::RAM_SIZE]>,
+}
+
+impl Harmony {
+    pub const FLASH_BASE: u32 = 0x0000_0000;
+    pub const RAM_BASE: u32 = 0x4000_0000;
+    pub const RAM_SIZE: usize = 8192;
+    pub const PERIPHERALS_BASE: u32 = 0xE000_0000;
+    pub const CLOCK_RATE: u64 = 70_000_000;
+    pub const STACK_TOP: u32 = 0x4000_1FB4;
+    pub const CYCLE_LIMIT: u64 = Self::CLOCK_RATE;
+
+    // The memory accelerator hides most of the flash latency on straight
+    // runs of code, so a single wait state stands for its average cost.
+    pub const FLASH_WAIT_STATES: u64 = 1;
+
+    pub fn new(flash: &[u8]) -> Self {
+        Self { flash: flash.into(), ram: Box::new([0; Self::RAM_SIZE]) }
+    }
+
+    pub fn flash(&self) -> &[u8] {
+        &self.flash
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram[..]
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram[..]
+    }
+
+    pub fn ram_word(&self, offset: usize) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.ram[offset..offset + 4]);
+        u32::from_le_bytes(bytes)
+    }
+
+    pub fn set_ram_word(&mut self, offset: usize, value: u32) {
+        self.ram[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    // Runs a function of the game's C code from a fresh register file. The
+    // link register holds an ARM state address, so the final return ends
+    // the run. The result is how many 6507 cycles the ARM took.
+    pub fn call<B>(
+        bus: &mut B,
+        arm: &mut Thumb,
+        entry: u32,
+    ) -> Result<u64, ArmError>
+    where
+        B: ArmBus,
+    {
+        for index in 0..Thumb::SP {
+            arm.set_register(index, 0);
+        }
+        arm.set_register(Thumb::SP, Self::STACK_TOP);
+        arm.set_register(Thumb::LR, entry & !0xFF);
+        arm.set_register(Thumb::PC, entry | 1);
+        let cycles = arm.run(bus, Self::CYCLE_LIMIT)?;
+        let rate = u64::from(Dpc::NTSC_COLOR_CLOCK_RATE) / 3;
+        Ok((cycles * rate).div_ceil(Self::CLOCK_RATE))
+    }
+
+    fn bytes(&self, address: u32, width: Width) -> Option<&[u8]> {
+        let size = width.bytes();
+        if let Some(offset) = address.checked_sub(Self::RAM_BASE) {
+            let offset = offset as usize;
+            self.ram.get(offset..offset.checked_add(size)?)
+        } else {
+            let offset = (address - Self::FLASH_BASE) as usize;
+            self.flash.get(offset..offset.checked_add(size)?)
+        }
+    }
+}
+
+impl ArmBus for Harmony {
+    // Peripherals (timers, mostly) are not emulated: they read as zero and
+    // ignore writes.
+    fn read(&mut self, address: u32, width: Width) -> Result<u32, ArmError> {
+        if address >= Self::PERIPHERALS_BASE {
+            return Ok(0);
+        }
+        let bytes =
+            self.bytes(address, width).ok_or(ArmError::Read { address })?;
+        let mut word = [0; 4];
+        word[..bytes.len()].copy_from_slice(bytes);
+        Ok(u32::from_le_bytes(word))
+    }
+
+    fn write(
+        &mut self,
+        address: u32,
+        width: Width,
+        data: u32,
+    ) -> Result<(), ArmError> {
+        if address >= Self::PERIPHERALS_BASE {
+            return Ok(());
+        }
+        let size = width.bytes();
+        let offset = address
+            .checked_sub(Self::RAM_BASE)
+            .map(|offset| offset as usize)
+            .filter(|offset| offset + size <= Self::RAM_SIZE)
+            .ok_or(ArmError::Write { address })?;
+        self.ram[offset..offset + size]
+            .copy_from_slice(&data.to_le_bytes()[..size]);
+        Ok(())
+    }
+
+    fn wait_states(&self, address: u32) -> u64 {
+        if address < Self::RAM_BASE {
+            Self::FLASH_WAIT_STATES
+        } else {
+            0
+        }
+    }
+}
+
+// Both drivers advance their music counters at 20 kHz, spread over CPU
+// cycles the same way the DPC does it.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Oscillator {
+    remainder: u32,
+}
+
+impl Oscillator {
+    pub(crate) fn clock(&mut self) -> u32 {
+        self.remainder += 3 * Dpc::OSCILLATOR_RATE;
+        let ticks = self.remainder / Dpc::NTSC_COLOR_CLOCK_RATE;
+        self.remainder %= Dpc::NTSC_COLOR_CLOCK_RATE;
+        ticks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY: u32 = 0x0808;
+
+    fn harmony(code: &[u16]) -> Harmony {
+        let mut flash = vec![0; 0x1000];
+        for (index, half) in code.iter().enumerate() {
+            let offset = ENTRY as usize + 2 * index;
+            flash[offset..offset + 2].copy_from_slice(&half.to_le_bytes());
+        }
+        Harmony::new(&flash)
+    }
+
+    // 800 ARM cycles at 70 MHz, counting one wait state per flash access,
+    // are just under 14 cycles of the 6507.
+    #[test]
+    fn call_returns_the_stall() {
+        let mut harmony = harmony(&[
+            0x2064, // MOVS r0, #100
+            0x3801, // loop: SUBS r0, #1
+            0xD1FD, // BNE loop
+            0x4770, // BX lr
+        ]);
+        let mut arm = Thumb::new();
+        arm.set_register(0, 7);
+        let stall = Harmony::call(&mut harmony, &mut arm, ENTRY).unwrap();
+        assert_eq!(arm.cycles(), 800);
+        assert_eq!(stall, 14);
+        assert_eq!(arm.register(0), 0);
+        assert_eq!(arm.register(Thumb::SP), Harmony::STACK_TOP);
+    }
+
+    #[test]
+    fn stores_reach_ram_only() {
+        let mut harmony = harmony(&[]);
+        harmony.write(Harmony::RAM_BASE + 4, Width::Word, 0x1234_5678).unwrap();
+        assert_eq!(harmony.ram_word(4), 0x1234_5678);
+        assert_eq!(
+            harmony.write(0x100, Width::Byte, 0),
+            Err(ArmError::Write { address: 0x100 })
+        );
+        harmony.write(Harmony::PERIPHERALS_BASE, Width::Word, 1).unwrap();
+        let timer = harmony.read(Harmony::PERIPHERALS_BASE, Width::Word);
+        assert_eq!(timer, Ok(0));
+    }
+
+    #[test]
+    fn runaway_code_times_out() {
+        // B to itself
+        let mut harmony = harmony(&[0xE7FE]);
+        let mut arm = Thumb::new();
+        let result = Harmony::call(&mut harmony, &mut arm, ENTRY);
+        assert!(matches!(result, Err(ArmError::Timeout { .. })));
+    }
+}
@@ -0,0 +1,128 @@
+use crate::error::BankError;
+
+#[derive(Debug, Clone)]
+pub struct ExtraRam {
+    bytes: Box<[u8]>,
+    window: u16,
+    write_port: u16,
+    read_port: u16,
+    selected: u8,
+}
+
+impl ExtraRam {
+    pub const SUPERCHIP_SIZE: u16 = 128;
+    pub const SUPERCHIP_WRITE_PORT: u16 = 0x1000;
+    pub const SUPERCHIP_READ_PORT: u16 = 0x1080;
+
+    pub fn new(
+        size: usize,
+        window: u16,
+        write_port: u16,
+        read_port: u16,
+    ) -> Self {
+        Self {
+            bytes: vec![0; size].into_boxed_slice(),
+            window,
+            write_port,
+            read_port,
+            selected: 0,
+        }
+    }
+
+    pub fn superchip() -> Self {
+        Self::new(
+            usize::from(Self::SUPERCHIP_SIZE),
+            Self::SUPERCHIP_SIZE,
+            Self::SUPERCHIP_WRITE_PORT,
+            Self::SUPERCHIP_READ_PORT,
+        )
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn banks(&self) -> usize {
+        self.bytes.len() / usize::from(self.window)
+    }
+
+    pub fn selected_index(&self) -> u8 {
+        self.selected
+    }
+
+    pub fn select_bank(&mut self, bank: u8) -> Result<(), BankError> {
+        if usize::from(bank) < self.banks() {
+            self.selected = bank;
+            Ok(())
+        } else {
+            Err(BankError { bank })
+        }
+    }
+
+    // There is no R/W line on the cartridge port, so the chip is written on
+    // any access to the write port. A read there stores whatever is left on
+    // the data bus and the CPU gets that same garbage back.
+    pub fn read(&mut self, address: u16, data_bus: u8) -> Option<u8> {
+        if let Some(index) = self.index(address, self.write_port) {
+            self.bytes[index] = data_bus;
+            Some(data_bus)
+        } else {
+            self.peek(address)
+        }
+    }
+
+    pub fn write(&mut self, address: u16, data: u8) -> bool {
+        match self.index(address, self.write_port) {
+            Some(index) => {
+                self.bytes[index] = data;
+                true
+            },
+            None => self.index(address, self.read_port).is_some(),
+        }
+    }
+
+    pub fn peek(&self, address: u16) -> Option<u8> {
+        self.index(address, self.read_port).map(|index| self.bytes[index])
+    }
+
+    fn index(&self, address: u16, port: u16) -> Option<usize> {
+        let offset = address.wrapping_sub(port);
+        if offset < self.window {
+            let base = usize::from(self.selected) * usize::from(self.window);
+            Some(base + usize::from(offset))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn superchip_ports() {
+        let mut ram = ExtraRam::superchip();
+        assert!(ram.write(0x1000, 0x12));
+        assert!(ram.write(0x107F, 0x34));
+        assert_eq!(ram.peek(0x1080), Some(0x12));
+        assert_eq!(ram.peek(0x10FF), Some(0x34));
+        assert_eq!(ram.peek(0x1000), None);
+        assert_eq!(ram.peek(0x1100), None);
+        assert!(!ram.write(0x1100, 0x56));
+    }
+
+    #[test]
+    fn writes_to_the_read_port_are_swallowed() {
+        let mut ram = ExtraRam::superchip();
+        assert!(ram.write(0x1080, 0x56));
+        assert_eq!(ram.peek(0x1080), Some(0x00));
+    }
+
+    #[test]
+    fn reading_the_write_port_stores_the_data_bus() {
+        let mut ram = ExtraRam::superchip();
+        assert_eq!(ram.read(0x1005, 0xAB), Some(0xAB));
+        assert_eq!(ram.read(0x1085, 0x00), Some(0xAB));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{
+    cartridge::{ExtraRam, Mapper, Scheme},
+    error::{ReadError, SchemeError, WriteError},
+    memory::{Rom, RomBank},
+};
+
+#[derive(Debug, Clone)]
+pub struct Standard {
+    rom: Rom,
+    scheme: Scheme,
+    hotspot: u16,
+    ram: Option<ExtraRam>,
+    data_bus: u8,
+}
+
+impl Standard {
+    pub const F8_HOTSPOT: u16 = 0x1FF8;
+    pub const F6_HOTSPOT: u16 = 0x1FF6;
+    pub const F4_HOTSPOT: u16 = 0x1FF4;
+    pub const FA_HOTSPOT: u16 = 0x1FF8;
+    pub const EF_HOTSPOT: u16 = 0x1FE0;
+
+    pub fn f8(rom: Rom) -> Result<Self, SchemeError> {
+        Self::new(rom, Scheme::F8, Self::F8_HOTSPOT, 2, 1)
+    }
+
+    pub fn f6(rom: Rom) -> Result<Self, SchemeError> {
+        Self::new(rom, Scheme::F6, Self::F6_HOTSPOT, 4, 0)
+    }
+
+    pub fn f4(rom: Rom) -> Result<Self, SchemeError> {
+        Self::new(rom, Scheme::F4, Self::F4_HOTSPOT, 8, 0)
+    }
+
+    // CBS RAM Plus carts have 256 bytes of RAM, written at $F000-$F0FF and
+    // read back at $F100-$F1FF.
+    pub fn fa(rom: Rom) -> Result<Self, SchemeError> {
+        let mut this = Self::new(rom, Scheme::Fa, Self::FA_HOTSPOT, 3, 0)?;
+        this.ram = Some(ExtraRam::new(256, 256, 0x1000, 0x1100));
+        Ok(this)
+    }
+
+    pub fn ef(rom: Rom) -> Result<Self, SchemeError> {
+        Self::new(rom, Scheme::Ef, Self::EF_HOTSPOT, 16, 0)
+    }
+
+    fn new(
+        mut rom: Rom,
+        scheme: Scheme,
+        hotspot: u16,
+        banks: usize,
+        start: u8,
+    ) -> Result<Self, SchemeError> {
+        let size = rom.banks() * RomBank::SIZE;
+        if rom.banks() != banks || rom.select_bank(start).is_err() {
+            return Err(SchemeError { scheme, size });
+        }
+        Ok(Self { rom, scheme, hotspot, ram: None, data_bus: 0 })
+    }
+
+    pub fn with_superchip(mut self) -> Self {
+        self.scheme = match self.scheme {
+            Scheme::F8 => Scheme::F8Sc,
+            Scheme::F6 => Scheme::F6Sc,
+            Scheme::F4 => Scheme::F4Sc,
+            Scheme::Ef => Scheme::EfSc,
+            scheme => scheme,
+        };
+        self.ram = Some(ExtraRam::superchip());
+        self
+    }
+
+    pub fn scheme(&self) -> Scheme {
+        self.scheme
+    }
+
+    pub fn ram(&self) -> Option<&ExtraRam> {
+        self.ram.as_ref()
+    }
+
+    // Any access to a hotspot, read or write, switches the whole 4K window.
+    fn access(&mut self, address: u16) {
+        let bank = address.wrapping_sub(self.hotspot);
+        if bank < self.rom.banks() as u16 {
+            let _ = self.rom.select_bank(bank as u8);
+        }
+    }
+}
+
+impl Mapper for Standard {
+    fn rom(&self) -> &Rom {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut Rom {
+        &mut self.rom
+    }
+
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        self.access(address);
+        let data_bus = self.data_bus;
+        let data =
+            match self.ram.as_mut().and_then(|ram| ram.read(address, data_bus))
+            {
+                Some(data) => data,
+                None => self.rom.read(address)?,
+            };
+        self.data_bus = data;
+        Ok(data)
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError> {
+        self.access(address);
+        if let Some(ram) = &mut self.ram {
+            ram.write(address, data);
+        }
+        self.data_bus = data;
+        Ok(())
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        match self.ram.as_ref().and_then(|ram| ram.peek(address)) {
+            Some(data) => Ok(data),
+            None => self.rom.read(address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every bank is filled with its own number.
+    fn rom(banks: u8) -> Rom {
+        let bank = |n| RomBank::new([n; RomBank::SIZE]);
+        Rom::new(bank(0), (1..banks).map(bank))
+    }
+
+    #[test]
+    fn f8_starts_in_the_last_bank() {
+        let mut f8 = Standard::f8(rom(2)).unwrap();
+        assert_eq!(f8.peek(0x1000).unwrap(), 1);
+        f8.read(0x1FF8).unwrap();
+        assert_eq!(f8.peek(0x1000).unwrap(), 0);
+        f8.write(0x1FF9, 0).unwrap();
+        assert_eq!(f8.peek(0x1000).unwrap(), 1);
+    }
+
+    #[test]
+    fn f6_hotspots() {
+        let mut f6 = Standard::f6(rom(4)).unwrap();
+        assert_eq!(f6.peek(0x1000).unwrap(), 0);
+        for bank in 0..4 {
+            f6.read(0x1FF6 + bank).unwrap();
+            assert_eq!(f6.peek(0x1234).unwrap(), bank as u8);
+        }
+        f6.read(0x1FFA).unwrap();
+        f6.read(0x1FF5).unwrap();
+        assert_eq!(f6.peek(0x1234).unwrap(), 3);
+    }
+
+    #[test]
+    fn f4_hotspots() {
+        let mut f4 = Standard::f4(rom(8)).unwrap();
+        for bank in (0..8).rev() {
+            f4.write(0x1FF4 + bank, 0).unwrap();
+            assert_eq!(f4.peek(0x1FFF).unwrap(), bank as u8);
+        }
+        f4.read(0x1FFC).unwrap();
+        assert_eq!(f4.peek(0x1FFF).unwrap(), 0);
+    }
+
+    #[test]
+    fn wrong_bank_count_is_rejected() {
+        assert!(Standard::f8(rom(4)).is_err());
+        assert!(Standard::f6(rom(2)).is_err());
+        assert!(Standard::f4(rom(4)).is_err());
+    }
+
+    #[test]
+    fn superchip_over_banked_rom() {
+        let mut f8sc = Standard::f8(rom(2)).unwrap().with_superchip();
+        assert_eq!(f8sc.scheme(), Scheme::F8Sc);
+        f8sc.write(0x1010, 0x99).unwrap();
+        f8sc.read(0x1FF8).unwrap();
+        assert_eq!(f8sc.read(0x1090).unwrap(), 0x99);
+        assert_eq!(f8sc.peek(0x1100).unwrap(), 0);
+        // The last value on the bus was $99, which a read of the write port
+        // stores into the RAM and returns.
+        assert_eq!(f8sc.read(0x1011).unwrap(), 0x99);
+        assert_eq!(f8sc.peek(0x1091).unwrap(), 0x99);
+    }
+}
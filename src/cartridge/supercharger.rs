@@ -0,0 +1,392 @@
+use crate::{
+    cartridge::{Mapper, Scheme},
+    error::{ReadError, SchemeError, WriteError},
+    memory::{Rom, RomBank},
+};
+
+#[derive(Debug, Clone)]
+pub struct Load {
+    data: Box<[u8; Load::DATA_SIZE]>,
+    header: [u8; Load::HEADER_SIZE],
+}
+
+impl Load {
+    pub const DATA_SIZE: usize = 8192;
+    pub const HEADER_SIZE: usize = 256;
+    pub const SIZE: usize = Self::DATA_SIZE + Self::HEADER_SIZE;
+    pub const PAGE_SIZE: usize = 256;
+
+    pub fn new(
+        data: [u8; Self::DATA_SIZE],
+        header: [u8; Self::HEADER_SIZE],
+    ) -> Self {
+        Self { data: Box::new(data), header }
+    }
+
+    pub fn try_new(slice: &[u8]) -> Option<Self> {
+        if slice.len() != Self::SIZE {
+            return None;
+        }
+        let mut data = [0; Self::DATA_SIZE];
+        let mut header = [0; Self::HEADER_SIZE];
+        data.copy_from_slice(&slice[..Self::DATA_SIZE]);
+        header.copy_from_slice(&slice[Self::DATA_SIZE..]);
+        Some(Self::new(data, header))
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data[..]
+    }
+
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    pub fn start_address(&self) -> u16 {
+        u16::from_le_bytes([self.header[0], self.header[1]])
+    }
+
+    pub fn configuration(&self) -> u8 {
+        self.header[2]
+    }
+
+    pub fn pages(&self) -> usize {
+        usize::from(self.header[3]).min(Self::DATA_SIZE / Self::PAGE_SIZE)
+    }
+
+    pub fn number(&self) -> u8 {
+        self.header[5]
+    }
+
+    // Each page of the load carries a location byte telling the BIOS which
+    // RAM bank (low two bits) and which page of that bank it goes to.
+    pub fn page_location(&self, page: usize) -> (usize, usize) {
+        let location = self.header[16 + page];
+        (usize::from(location & 0x03), usize::from((location >> 2) & 0x07))
+    }
+
+    pub fn page(&self, page: usize) -> &[u8] {
+        &self.data[page * Self::PAGE_SIZE..(page + 1) * Self::PAGE_SIZE]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Supercharger {
+    bios: Rom,
+    ram: Box<[u8; Supercharger::RAM_SIZE]>,
+    slots: [usize; 2],
+    configuration: u8,
+    write_enabled: bool,
+    data_hold: u8,
+    write_pending: bool,
+    distinct_accesses: u8,
+    last_address: u16,
+    loads: Vec<Load>,
+    requested_load: u8,
+    booted: bool,
+}
+
+impl Supercharger {
+    pub const BANK_SIZE: usize = 2048;
+    pub const RAM_BANKS: usize = 3;
+    pub const RAM_SIZE: usize = Self::RAM_BANKS * Self::BANK_SIZE;
+    pub const BIOS_BANK: usize = 3;
+    pub const HOTSPOT: u16 = 0x1FF8;
+    pub const LOAD_TRAP: u16 = 0x1850;
+    pub const LOAD_NUMBER: u16 = 0x80;
+    pub const WRITE_DELAY: u8 = 5;
+
+    // Bank pairs mapped at $F000 and $F800 for each value of bits 2-4 of
+    // the configuration byte, bank 3 being the BIOS.
+    const LAYOUTS: [[usize; 2]; 8] =
+        [[2, 3], [0, 3], [2, 0], [0, 2], [2, 3], [1, 3], [2, 1], [1, 2]];
+
+    const BIOS_ENTRY: u16 = 0xF800;
+    const TRAMPOLINE: usize = 0x20;
+
+    // $F800: LDA $FA; STA $80; SEI; CLD; LDX #$FF; TXS; LDA $F850; LDX #8
+    // $F80E: LDA $F820,X; STA $F0,X; DEX; BPL $F80E; JMP $00F0
+    const BIOS_CODE: [u8; 25] = [
+        0xA5, 0xFA, 0x85, 0x80, 0x78, 0xD8, 0xA2, 0xFF, 0x9A, 0xAD, 0x50, 0xF8,
+        0xA2, 0x08, 0xBD, 0x20, 0xF8, 0x95, 0xF0, 0xCA, 0x10, 0xF8, 0x4C, 0xF0,
+        0x00,
+    ];
+
+    // $F820: CMP $F0xx; CMP $FFF8; JMP start
+    const BIOS_TRAMPOLINE: [u8; 9] =
+        [0xCD, 0x00, 0xF0, 0xCD, 0xF8, 0xFF, 0x4C, 0x00, 0xF8];
+
+    pub fn new(image: &[u8]) -> Result<Self, SchemeError> {
+        if image.is_empty() || !image.len().is_multiple_of(Load::SIZE) {
+            return Err(SchemeError {
+                scheme: Scheme::Supercharger,
+                size: image.len(),
+            });
+        }
+        let loads = image.chunks(Load::SIZE).filter_map(Load::try_new);
+        Ok(Self::with_loads(loads))
+    }
+
+    pub fn with_loads<I>(loads: I) -> Self
+    where
+        I: IntoIterator<Item = Load>,
+    {
+        let mut this = Self {
+            bios: Self::bios(0, Self::BIOS_ENTRY),
+            ram: Box::new([0; Self::RAM_SIZE]),
+            slots: [0; 2],
+            configuration: 0,
+            write_enabled: false,
+            data_hold: 0,
+            write_pending: false,
+            distinct_accesses: 0,
+            last_address: 0,
+            loads: loads.into_iter().collect(),
+            requested_load: 0,
+            booted: false,
+        };
+        this.configure(0);
+        this
+    }
+
+    pub fn loads(&self) -> &[Load] {
+        &self.loads
+    }
+
+    pub fn push_load(&mut self, load: Load) {
+        self.loads.push(load);
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram[..]
+    }
+
+    pub fn configuration(&self) -> u8 {
+        self.configuration
+    }
+
+    pub fn write_enabled(&self) -> bool {
+        self.write_enabled
+    }
+
+    // Stands in for the BIOS: it saves the requested load number where the
+    // real one does, hits the load trap, and then copies a trampoline to
+    // zero page which sets the configuration from the load header and jumps
+    // to its start address, since RAM may replace this bank once configured.
+    fn bios(configuration: u8, start: u16) -> Rom {
+        let mut bytes = [0; Self::BANK_SIZE];
+        let [bios_low, bios_high] = Self::BIOS_ENTRY.to_le_bytes();
+        let [start_low, start_high] = start.to_le_bytes();
+        let mut trampoline = Self::BIOS_TRAMPOLINE;
+        trampoline[1] = configuration;
+        trampoline[7] = start_low;
+        trampoline[8] = start_high;
+        bytes[..Self::BIOS_CODE.len()].copy_from_slice(&Self::BIOS_CODE);
+        bytes[Self::TRAMPOLINE..Self::TRAMPOLINE + trampoline.len()]
+            .copy_from_slice(&trampoline);
+        for vector in [0x7FC, 0x7FE].iter() {
+            bytes[*vector] = bios_low;
+            bytes[*vector + 1] = bios_high;
+        }
+        let bank = RomBank::mirrored(&bytes).expect("BIOS fits in a bank");
+        Rom::new(bank, None)
+    }
+
+    fn configure(&mut self, configuration: u8) {
+        self.configuration = configuration;
+        self.write_enabled = configuration & 0x02 != 0;
+        self.slots = Self::LAYOUTS[usize::from((configuration >> 2) & 0x07)];
+    }
+
+    // The first call after power-up takes whatever comes first on the
+    // tape, later ones look for the load number the game asked for. A load
+    // that is missing from the image leaves RAM as it is.
+    fn load(&mut self) {
+        let index = if self.booted {
+            let number = self.requested_load;
+            self.loads.iter().position(|load| load.number() == number)
+        } else if self.loads.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+        let load = match index {
+            Some(index) => &self.loads[index],
+            None => return,
+        };
+
+        for page in 0..load.pages() {
+            let (bank, offset) = load.page_location(page);
+            if bank < Self::RAM_BANKS {
+                let start = bank * Self::BANK_SIZE + offset * Load::PAGE_SIZE;
+                self.ram[start..start + Load::PAGE_SIZE]
+                    .copy_from_slice(load.page(page));
+            }
+        }
+        self.bios = Self::bios(load.configuration(), load.start_address());
+        self.booted = true;
+    }
+
+    // Writes to the RAM are delayed: touching $F0xx latches xx in the data
+    // hold register, and the fifth distinct address seen on the bus after
+    // that receives it. Accesses outside the cartridge count as well, the
+    // cartridge sees them through snooping.
+    fn observe(&mut self, address: u16) {
+        if address != self.last_address {
+            self.last_address = address;
+            if self.write_pending {
+                self.distinct_accesses += 1;
+                if self.distinct_accesses > Self::WRITE_DELAY {
+                    self.write_pending = false;
+                }
+            }
+        }
+    }
+
+    fn access(&mut self, address: u16) {
+        if address == Self::LOAD_TRAP && self.slots[1] == Self::BIOS_BANK {
+            self.load();
+        } else if address & 0x0F00 == 0
+            && (!self.write_enabled || !self.write_pending)
+        {
+            self.data_hold = address as u8;
+            self.write_pending = true;
+            self.distinct_accesses = 0;
+        } else if address == Self::HOTSPOT {
+            self.write_pending = false;
+            self.configure(self.data_hold);
+        } else if self.write_enabled
+            && self.write_pending
+            && self.distinct_accesses == Self::WRITE_DELAY
+        {
+            if let Some(index) = self.ram_index(address) {
+                self.ram[index] = self.data_hold;
+            }
+            self.write_pending = false;
+        }
+    }
+
+    fn ram_index(&self, address: u16) -> Option<usize> {
+        let bank = self.slots[usize::from(address & 0x0800 != 0)];
+        if bank < Self::RAM_BANKS {
+            Some(bank * Self::BANK_SIZE + usize::from(address & 0x07FF))
+        } else {
+            None
+        }
+    }
+}
+
+impl Mapper for Supercharger {
+    fn rom(&self) -> &Rom {
+        &self.bios
+    }
+
+    fn rom_mut(&mut self) -> &mut Rom {
+        &mut self.bios
+    }
+
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        self.observe(address);
+        self.access(address);
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: u16, _data: u8) -> Result<(), WriteError> {
+        self.observe(address);
+        self.access(address);
+        Ok(())
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        match self.ram_index(address) {
+            Some(index) => Ok(self.ram[index]),
+            None => self.bios.read(address),
+        }
+    }
+
+    fn snoop(&mut self, address: u16, data: u8) {
+        self.observe(address);
+        if address == Self::LOAD_NUMBER {
+            self.requested_load = data;
+        }
+    }
+
+    fn snoop_read(&mut self, address: u16, _data: u8) {
+        self.observe(address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fills each RAM bank with its own number so that a peek tells which
+    // bank is mapped; the BIOS starts with LDA zero page.
+    fn banks_at(configuration: u8) -> [u8; 2] {
+        let mut supercharger = Supercharger::with_loads(None);
+        for (bank, chunk) in
+            supercharger.ram.chunks_mut(Supercharger::BANK_SIZE).enumerate()
+        {
+            chunk.iter_mut().for_each(|byte| *byte = bank as u8);
+        }
+        supercharger.configure(configuration << 2);
+        let bank = |address| match supercharger.peek(address).unwrap() {
+            0xA5 => 3,
+            bank => bank,
+        };
+        [bank(0x1000), bank(0x1800)]
+    }
+
+    #[test]
+    fn write_delay_counts_reads_outside_the_cartridge() {
+        let mut supercharger = Supercharger::with_loads(None);
+        supercharger.configure(0x02);
+        supercharger.read(0x1042).unwrap();
+        supercharger.snoop_read(0x1042, 0);
+        for address in 0x80..0x84 {
+            supercharger.snoop_read(address, 0);
+        }
+        supercharger.read(0x1100).unwrap();
+        assert_eq!(supercharger.peek(0x1100).unwrap(), 0x42);
+    }
+
+    #[test]
+    fn configuration_0() {
+        assert_eq!(banks_at(0), [2, 3]);
+    }
+
+    #[test]
+    fn configuration_1() {
+        assert_eq!(banks_at(1), [0, 3]);
+    }
+
+    #[test]
+    fn configuration_2() {
+        assert_eq!(banks_at(2), [2, 0]);
+    }
+
+    #[test]
+    fn configuration_3() {
+        assert_eq!(banks_at(3), [0, 2]);
+    }
+
+    #[test]
+    fn configuration_4() {
+        assert_eq!(banks_at(4), [2, 3]);
+    }
+
+    #[test]
+    fn configuration_5() {
+        assert_eq!(banks_at(5), [1, 3]);
+    }
+
+    #[test]
+    fn configuration_6() {
+        assert_eq!(banks_at(6), [2, 1]);
+    }
+
+    #[test]
+    fn configuration_7() {
+        assert_eq!(banks_at(7), [1, 2]);
+    }
+}
@@ -0,0 +1,362 @@
+use crate::{
+    cartridge::Load,
+    error::{ChecksumError, TapeBlock, TapeError, WavError},
+};
+
+#[derive(Debug, Clone)]
+pub struct Tape {
+    loads: Vec<Load>,
+    damaged: Vec<ChecksumError>,
+}
+
+impl Tape {
+    pub const LEADER: u8 = 0x55;
+    pub const SYNC: u8 = 0x54;
+    pub const CHECKSUM: u8 = 0x55;
+    pub const HEADER_SIZE: usize = 8;
+
+    pub fn new(loads: Vec<Load>) -> Self {
+        Self { loads, damaged: Vec::new() }
+    }
+
+    // Loads with a bad checksum are left out and listed as damaged, so one
+    // bad spot on the tape does not cost the loads after it.
+    pub fn from_wav(bytes: &[u8]) -> Result<Self, TapeError> {
+        let samples = read_wav(bytes)?;
+        let bits = demodulate(&samples);
+        let mut reader = BitReader { bits: &bits, position: 0 };
+        let mut loads = Vec::new();
+        let mut damaged = Vec::new();
+
+        while reader.find_sync() {
+            match reader.read_load(loads.len() + damaged.len()) {
+                Ok(load) => loads.push(load),
+                Err(TapeError::Checksum(error)) => damaged.push(error),
+                Err(error) => return Err(error),
+            }
+        }
+
+        match damaged.first() {
+            _ if !loads.is_empty() => Ok(Self { loads, damaged }),
+            Some(error) => Err(error.clone().into()),
+            None => Err(TapeError::NoSignal),
+        }
+    }
+
+    pub fn loads(&self) -> &[Load] {
+        &self.loads
+    }
+
+    pub fn damaged(&self) -> &[ChecksumError] {
+        &self.damaged
+    }
+
+    pub fn into_loads(self) -> Vec<Load> {
+        self.loads
+    }
+}
+
+// Only the first channel is used, as a sample level with the DC offset of
+// unsigned encodings already removed.
+fn read_wav(bytes: &[u8]) -> Result<Vec<f64>, WavError> {
+    if bytes.get(0..4) != Some(b"RIFF") || bytes.get(8..12) != Some(b"WAVE") {
+        return Err(WavError::Malformed);
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = u32::from_le_bytes([
+            bytes[offset + 4],
+            bytes[offset + 5],
+            bytes[offset + 6],
+            bytes[offset + 7],
+        ]) as usize;
+        let start = offset + 8;
+        let end = start.saturating_add(size).min(bytes.len());
+        match id {
+            b"fmt " => format = Some(&bytes[start..end]),
+            b"data" => data = Some(&bytes[start..end]),
+            _ => (),
+        }
+        offset = start.saturating_add(size + size % 2);
+    }
+
+    let (format, data) = match (format, data) {
+        (Some(format), Some(data)) if format.len() >= 16 => (format, data),
+        _ => return Err(WavError::Malformed),
+    };
+    let field =
+        |index: usize| u16::from_le_bytes([format[index], format[index + 1]]);
+    let (encoding, channels, bits) = (field(0), field(2), field(14));
+    if channels == 0 {
+        return Err(WavError::Malformed);
+    }
+    let block = usize::from(channels) * usize::from(bits / 8);
+
+    let samples = match (encoding, bits) {
+        (1, 8) | (0xFFFE, 8) => data
+            .chunks_exact(block)
+            .map(|frame| f64::from(frame[0]) - 128.0)
+            .collect(),
+        (1, 16) | (0xFFFE, 16) => data
+            .chunks_exact(block)
+            .map(|frame| f64::from(i16::from_le_bytes([frame[0], frame[1]])))
+            .collect(),
+        _ => return Err(WavError::Unsupported { format: encoding, bits }),
+    };
+    Ok(samples)
+}
+
+// Each bit is one full cycle of a square-ish wave, a short cycle being a
+// zero and a long one a one. Recordings drift in speed and level, so cycles
+// are timed between rising edges (with some hysteresis against noise) and
+// the boundary between the two lengths is found by clustering them.
+fn demodulate(samples: &[f64]) -> Vec<Option<bool>> {
+    let mean = samples.iter().sum::<f64>() / samples.len().max(1) as f64;
+    let peak =
+        samples.iter().map(|sample| (sample - mean).abs()).fold(0.0, f64::max);
+    let hysteresis = peak * 0.1;
+
+    let mut high = false;
+    let mut last_edge = None;
+    let mut cycles = Vec::new();
+    for (index, sample) in samples.iter().enumerate() {
+        let level = sample - mean;
+        if !high && level > hysteresis {
+            high = true;
+            if let Some(last) = last_edge {
+                cycles.push((index - last) as f64);
+            }
+            last_edge = Some(index);
+        } else if high && level < -hysteresis {
+            high = false;
+        }
+    }
+
+    let mut threshold = cycles.iter().sum::<f64>() / cycles.len().max(1) as f64;
+    for _ in 0..16 {
+        let (mut short, mut long) = ((0.0, 0), (0.0, 0));
+        for &cycle in &cycles {
+            if cycle < threshold {
+                short = (short.0 + cycle, short.1 + 1);
+            } else if cycle < threshold * 3.0 {
+                long = (long.0 + cycle, long.1 + 1);
+            }
+        }
+        if short.1 == 0 || long.1 == 0 {
+            break;
+        }
+        threshold = (short.0 / short.1 as f64 + long.0 / long.1 as f64) / 2.0;
+    }
+
+    // Anything much longer than a one is a gap in the signal, which breaks
+    // any block it falls in.
+    cycles
+        .into_iter()
+        .map(|cycle| {
+            if cycle < threshold {
+                Some(false)
+            } else if cycle < threshold * 3.0 {
+                Some(true)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct BitReader<'bits> {
+    bits: &'bits [Option<bool>],
+    position: usize,
+}
+
+impl<'bits> BitReader<'bits> {
+    // A load starts with a long leader of alternating bits ending in a
+    // single sync byte, which is also what lines the reader up with the
+    // byte boundaries.
+    fn find_sync(&mut self) -> bool {
+        let pattern = u16::from_be_bytes([Tape::LEADER, Tape::SYNC]);
+        let mut shift = 0u16;
+        let mut valid = 0;
+        while let Some(&bit) = self.bits.get(self.position) {
+            self.position += 1;
+            match bit {
+                Some(bit) => {
+                    shift = (shift << 1) | u16::from(bit);
+                    valid += 1;
+                },
+                None => valid = 0,
+            }
+            if valid >= 16 && shift == pattern {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        let mut byte = 0;
+        for _ in 0..8 {
+            let bit = (*self.bits.get(self.position)?)?;
+            self.position += 1;
+            byte = (byte << 1) | u8::from(bit);
+        }
+        Some(byte)
+    }
+
+    fn peek_byte(&mut self) -> Option<u8> {
+        let position = self.position;
+        let byte = self.read_byte();
+        self.position = position;
+        byte
+    }
+
+    fn read_block(
+        &mut self,
+        buffer: &mut [u8],
+        load: usize,
+        block: TapeBlock,
+    ) -> Result<(), TapeError> {
+        for byte in buffer.iter_mut() {
+            *byte = self.read_byte().ok_or(TapeError::Truncated { load })?;
+        }
+        let sum = buffer.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        if sum == Tape::CHECKSUM {
+            Ok(())
+        } else {
+            Err(ChecksumError { load, block, sum }.into())
+        }
+    }
+
+    // The header holds the start address, configuration byte, page count,
+    // its own checksum and the load number. Every page then comes with the
+    // byte telling where it goes and a checksum byte, which make each block
+    // sum to 0x55 with its contents. A bad page does not stop the reader
+    // before the end of the load, so that it stays in step with the tape,
+    // but only the first bad block of the load is reported.
+    fn read_load(&mut self, load: usize) -> Result<Load, TapeError> {
+        let mut header = [0; Load::HEADER_SIZE];
+        self.read_block(
+            &mut header[..Tape::HEADER_SIZE],
+            load,
+            TapeBlock::Header,
+        )?;
+
+        let mut data = [0; Load::DATA_SIZE];
+        let mut damaged = None;
+        let pages =
+            usize::from(header[3]).min(Load::DATA_SIZE / Load::PAGE_SIZE);
+        for page in 0..pages {
+            // Some recordings put another leader between the blocks. Page
+            // location bytes never go past 0x1F, so there is no ambiguity.
+            if self.peek_byte().is_some_and(|byte| byte > 0x1F)
+                && !self.find_sync()
+            {
+                return Err(TapeError::Truncated { load });
+            }
+            let mut block = [0; Load::PAGE_SIZE + 2];
+            match self.read_block(&mut block, load, TapeBlock::Page(page)) {
+                Err(TapeError::Checksum(error)) => {
+                    damaged.get_or_insert(error);
+                },
+                result => result?,
+            }
+            header[16 + page] = block[0];
+            header[64 + page] = block[1];
+            data[page * Load::PAGE_SIZE..(page + 1) * Load::PAGE_SIZE]
+                .copy_from_slice(&block[2..]);
+        }
+        match damaged {
+            Some(error) => Err(error.into()),
+            None => Ok(Load::new(data, header)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Block checksums are chosen to make the sum 0x55; `bad` breaks them.
+    fn block(mut bytes: Vec<u8>, checksum: usize, bad: bool) -> Vec<u8> {
+        let sum = bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        bytes[checksum] = Tape::CHECKSUM.wrapping_sub(sum) ^ u8::from(bad);
+        bytes
+    }
+
+    fn load(number: u8, bad_page: bool) -> Vec<u8> {
+        let mut bytes = vec![Tape::LEADER; 32];
+        bytes.push(Tape::SYNC);
+        let header = vec![0x00, 0xF0, 0x00, 1, 0, number, 0, 0];
+        bytes.extend(block(header, 4, false));
+        let mut page = vec![0; Load::PAGE_SIZE + 2];
+        page[2..].iter_mut().for_each(|byte| *byte = number);
+        bytes.extend(block(page, 1, bad_page));
+        bytes
+    }
+
+    // Zeros are cycles of 4 samples and ones of 8, with a gap of silence
+    // after the signal.
+    fn wav(bytes: &[u8]) -> Vec<u8> {
+        let mut samples = Vec::new();
+        for byte in bytes {
+            for bit in (0..8).rev().map(|shift| byte >> shift & 1 != 0) {
+                let half = if bit { 4 } else { 2 };
+                samples.extend(vec![0xE0; half]);
+                samples.extend(vec![0x20; half]);
+            }
+        }
+        samples.extend(vec![0xE0; 4]);
+        samples.extend(vec![0x80; 64]);
+
+        let mut wav = b"RIFF".to_vec();
+        wav.extend(&(36 + samples.len() as u32).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(&16u32.to_le_bytes());
+        for field in &[1u16, 1] {
+            wav.extend(&field.to_le_bytes());
+        }
+        wav.extend(&44_100u32.to_le_bytes());
+        wav.extend(&44_100u32.to_le_bytes());
+        for field in &[1u16, 8] {
+            wav.extend(&field.to_le_bytes());
+        }
+        wav.extend(b"data");
+        wav.extend(&(samples.len() as u32).to_le_bytes());
+        wav.extend(samples);
+        wav
+    }
+
+    #[test]
+    fn reads_every_load() {
+        let mut bytes = load(1, false);
+        bytes.extend(load(2, false));
+        let tape = Tape::from_wav(&wav(&bytes)).unwrap();
+        let numbers = tape.loads().iter().map(Load::number).collect::<Vec<_>>();
+        assert_eq!(numbers, [1, 2]);
+        assert_eq!(tape.loads()[1].page(0)[0], 2);
+        assert!(tape.damaged().is_empty());
+    }
+
+    #[test]
+    fn damaged_load_does_not_hide_later_ones() {
+        let mut bytes = load(1, true);
+        bytes.extend(load(2, false));
+        let tape = Tape::from_wav(&wav(&bytes)).unwrap();
+        let numbers = tape.loads().iter().map(Load::number).collect::<Vec<_>>();
+        assert_eq!(numbers, [2]);
+        assert_eq!(tape.damaged().len(), 1);
+        assert_eq!(tape.damaged()[0].load, 0);
+        assert_eq!(tape.damaged()[0].block, TapeBlock::Page(0));
+    }
+
+    #[test]
+    fn only_damaged_loads_is_an_error() {
+        let result = Tape::from_wav(&wav(&load(1, true)));
+        assert!(matches!(result, Err(TapeError::Checksum(_))));
+    }
+}
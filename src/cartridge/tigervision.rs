@@ -0,0 +1,201 @@
+use crate::{
+    cartridge::{ExtraRam, Mapper, Scheme},
+    error::{ReadError, SchemeError, WriteError},
+    memory::{Rom, RomBank, SegmentSize},
+};
+
+#[derive(Debug, Clone)]
+pub struct Tigervision {
+    rom: Rom,
+    scheme: Scheme,
+    ram: Option<ExtraRam>,
+    ram_selected: bool,
+    data_bus: u8,
+}
+
+impl Tigervision {
+    pub const MAX_BANKS: usize = 128;
+    pub const HOTSPOT_END: u16 = 0x3F;
+    pub const RAM_HOTSPOT: u16 = 0x3E;
+    pub const ROM_HOTSPOT: u16 = 0x3F;
+    pub const RAM_SIZE: usize = 32 * 1024;
+    pub const RAM_WINDOW: u16 = 0x400;
+    pub const RAM_READ_PORT: u16 = 0x1000;
+    pub const RAM_WRITE_PORT: u16 = 0x1400;
+
+    pub fn t3f(rom: Rom) -> Result<Self, SchemeError> {
+        Self::new(rom, Scheme::Tv3F, None)
+    }
+
+    pub fn t3e(rom: Rom) -> Result<Self, SchemeError> {
+        let ram = ExtraRam::new(
+            Self::RAM_SIZE,
+            Self::RAM_WINDOW,
+            Self::RAM_WRITE_PORT,
+            Self::RAM_READ_PORT,
+        );
+        Self::new(rom, Scheme::Tv3E, Some(ram))
+    }
+
+    // The window is cut in two 2K halves. The lower one is switchable and the
+    // upper one always shows the last 2K of the image.
+    fn new(
+        mut rom: Rom,
+        scheme: Scheme,
+        ram: Option<ExtraRam>,
+    ) -> Result<Self, SchemeError> {
+        if rom.banks() > Self::MAX_BANKS {
+            let size = rom.banks() * RomBank::SIZE;
+            return Err(SchemeError { scheme, size });
+        }
+        rom.set_segment_size(SegmentSize::Half);
+        let last = (rom.chunks() - 1) as u8;
+        rom.select_chunk(0, 0).expect("chunk in range");
+        rom.select_chunk(1, last).expect("chunk in range");
+        Ok(Self { rom, scheme, ram, ram_selected: false, data_bus: 0 })
+    }
+
+    pub fn scheme(&self) -> Scheme {
+        self.scheme
+    }
+
+    pub fn ram(&self) -> Option<&ExtraRam> {
+        self.ram.as_ref()
+    }
+
+    pub fn ram_selected(&self) -> bool {
+        self.ram_selected
+    }
+
+    fn select_rom(&mut self, data: u8) {
+        let chunk = usize::from(data) % self.rom.chunks();
+        let _ = self.rom.select_chunk(0, chunk as u8);
+        self.ram_selected = false;
+    }
+
+    fn select_ram(&mut self, data: u8) {
+        if let Some(ram) = &mut self.ram {
+            let bank = usize::from(data) % ram.banks();
+            let _ = ram.select_bank(bank as u8);
+            self.ram_selected = true;
+        }
+    }
+
+    fn active_ram(&mut self) -> Option<&mut ExtraRam> {
+        if self.ram_selected {
+            self.ram.as_mut()
+        } else {
+            None
+        }
+    }
+}
+
+impl Mapper for Tigervision {
+    fn rom(&self) -> &Rom {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut Rom {
+        &mut self.rom
+    }
+
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        let data_bus = self.data_bus;
+        let data =
+            match self.active_ram().and_then(|ram| ram.read(address, data_bus))
+            {
+                Some(data) => data,
+                None => self.rom.read(address)?,
+            };
+        self.data_bus = data;
+        Ok(data)
+    }
+
+    fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError> {
+        if let Some(ram) = self.active_ram() {
+            ram.write(address, data);
+        }
+        Ok(())
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        let ram = self.ram.as_ref().filter(|_| self.ram_selected);
+        match ram.and_then(|ram| ram.peek(address)) {
+            Some(data) => Ok(data),
+            None => self.rom.read(address),
+        }
+    }
+
+    // The cartridge sits on the whole bus, so it sees the writes meant for
+    // the TIA as well. 3F switches on any of them, 3E only on its two
+    // hotspots.
+    fn snoop(&mut self, address: u16, data: u8) {
+        self.data_bus = data;
+        match self.scheme {
+            Scheme::Tv3E if address == Self::RAM_HOTSPOT => {
+                self.select_ram(data)
+            },
+            Scheme::Tv3E if address == Self::ROM_HOTSPOT => {
+                self.select_rom(data)
+            },
+            Scheme::Tv3F if address <= Self::HOTSPOT_END => {
+                self.select_rom(data)
+            },
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Four 2K chunks, each filled with its own number.
+    fn rom() -> Rom {
+        let bank = |first: u8| {
+            let mut bytes = [first; RomBank::SIZE];
+            bytes[2048..].fill(first + 1);
+            RomBank::new(bytes)
+        };
+        Rom::new(bank(0), Some(bank(2)))
+    }
+
+    fn halves(mapper: &Tigervision) -> [u8; 2] {
+        [mapper.peek(0x1000).unwrap(), mapper.peek(0x1800).unwrap()]
+    }
+
+    #[test]
+    fn t3f_switches_on_tia_writes() {
+        let mut t3f = Tigervision::t3f(rom()).unwrap();
+        assert_eq!(halves(&t3f), [0, 3]);
+        t3f.snoop(0x05, 2);
+        assert_eq!(halves(&t3f), [2, 3]);
+        t3f.snoop(0x40, 1);
+        assert_eq!(halves(&t3f), [2, 3]);
+        t3f.snoop(0x3F, 5);
+        assert_eq!(halves(&t3f), [1, 3]);
+    }
+
+    #[test]
+    fn t3e_switches_rom_and_ram() {
+        let mut t3e = Tigervision::t3e(rom()).unwrap();
+        t3e.snoop(0x05, 2);
+        assert_eq!(halves(&t3e), [0, 3]);
+        t3e.snoop(0x3F, 1);
+        assert_eq!(halves(&t3e), [1, 3]);
+
+        t3e.snoop(0x3E, 2);
+        assert!(t3e.ram_selected());
+        t3e.write(0x1405, 0x77).unwrap();
+        assert_eq!(t3e.peek(0x1005).unwrap(), 0x77);
+        assert_eq!(halves(&t3e), [0, 3]);
+        t3e.snoop(0x3E, 3);
+        assert_eq!(t3e.peek(0x1005).unwrap(), 0);
+        t3e.snoop(0x3E, 2);
+        assert_eq!(t3e.peek(0x1005).unwrap(), 0x77);
+
+        t3e.snoop(0x3F, 2);
+        assert!(!t3e.ram_selected());
+        assert_eq!(halves(&t3e), [2, 3]);
+    }
+}
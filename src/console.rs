@@ -0,0 +1,341 @@
+use crate::{
+    cartridge::{Cartridge, Detection, Mapper, Reason},
+    error::MachineError,
+    instruction::Instruction,
+    machine::Machine,
+    memory::{Memory, Ram},
+    properties::Database,
+    tia::{Frame, TvFormat},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Difficulty {
+    A,
+    B,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Controller {
+    Joystick,
+    Paddles,
+    Driving,
+    Keyboard,
+    BoosterGrip,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub format: TvFormat,
+    pub color: bool,
+    pub difficulties: [Difficulty; 2],
+    pub controllers: [Controller; 2],
+    pub display_start: Option<usize>,
+    pub display_height: Option<usize>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            format: TvFormat::Ntsc,
+            color: true,
+            difficulties: [Difficulty::B; 2],
+            controllers: [Controller::Joystick; 2],
+            display_start: None,
+            display_height: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Console {
+    machine: Machine<Memory>,
+    color_clocks: u64,
+    scanlines: u64,
+    settings: Settings,
+}
+
+impl Console {
+    pub const COLOR_CLOCKS_PER_CYCLE: u64 = 3;
+    pub const COLOR_SWITCH: u8 = 0x08;
+    pub const DIFFICULTY_SWITCHES: [u8; 2] = [0x40, 0x80];
+
+    pub fn new(memory: Memory) -> Result<Self, MachineError> {
+        let machine = Machine::new(memory)?;
+        let mut this = Self {
+            machine,
+            color_clocks: 0,
+            scanlines: 0,
+            settings: Settings::default(),
+        };
+        this.configure(Settings::default());
+        Ok(this)
+    }
+
+    // The properties entry for the image, if the database has one, picks
+    // the cartridge scheme and the console settings.
+    pub fn from_image(
+        image: &[u8],
+        database: Option<&Database>,
+    ) -> Result<(Self, Detection), MachineError> {
+        let properties = database.and_then(|database| database.lookup(image));
+        let scheme = properties.and_then(|properties| properties.scheme());
+        let (cartridge, mut detection) = Cartridge::from_bytes(image, scheme)?;
+        if scheme.is_some() {
+            detection.reason = Reason::Database;
+        }
+
+        let mut console = Self::new(Memory::new(Ram::new(), cartridge))?;
+        if let Some(properties) = properties {
+            console.configure(properties.settings());
+        }
+        Ok((console, detection))
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    // Difficulty and colour switches are wired to port B of the RIOT, where
+    // a set bit means the A position and colour respectively.
+    pub fn configure(&mut self, settings: Settings) {
+        let memory = self.machine.bus_mut();
+        memory.tia_mut().set_format(settings.format);
+
+        let mut switches = memory.riot().port_b_input()
+            & !(Self::COLOR_SWITCH
+                | Self::DIFFICULTY_SWITCHES[0]
+                | Self::DIFFICULTY_SWITCHES[1]);
+        if settings.color {
+            switches |= Self::COLOR_SWITCH;
+        }
+        for (difficulty, bit) in
+            settings.difficulties.iter().zip(Self::DIFFICULTY_SWITCHES.iter())
+        {
+            if *difficulty == Difficulty::A {
+                switches |= bit;
+            }
+        }
+        memory.riot_mut().set_port_b(switches);
+        self.settings = settings;
+    }
+
+    pub fn machine(&self) -> &Machine<Memory> {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine<Memory> {
+        &mut self.machine
+    }
+
+    pub fn memory(&self) -> &Memory {
+        self.machine.bus()
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        self.machine.bus_mut()
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.machine.cycles()
+    }
+
+    pub fn color_clocks(&self) -> u64 {
+        self.color_clocks
+    }
+
+    pub fn scanlines(&self) -> u64 {
+        self.scanlines
+    }
+
+    pub fn reset(&mut self) -> Result<(), MachineError> {
+        self.machine.reset()
+    }
+
+    // Devices are brought up to the last cycle of the instruction before it
+    // runs, since that is when loads and stores touch the bus; a write to
+    // WSYNC then holds the CPU until the TIA starts the next line.
+    pub fn step(&mut self) -> Result<Instruction, MachineError> {
+        let instruction = self.machine.fetch()?;
+        let cycles = self.machine.timing(instruction)?;
+        self.advance(u64::from(cycles) - 1);
+        self.machine.execute(instruction)?;
+        self.advance(1);
+
+        while self.memory().tia().wsync() {
+            self.advance(1);
+            self.machine.stall(1);
+        }
+
+        // ARM cartridges keep the 6507 busy while their own code runs.
+        let stall = self.memory_mut().cartridge_mut().stall();
+        self.advance(stall);
+        self.machine.stall(stall);
+
+        Ok(instruction)
+    }
+
+    pub fn run_cycles(&mut self, cycles: u64) -> Result<u64, MachineError> {
+        let start = self.machine.cycles();
+        while self.machine.cycles() - start < cycles {
+            self.step()?;
+        }
+        Ok(self.machine.cycles() - start)
+    }
+
+    pub fn run_scanline(&mut self) -> Result<u64, MachineError> {
+        let start = self.machine.cycles();
+        let scanline = self.scanlines;
+        while self.scanlines == scanline {
+            self.step()?;
+        }
+        Ok(self.machine.cycles() - start)
+    }
+
+    pub fn run_frame(&mut self) -> Result<Frame, MachineError> {
+        loop {
+            self.step()?;
+            if let Some(frame) = self.memory_mut().tia_mut().take_frame() {
+                break Ok(self.crop(frame));
+            }
+        }
+    }
+
+    fn crop(&self, frame: Frame) -> Frame {
+        match (self.settings.display_start, self.settings.display_height) {
+            (None, None) => frame,
+            (start, height) => {
+                let start = start.unwrap_or(0);
+                let height = height.unwrap_or_else(|| frame.height());
+                frame.crop(start, height)
+            },
+        }
+    }
+
+    fn advance(&mut self, cycles: u64) {
+        let memory = self.machine.bus_mut();
+        for _ in 0..cycles {
+            memory.riot_mut().clock();
+            memory.cartridge_mut().clock();
+            for _ in 0..Self::COLOR_CLOCKS_PER_CYCLE {
+                if memory.tia_mut().clock() {
+                    self.scanlines += 1;
+                }
+            }
+        }
+        self.color_clocks += cycles * Self::COLOR_CLOCKS_PER_CYCLE;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cartridge::Scheme, md5::Digest};
+
+    const LINE: u64 = 228;
+
+    // A 4K image running the program from $F000.
+    fn console(program: &[u8]) -> Console {
+        let mut image = vec![0xEA; 0x1000];
+        image[..program.len()].copy_from_slice(program);
+        image[0xFFC..].copy_from_slice(&[0x00, 0xF0, 0x00, 0xF0]);
+        Console::from_image(&image, None).unwrap().0
+    }
+
+    #[test]
+    fn run_cycles_finishes_the_last_instruction() {
+        let mut console = console(&[0xEA, 0xEA, 0xEA]);
+        assert_eq!(console.run_cycles(4).unwrap(), 4);
+        assert_eq!(console.run_cycles(1).unwrap(), 2);
+        assert_eq!(console.color_clocks(), 18);
+    }
+
+    #[test]
+    fn wsync_stalls_to_the_next_line() {
+        // nop; sta WSYNC
+        let mut console = console(&[0xEA, 0x85, 0x02, 0xEA]);
+        console.step().unwrap();
+        console.step().unwrap();
+        assert_eq!(console.color_clocks(), LINE);
+        assert_eq!(console.scanlines(), 1);
+        assert_eq!(console.memory().tia().video().hpos(), 0);
+        assert_eq!(
+            console.cycles() - u64::from(Machine::<Memory>::RESET_CYCLES),
+            76
+        );
+    }
+
+    #[test]
+    fn run_scanline_advances_one_line() {
+        // loop: sta WSYNC; jmp loop
+        let mut console = console(&[0x85, 0x02, 0x4C, 0x00, 0xF0]);
+        console.run_scanline().unwrap();
+        for line in 2..5 {
+            assert_eq!(console.run_scanline().unwrap(), 76);
+            assert_eq!(console.scanlines(), line);
+            assert_eq!(console.color_clocks(), line * LINE);
+        }
+    }
+
+    #[test]
+    fn run_frame_returns_on_vsync() {
+        let program = [
+            0xA9, 0x02, // loop: lda #2
+            0x85, 0x00, // sta VSYNC
+            0x85, 0x02, // sta WSYNC
+            0x85, 0x02, // sta WSYNC
+            0x85, 0x02, // sta WSYNC
+            0xA9, 0x00, // lda #0
+            0x85, 0x00, // sta VSYNC
+            0xA2, 0xC8, // ldx #200
+            0x85, 0x02, // line: sta WSYNC
+            0xCA, // dex
+            0xD0, 0xFB, // bne line
+            0x4C, 0x00, 0xF0, // jmp loop
+        ];
+        let mut console = console(&program);
+        // The first VSYNC closes the empty frame in progress at reset.
+        assert_eq!(console.run_frame().unwrap().height(), 0);
+        for _ in 0..2 {
+            let scanlines = console.scanlines();
+            let frame = console.run_frame().unwrap();
+            assert_eq!(frame.height(), 203);
+            assert_eq!(frame.pixels().len(), 203 * Frame::WIDTH);
+            assert_eq!(console.scanlines() - scanlines, 203);
+        }
+    }
+
+    #[test]
+    fn from_image_applies_the_database_entry() {
+        let mut image = vec![0; 0x2000];
+        image[0x1FFC..].copy_from_slice(&[0x00, 0xF0, 0x00, 0xF0]);
+        let text = format!(
+            "\"Cart.MD5\" \"{}\"\n\"Cart.Type\" \"E0\"\n\
+             \"Display.Format\" \"PAL\"\n\"Console.TelevisionType\" \"BW\"\n\
+             \"Console.LeftDifficulty\" \"A\"\n\"Display.YStart\" \"30\"\n\"\"\n",
+            Digest::of(&image)
+        );
+        let database = Database::parse(&text).unwrap();
+
+        let (console, detection) =
+            Console::from_image(&image, Some(&database)).unwrap();
+        assert_eq!(detection.scheme, Scheme::E0);
+        assert_eq!(detection.reason, Reason::Database);
+        assert_eq!(console.settings().format, TvFormat::Pal);
+        assert_eq!(console.memory().tia().format(), TvFormat::Pal);
+        assert_eq!(console.settings().display_start, Some(30));
+        let switches = console.memory().riot().port_b_input();
+        assert_eq!(switches & Console::COLOR_SWITCH, 0);
+        assert_eq!(switches & Console::DIFFICULTY_SWITCHES[0], 0x40);
+        assert_eq!(switches & Console::DIFFICULTY_SWITCHES[1], 0);
+
+        // Other images keep the detected scheme and the default settings.
+        image[0] = 1;
+        let (console, detection) =
+            Console::from_image(&image, Some(&database)).unwrap();
+        assert_eq!(detection.reason, Reason::Size);
+        assert_eq!(console.settings(), &Settings::default());
+        let switches = console.memory().riot().port_b_input();
+        assert_eq!(switches & Console::COLOR_SWITCH, Console::COLOR_SWITCH);
+    }
+}
@@ -0,0 +1,704 @@
+use crate::{
+    addrmode::{Absolute, AbsoluteX, Immediate, Operand, Zeropage, ZeropageX},
+    binary::Decoder,
+    cartridge::{Cartridge, Mapper},
+    error::MachineError,
+    instruction::{Instruction, Mnemonic, Type},
+    memory::{Memory, RomBank},
+    syntax::{Format, Syntax},
+};
+use std::{collections::BTreeMap, fmt};
+
+// Addresses are given in the mirror the bank's own reset vector points
+// into, usually $F000-$FFFF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    pub bank: u8,
+    pub address: u16,
+}
+
+impl Location {
+    fn offset(self) -> usize {
+        usize::from(self.address) % RomBank::SIZE
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Mark {
+    Data,
+    Code,
+}
+
+// Ordered by precedence, for locations reached in more than one way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Name {
+    Data,
+    Jump,
+    Subroutine,
+    Entry(&'static str),
+}
+
+#[derive(Debug)]
+struct BankDecoder<'bank> {
+    bank: &'bank RomBank,
+    address: u16,
+}
+
+impl<'bank> Decoder for BankDecoder<'bank> {
+    type Error = MachineError;
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<(), Self::Error> {
+        for byte in buf {
+            *byte = self.bank.read(self.address)?;
+            self.address += 1;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Disassembly {
+    banks: Vec<RomBank>,
+    origins: Vec<u16>,
+    marks: Vec<Vec<Mark>>,
+    instructions: BTreeMap<Location, Instruction>,
+    labels: BTreeMap<Location, String>,
+    notes: BTreeMap<Location, String>,
+}
+
+impl Disassembly {
+    pub const RESET_VECTOR: u16 = 0x1FFC;
+    pub const BREAK_VECTOR: u16 = 0x1FFE;
+
+    const DATA_PER_LINE: usize = 8;
+
+    // Tracing starts from the vectors of the bank the cartridge powers up
+    // in. Switches are found by replaying each absolute access to
+    // cartridge space on a copy of the cartridge, so any scheme that
+    // switches whole banks on hotspots is followed; switches that depend on
+    // the data written are not.
+    pub fn new(cartridge: &Cartridge) -> Self {
+        let mut rom = cartridge.rom().clone();
+        let start = rom.selected_index();
+        let banks = (0..rom.banks())
+            .filter_map(|bank| {
+                rom.select_bank(bank as u8).ok()?;
+                Some(rom.selected_bank().clone())
+            })
+            .collect::<Vec<_>>();
+        let origins = banks
+            .iter()
+            .map(|bank| match read_word(bank, Self::RESET_VECTOR) {
+                Some(vector) if vector & RomBank::OFFSET != 0 => {
+                    vector & 0xF000
+                },
+                _ => 0xF000,
+            })
+            .collect();
+
+        let mut this = Self {
+            marks: vec![vec![Mark::Data; RomBank::SIZE]; banks.len()],
+            banks,
+            origins,
+            instructions: BTreeMap::new(),
+            labels: BTreeMap::new(),
+            notes: BTreeMap::new(),
+        };
+        let mut names = BTreeMap::new();
+        let mut queue = Vec::new();
+        let entries =
+            [(Self::RESET_VECTOR, "Start"), (Self::BREAK_VECTOR, "Break")];
+        for &(vector, name) in &entries {
+            if let Some(entry) = this.vector(start, vector) {
+                name_location(&mut names, entry, Name::Entry(name));
+                queue.push(entry);
+            }
+        }
+        while let Some(location) = queue.pop() {
+            this.trace(cartridge, location, &mut queue, &mut names);
+        }
+        this.name_data(&mut names);
+        this.labels = names
+            .into_iter()
+            .map(|(location, name)| (location, this.label_name(location, name)))
+            .collect();
+        this
+    }
+
+    pub fn banks(&self) -> usize {
+        self.banks.len()
+    }
+
+    pub fn origin(&self, bank: u8) -> u16 {
+        self.origins[usize::from(bank)]
+    }
+
+    pub fn mark(&self, location: Location) -> Mark {
+        self.marks[usize::from(location.bank)][location.offset()]
+    }
+
+    pub fn instruction(&self, location: Location) -> Option<Instruction> {
+        self.instructions.get(&location).copied()
+    }
+
+    pub fn instructions(&self) -> &BTreeMap<Location, Instruction> {
+        &self.instructions
+    }
+
+    pub fn label(&self, location: Location) -> Option<&str> {
+        self.labels.get(&location).map(String::as_str)
+    }
+
+    pub fn labels(&self) -> &BTreeMap<Location, String> {
+        &self.labels
+    }
+
+    pub fn note(&self, location: Location) -> Option<&str> {
+        self.notes.get(&location).map(String::as_str)
+    }
+
+    fn location(&self, bank: u8, address: u16) -> Option<Location> {
+        if address & RomBank::OFFSET == 0 {
+            return None;
+        }
+        let address = self.origin(bank) | (address & 0x0FFF);
+        Some(Location { bank, address })
+    }
+
+    // Pointers may be given in any mirror of the bank.
+    fn vector(&self, bank: u8, address: u16) -> Option<Location> {
+        let address = RomBank::OFFSET | (address & 0x0FFF);
+        let target = read_word(&self.banks[usize::from(bank)], address)?;
+        self.location(bank, target)
+    }
+
+    // Instructions always target the bank they are in; only the fall
+    // through may continue in another bank.
+    fn target(
+        &self,
+        location: Location,
+        instruction: Instruction,
+    ) -> Option<Location> {
+        let address = match instruction.operand {
+            Operand::Abs(data) => data.address,
+            Operand::AbsX(data) => data.address,
+            Operand::AbsY(data) => data.address,
+            Operand::Ind(data) => data.address,
+            Operand::Rel(data) => location
+                .address
+                .wrapping_add(2)
+                .wrapping_add(data.address as u16),
+            _ => return None,
+        };
+        self.location(location.bank, address)
+    }
+
+    fn trace(
+        &mut self,
+        cartridge: &Cartridge,
+        location: Location,
+        queue: &mut Vec<Location>,
+        names: &mut BTreeMap<Location, Name>,
+    ) {
+        let bank = usize::from(location.bank);
+        let offset = location.offset();
+        if self.instructions.contains_key(&location) {
+            return;
+        }
+        let mut decoder = BankDecoder {
+            bank: &self.banks[bank],
+            address: RomBank::OFFSET + offset as u16,
+        };
+        let decoded = decoder
+            .decode::<Instruction>()
+            .ok()
+            .or_else(|| undocumented_nop(&self.banks[bank], offset));
+        let instruction = match decoded {
+            Some(instruction) => instruction,
+            None => {
+                self.notes
+                    .insert(location, "not an opcode, trace stops".into());
+                return;
+            },
+        };
+        let end = offset + length(instruction);
+        for mark in &mut self.marks[bank][offset..end] {
+            *mark = Mark::Code;
+        }
+        self.instructions.insert(location, instruction);
+
+        let target = self.target(location, instruction);
+        let mut next = Location {
+            bank: location.bank,
+            address: location.address.wrapping_add((end - offset) as u16),
+        };
+        match (instruction.mnemonic, target) {
+            (Mnemonic::Jmp, Some(target)) => {
+                let target = match instruction.operand {
+                    Operand::Ind(_) => {
+                        match self.vector(location.bank, target.address) {
+                            Some(target) => target,
+                            None => return,
+                        }
+                    },
+                    _ => target,
+                };
+                name_location(names, target, Name::Jump);
+                queue.push(target);
+                return;
+            },
+            (Mnemonic::Jmp, None)
+            | (Mnemonic::Rts, _)
+            | (Mnemonic::Rti, _)
+            | (Mnemonic::Brk, _) => return,
+            (Mnemonic::Jsr, Some(target)) => {
+                name_location(names, target, Name::Subroutine);
+                queue.push(target);
+            },
+            (mnemonic, Some(target)) if mnemonic.instr_type() == Type::Bch => {
+                name_location(names, target, Name::Jump);
+                queue.push(target);
+            },
+            (_, Some(_)) => {
+                if let Some(switched) =
+                    switch(cartridge, location.bank, instruction)
+                {
+                    self.notes.insert(
+                        location,
+                        format!("switches to bank {}", switched),
+                    );
+                    next = Location {
+                        bank: switched,
+                        address: self.origin(switched) | (end as u16 & 0x0FFF),
+                    };
+                }
+            },
+            _ => (),
+        }
+        if end < RomBank::SIZE {
+            queue.push(next);
+        }
+    }
+
+    // Data read by absolute addressing within the same bank gets a label
+    // too, unless the access is a bank switch. The pointer read by an
+    // indirect jump counts as data.
+    fn name_data(&self, names: &mut BTreeMap<Location, Name>) {
+        for (&location, &instruction) in &self.instructions {
+            let target = match self.target(location, instruction) {
+                Some(target) => target,
+                None => continue,
+            };
+            let code = match instruction.operand {
+                Operand::Rel(_) => true,
+                Operand::Abs(_) => {
+                    matches!(
+                        instruction.mnemonic,
+                        Mnemonic::Jmp | Mnemonic::Jsr
+                    )
+                },
+                _ => false,
+            };
+            if code
+                || self.notes.contains_key(&location)
+                || self.mark(target) != Mark::Data
+            {
+                continue;
+            }
+            name_location(names, target, Name::Data);
+        }
+    }
+
+    fn label_name(&self, location: Location, name: Name) -> String {
+        let prefix = match name {
+            Name::Entry(name) => return name.to_string(),
+            Name::Subroutine => "S",
+            Name::Jump => "L",
+            Name::Data => "D",
+        };
+        if self.banks.len() > 1 {
+            format!("{}{}_{:04X}", prefix, location.bank, location.address)
+        } else {
+            format!("{}{:04X}", prefix, location.address)
+        }
+    }
+
+    fn format_instruction(
+        &self,
+        location: Location,
+        instruction: Instruction,
+        syntax: Syntax,
+        fmtr: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        let bytes = self.bytes(location, length(instruction));
+        let target = self.target(location, instruction);
+        let label = target.and_then(|target| self.label(target));
+        let x = syntax.letter('X');
+        let y = syntax.letter('Y');
+        let operand = match (instruction.operand, label) {
+            (Operand::Impl(_), _) => String::new(),
+            (Operand::Abs(_), Some(label)) | (Operand::Rel(_), Some(label)) => {
+                label.to_string()
+            },
+            (Operand::AbsX(_), Some(label)) => format!("{},{}", label, x),
+            (Operand::AbsY(_), Some(label)) => format!("{},{}", label, y),
+            (Operand::Ind(_), Some(label)) => format!("({})", label),
+            (Operand::Rel(_), None) => match target {
+                Some(target) => syntax.word(target.address),
+                None => instruction.operand.with_syntax(syntax).to_string(),
+            },
+            (operand, _) => operand.with_syntax(syntax).to_string(),
+        };
+
+        let text =
+            format!("{} {}", instruction.mnemonic.with_syntax(syntax), operand);
+        self.format_line(location, &bytes, text.trim_end(), fmtr)
+    }
+
+    fn format_line(
+        &self,
+        location: Location,
+        bytes: &[u8],
+        text: &str,
+        fmtr: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        let hex = bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+        write!(fmtr, "{:04X}  {:<8}  {}", location.address, hex, text)?;
+        if let Some(note) = self.note(location) {
+            write!(fmtr, "  ; {}", note)?;
+        }
+        writeln!(fmtr)
+    }
+
+    fn bytes(&self, location: Location, length: usize) -> Vec<u8> {
+        let start = RomBank::OFFSET + location.offset() as u16;
+        (start..start + length as u16)
+            .filter_map(|address| {
+                self.banks[usize::from(location.bank)].read(address).ok()
+            })
+            .collect()
+    }
+
+    // Data runs stop at labels and instructions so that both stay visible.
+    fn data_run(&self, location: Location) -> usize {
+        let offset = location.offset();
+        let mut length = 1;
+        while length < Self::DATA_PER_LINE && offset + length < RomBank::SIZE {
+            let next = Location {
+                bank: location.bank,
+                address: location.address + length as u16,
+            };
+            if self.labels.contains_key(&next)
+                || self.instructions.contains_key(&next)
+                || self.notes.contains_key(&next)
+            {
+                break;
+            }
+            length += 1;
+        }
+        length
+    }
+}
+
+impl Format for Disassembly {
+    fn format(&self, syntax: Syntax, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        for bank in 0..self.banks.len() {
+            if bank > 0 {
+                writeln!(fmtr)?;
+            }
+            writeln!(fmtr, "; Bank {}", bank)?;
+            let bank = bank as u8;
+            let origin = self.origin(bank);
+            let mut offset = 0;
+            while offset < RomBank::SIZE {
+                let location =
+                    Location { bank, address: origin | offset as u16 };
+                if let Some(label) = self.label(location) {
+                    writeln!(fmtr, "{}:", label)?;
+                }
+                match self.instruction(location) {
+                    Some(instruction) => {
+                        self.format_instruction(
+                            location,
+                            instruction,
+                            syntax,
+                            fmtr,
+                        )?;
+                        offset += length(instruction);
+                    },
+                    None => {
+                        let length = self.data_run(location);
+                        let bytes = self.bytes(location, length);
+                        let values = bytes
+                            .iter()
+                            .map(|&byte| syntax.byte(byte))
+                            .collect::<Vec<_>>()
+                            .join(",");
+                        let text =
+                            format!("{} {}", syntax.text(".byte"), values);
+                        self.format_line(location, &[], &text, fmtr)?;
+                        offset += length;
+                    },
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        self.format(Syntax::default(), fmtr)
+    }
+}
+
+fn read_word(bank: &RomBank, address: u16) -> Option<u16> {
+    let low = bank.read(address).ok()?;
+    let high = bank.read(address.wrapping_add(1)).ok()?;
+    Some(u16::from_le_bytes([low, high]))
+}
+
+fn name_location(
+    names: &mut BTreeMap<Location, Name>,
+    location: Location,
+    name: Name,
+) {
+    let entry = names.entry(location).or_insert(name);
+    *entry = (*entry).max(name);
+}
+
+// The instruction set has no room for these, but DASM assembles NOP with an
+// operand to them and macro.h's SLEEP relies on it.
+fn undocumented_nop(bank: &RomBank, offset: usize) -> Option<Instruction> {
+    let address = RomBank::OFFSET + offset as u16;
+    let byte = |index| bank.read(address + index).ok();
+    let word = || Some(u16::from_le_bytes([byte(1)?, byte(2)?]));
+    let operand = match byte(0)? {
+        0x80 => Operand::Imm(Immediate { bits: byte(1)? }),
+        0x04 => Operand::Zpg(Zeropage { address: byte(1)? }),
+        0x14 => Operand::ZpgX(ZeropageX { address: byte(1)? }),
+        0x0C => Operand::Abs(Absolute { address: word()? }),
+        0x1C => Operand::AbsX(AbsoluteX { address: word()? }),
+        _ => return None,
+    };
+    Some(Instruction { mnemonic: Mnemonic::Nop, operand })
+}
+
+fn length(instruction: Instruction) -> usize {
+    match instruction.operand {
+        Operand::Acc(_) | Operand::Impl(_) => 1,
+        Operand::Abs(_)
+        | Operand::AbsX(_)
+        | Operand::AbsY(_)
+        | Operand::Ind(_) => 3,
+        _ => 2,
+    }
+}
+
+fn switch(
+    cartridge: &Cartridge,
+    bank: u8,
+    instruction: Instruction,
+) -> Option<u8> {
+    let address = match instruction.operand {
+        Operand::Abs(data) => data.address,
+        Operand::AbsX(data) => data.address,
+        Operand::AbsY(data) => data.address,
+        _ => return None,
+    };
+    if address & RomBank::OFFSET == 0 {
+        return None;
+    }
+    let mut cartridge = cartridge.clone();
+    cartridge.rom_mut().select_bank(bank).ok()?;
+    let address = address & Memory::ADDRESS_MASK;
+    match instruction.mnemonic {
+        Mnemonic::Sta | Mnemonic::Stx | Mnemonic::Sty => {
+            let _ = cartridge.write(address, 0);
+        },
+        _ => {
+            let _ = cartridge.read(address);
+        },
+    }
+    let selected = cartridge.rom().selected_index();
+    Some(selected).filter(|&selected| selected != bank)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::{self, dasm},
+        cartridge::Scheme,
+    };
+
+    fn disassemble(source: &str, scheme: Scheme) -> Disassembly {
+        let assembly = dasm::assemble(source, |_| None).unwrap();
+        let (cartridge, _) =
+            Cartridge::from_bytes(&assembly.bytes(), Some(scheme)).unwrap();
+        Disassembly::new(&cartridge)
+    }
+
+    #[test]
+    fn undocumented_nop_from_sleep() {
+        let source = "
+    processor 6502
+    include \"macro.h\"
+    org $F000
+Start
+    SLEEP 5
+    lda #1
+    jmp Start
+    org $FFFC
+    .word Start
+    .word Start
+";
+        let disassembly = disassemble(source, Scheme::Rom4K);
+        let at = |address| Location { bank: 0, address };
+        let nop = disassembly.instruction(at(0xF000)).unwrap();
+        assert_eq!(nop.to_string(), "NOP $00");
+        assert_eq!(disassembly.mark(at(0xF003)), Mark::Code);
+        assert_eq!(disassembly.mark(at(0xF005)), Mark::Code);
+        assert_eq!(disassembly.label(at(0xF000)), Some("Start"));
+    }
+
+    #[test]
+    fn unknown_opcode_is_noted() {
+        let source = "
+    processor 6502
+    org $F000
+Start
+    nop
+    .byte $02
+    org $FFFC
+    .word Start
+    .word Start
+";
+        let disassembly = disassemble(source, Scheme::Rom4K);
+        let at = Location { bank: 0, address: 0xF001 };
+        assert_eq!(disassembly.mark(at), Mark::Data);
+        assert!(disassembly.note(at).is_some());
+        assert!(disassembly.to_string().contains("F001            .BYTE $02"));
+    }
+
+    // Bank 1 is where F8 starts; it switches to bank 0 in the middle of a
+    // run of code and bank 0 carries on from the next address.
+    const F8_SOURCE: &str = "
+    processor 6502
+    seg code
+    org $0000
+    rorg $F000
+    .byte $02,$02
+    nop
+    nop
+    nop
+    jsr Sub
+Loop
+    jmp Loop
+Sub
+    lda Table,y
+    rts
+Table
+    .byte 1,2,3
+    org $0FFC
+    rorg $FFFC
+    .word Loop
+    .word Loop
+
+    org $1000
+    rorg $F000
+Start
+    ldx #0
+    lda $1FF8
+    .byte $02
+    org $1FFC
+    rorg $FFFC
+    .word Start
+    .word Start
+";
+
+    #[test]
+    fn follows_bank_switches() {
+        let disassembly = disassemble(F8_SOURCE, Scheme::F8);
+        let at = |bank, address| Location { bank, address };
+        assert_eq!(disassembly.banks(), 2);
+        assert_eq!(disassembly.label(at(1, 0xF000)), Some("Start"));
+        assert_eq!(disassembly.note(at(1, 0xF002)), Some("switches to bank 0"));
+        assert_eq!(disassembly.mark(at(1, 0xF005)), Mark::Data);
+        assert_eq!(disassembly.mark(at(0, 0xF004)), Mark::Data);
+        assert_eq!(disassembly.label(at(0, 0xF008)), Some("L0_F008"));
+        assert_eq!(disassembly.label(at(0, 0xF00B)), Some("S0_F00B"));
+        assert_eq!(disassembly.label(at(0, 0xF00F)), Some("D0_F00F"));
+        assert_eq!(disassembly.mark(at(0, 0xF00E)), Mark::Code);
+        assert_eq!(disassembly.mark(at(0, 0xF00F)), Mark::Data);
+
+        let listing = disassembly
+            .instructions()
+            .iter()
+            .map(|(location, instruction)| {
+                (location.bank, location.address, instruction.to_string())
+            })
+            .collect::<Vec<_>>();
+        let expected = [
+            (0, 0xF005, "JSR $F00B"),
+            (0, 0xF008, "JMP $F008"),
+            (0, 0xF00B, "LDA $F00F,Y"),
+            (0, 0xF00E, "RTS"),
+            (1, 0xF000, "LDX #$00"),
+            (1, 0xF002, "LDA $1FF8"),
+        ];
+        let expected = expected
+            .iter()
+            .map(|&(bank, address, text)| (bank, address, text.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(listing, expected);
+    }
+
+    #[test]
+    fn listing_reassembles_to_the_same_instructions() {
+        let assembly = dasm::assemble(F8_SOURCE, |_| None).unwrap();
+        let disassembly = disassemble(F8_SOURCE, Scheme::F8);
+        for (location, instruction) in disassembly.instructions() {
+            let text = instruction.to_string();
+            let reassembled = asm::instruction(&text).unwrap();
+            assert_eq!(reassembled, *instruction);
+            assert!(assembly
+                .instructions()
+                .contains(&(location.address, *instruction)));
+        }
+        let listing = disassembly.to_string();
+        assert!(listing.contains("S0_F00B:"));
+        assert!(listing.contains("F00B  B9 0F F0  LDA D0_F00F,Y"));
+    }
+
+    #[test]
+    fn follows_indirect_jump_through_rom_pointer() {
+        let source = "
+    processor 6502
+    org $F000
+Start
+    jmp (Vector)
+    .byte $02
+Target
+    lda #1
+    rts
+Vector
+    .word Target
+    org $FFFC
+    .word Start
+    .word Start
+";
+        let disassembly = disassemble(source, Scheme::Rom4K);
+        let at = |address| Location { bank: 0, address };
+        let target = disassembly.instruction(at(0xF004)).unwrap();
+        assert_eq!(target.to_string(), "LDA #$01");
+        assert_eq!(disassembly.mark(at(0xF006)), Mark::Code);
+        assert_eq!(disassembly.mark(at(0xF003)), Mark::Data);
+        assert_eq!(disassembly.label(at(0xF004)), Some("LF004"));
+        assert_eq!(disassembly.label(at(0xF007)), Some("DF007"));
+    }
+}
@@ -1,6 +1,10 @@
 use std::{error::Error, fmt, io};
 
-use crate::{addrmode::AddrMode, instruction::Type};
+use crate::{
+    addrmode::AddrMode,
+    cartridge::Scheme,
+    instruction::{Mnemonic, Type},
+};
 
 #[derive(Debug, Clone)]
 pub struct BankError {
@@ -15,6 +19,37 @@ impl fmt::Display for BankError {
 
 impl Error for BankError {}
 
+#[derive(Debug, Clone)]
+pub struct SchemeError {
+    pub scheme: Scheme,
+    pub size: usize,
+}
+
+impl fmt::Display for SchemeError {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "cartridge scheme {} cannot hold 0x{:x} bytes",
+            self.scheme, self.size
+        )
+    }
+}
+
+impl Error for SchemeError {}
+
+#[derive(Debug, Clone)]
+pub struct DetectError {
+    pub size: usize,
+}
+
+impl fmt::Display for DetectError {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "no known cartridge scheme holds 0x{:x} bytes", self.size)
+    }
+}
+
+impl Error for DetectError {}
+
 #[derive(Debug, Clone)]
 pub struct ReadError {
     pub address: u16,
@@ -73,11 +108,262 @@ impl fmt::Display for AddrModeError {
 
 impl Error for AddrModeError {}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavError {
+    Malformed,
+    Unsupported { format: u16, bits: u16 },
+}
+
+impl fmt::Display for WavError {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WavError::Malformed => write!(fmtr, "malformed WAV data"),
+            WavError::Unsupported { format, bits } => write!(
+                fmtr,
+                "unsupported WAV encoding 0x{:x} with {} bits per sample",
+                format, bits
+            ),
+        }
+    }
+}
+
+impl Error for WavError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapeBlock {
+    Header,
+    Page(usize),
+}
+
+impl fmt::Display for TapeBlock {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TapeBlock::Header => write!(fmtr, "header"),
+            TapeBlock::Page(page) => write!(fmtr, "page {}", page),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChecksumError {
+    pub load: usize,
+    pub block: TapeBlock,
+    pub sum: u8,
+}
+
+impl fmt::Display for ChecksumError {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "bad checksum 0x{:x} in {} of tape load {}",
+            self.sum, self.block, self.load
+        )
+    }
+}
+
+impl Error for ChecksumError {}
+
+#[derive(Debug, Clone)]
+pub enum TapeError {
+    Wav(WavError),
+    Checksum(ChecksumError),
+    Truncated { load: usize },
+    NoSignal,
+}
+
+impl fmt::Display for TapeError {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TapeError::Wav(error) => write!(fmtr, "{}", error),
+            TapeError::Checksum(error) => write!(fmtr, "{}", error),
+            TapeError::Truncated { load } => {
+                write!(fmtr, "tape ends in the middle of load {}", load)
+            },
+            TapeError::NoSignal => write!(fmtr, "no load found on tape"),
+        }
+    }
+}
+
+impl Error for TapeError {}
+
+impl From<WavError> for TapeError {
+    fn from(error: WavError) -> Self {
+        TapeError::Wav(error)
+    }
+}
+
+impl From<ChecksumError> for TapeError {
+    fn from(error: ChecksumError) -> Self {
+        TapeError::Checksum(error)
+    }
+}
+
+impl From<TapeError> for io::Error {
+    fn from(error: TapeError) -> Self {
+        let kind = match error {
+            TapeError::Truncated { .. } => io::ErrorKind::UnexpectedEof,
+            _ => io::ErrorKind::InvalidData,
+        };
+
+        io::Error::new(kind, error)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PropertiesError {
+    pub line: usize,
+}
+
+impl fmt::Display for PropertiesError {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "malformed properties entry at line {}", self.line)
+    }
+}
+
+impl Error for PropertiesError {}
+
+impl From<PropertiesError> for io::Error {
+    fn from(error: PropertiesError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArmError {
+    Read { address: u32 },
+    Write { address: u32 },
+    Undefined { address: u32, instruction: u16 },
+    Timeout { cycles: u64 },
+}
+
+impl fmt::Display for ArmError {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArmError::Read { address } => {
+                write!(fmtr, "invalid ARM read at address 0x{:x}", address)
+            },
+            ArmError::Write { address } => {
+                write!(fmtr, "invalid ARM write at address 0x{:x}", address)
+            },
+            ArmError::Undefined { address, instruction } => write!(
+                fmtr,
+                "undefined thumb instruction 0x{:04x} at address 0x{:x}",
+                instruction, address
+            ),
+            ArmError::Timeout { cycles } => {
+                write!(fmtr, "ARM code still running after {} cycles", cycles)
+            },
+        }
+    }
+}
+
+impl Error for ArmError {}
+
+impl From<ArmError> for io::Error {
+    fn from(error: ArmError) -> Self {
+        let kind = match error {
+            ArmError::Read { .. } | ArmError::Write { .. } => {
+                io::ErrorKind::AddrNotAvailable
+            },
+            ArmError::Undefined { .. } => io::ErrorKind::InvalidData,
+            ArmError::Timeout { .. } => io::ErrorKind::TimedOut,
+        };
+
+        io::Error::new(kind, error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    Syntax,
+    UnknownMnemonic(String),
+    UnknownDirective(String),
+    Undefined(String),
+    Redefined(String),
+    AddrMode { mnemonic: Mnemonic, mode: Option<AddrMode> },
+    Range { value: i64 },
+    BranchRange { offset: i64 },
+    DivisionByZero,
+    Include(String),
+    Unbalanced(String),
+    Origin { origin: i64 },
+    Nesting,
+    Stopped,
+}
+
+impl fmt::Display for AsmErrorKind {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmErrorKind::Syntax => write!(fmtr, "syntax error"),
+            AsmErrorKind::UnknownMnemonic(name) => {
+                write!(fmtr, "unknown mnemonic {}", name)
+            },
+            AsmErrorKind::UnknownDirective(name) => {
+                write!(fmtr, "unknown directive {}", name)
+            },
+            AsmErrorKind::Undefined(name) => {
+                write!(fmtr, "undefined symbol {}", name)
+            },
+            AsmErrorKind::Redefined(name) => {
+                write!(fmtr, "symbol {} defined twice", name)
+            },
+            AsmErrorKind::AddrMode { mnemonic, mode: Some(mode) } => write!(
+                fmtr,
+                "addressing mode {} not available for {}",
+                mode, mnemonic
+            ),
+            AsmErrorKind::AddrMode { mnemonic, mode: None } => {
+                write!(fmtr, "invalid operand for {}", mnemonic)
+            },
+            AsmErrorKind::Range { value } => {
+                write!(fmtr, "value {} out of range", value)
+            },
+            AsmErrorKind::BranchRange { offset } => {
+                write!(fmtr, "branch offset {} out of range", offset)
+            },
+            AsmErrorKind::DivisionByZero => write!(fmtr, "division by zero"),
+            AsmErrorKind::Include(name) => {
+                write!(fmtr, "cannot include {}", name)
+            },
+            AsmErrorKind::Unbalanced(directive) => {
+                write!(fmtr, "unbalanced {}", directive)
+            },
+            AsmErrorKind::Origin { origin } => {
+                write!(fmtr, "origin ${:04X} goes back over output", origin)
+            },
+            AsmErrorKind::Nesting => write!(fmtr, "nesting too deep"),
+            AsmErrorKind::Stopped => write!(fmtr, "assembly stopped by ERR"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl Error for AsmError {}
+
+impl From<AsmError> for io::Error {
+    fn from(error: AsmError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum MachineError {
     Read(ReadError),
     Write(WriteError),
     Bank(BankError),
+    Scheme(SchemeError),
+    Detect(DetectError),
     Opcode(OpcodeError),
     AddrMode(AddrModeError),
 }
@@ -88,6 +374,8 @@ impl fmt::Display for MachineError {
             MachineError::Read(error) => write!(fmtr, "{}", error),
             MachineError::Write(error) => write!(fmtr, "{}", error),
             MachineError::Bank(error) => write!(fmtr, "{}", error),
+            MachineError::Scheme(error) => write!(fmtr, "{}", error),
+            MachineError::Detect(error) => write!(fmtr, "{}", error),
             MachineError::Opcode(error) => write!(fmtr, "{}", error),
             MachineError::AddrMode(error) => write!(fmtr, "{}", error),
         }
@@ -114,6 +402,18 @@ impl From<BankError> for MachineError {
     }
 }
 
+impl From<SchemeError> for MachineError {
+    fn from(error: SchemeError) -> Self {
+        MachineError::Scheme(error)
+    }
+}
+
+impl From<DetectError> for MachineError {
+    fn from(error: DetectError) -> Self {
+        MachineError::Detect(error)
+    }
+}
+
 impl From<OpcodeError> for MachineError {
     fn from(error: OpcodeError) -> Self {
         MachineError::Opcode(error)
@@ -133,6 +433,8 @@ impl From<MachineError> for io::Error {
                 io::ErrorKind::AddrNotAvailable
             },
             MachineError::Bank(_) => io::ErrorKind::NotFound,
+            MachineError::Scheme(_) => io::ErrorKind::InvalidData,
+            MachineError::Detect(_) => io::ErrorKind::InvalidData,
             MachineError::Opcode(_) => io::ErrorKind::InvalidData,
             MachineError::AddrMode(_) => io::ErrorKind::InvalidInput,
         };
@@ -157,7 +157,7 @@ impl fmt::Display for Type {
 types_addrmode! {
     match instr_type {
         Aop => match mode_bits_b {
-            0x0 => Ind,
+            0x0 => XInd,
             0x1 => Zpg,
             0x2 => Imm,
             0x3 => Abs,
@@ -181,7 +181,7 @@ types_addrmode! {
             0x7 => AbsX,
         },
         Sta => match mode_bits_b {
-            0x0 => Ind,
+            0x0 => XInd,
             0x1 => Zpg,
             0x3 => Abs,
             0x4 => IndY,
@@ -202,6 +202,7 @@ types_addrmode! {
         Cxy => match mode_bits_b {
             0x0 => Imm,
             0x1 => Zpg,
+            0x3 => Abs,
         },
         Rsh => match mode_bits_b {
             0x1 => Zpg,
@@ -19,6 +19,7 @@ macro_rules! mnemonic_opcodes {
                 let bits_a = opcode::bits_a(opcode);
                 let bits_b = opcode::bits_b(opcode);
                 let bits_c = opcode::bits_c(opcode);
+                #[allow(clippy::manual_range_patterns)]
                 match (bits_a, bits_b, bits_c) {
                     $((
                         $pat_a $(|$pats_a)*,
@@ -89,6 +90,8 @@ pub enum Mnemonic {
     Sei,
     Tya,
     Tay,
+    Tax,
+    Tsx,
     Txa,
     Txs,
     Clv,
@@ -150,6 +153,8 @@ mnemonic_opcodes! {
         (5, 6, 0) => Clv,
         (5, 0 | 1 | 2 | 3 | 4 | 5 | 6 | 7, 1) => Lda,
         (5, 0 | 1 | 3 | 5 | 7, 2) => Ldx,
+        (5, 2, 2) => Tax,
+        (5, 6, 2) => Tsx,
         (6, 0 | 1 | 3, 0) => Cpy,
         (6, 2, 0) => Iny,
         (6, 4, 0) => Bne,
@@ -168,6 +173,133 @@ mnemonic_opcodes! {
 }
 
 impl Mnemonic {
+    pub const ALL: [Mnemonic; 56] = [
+        Mnemonic::Ora,
+        Mnemonic::And,
+        Mnemonic::Eor,
+        Mnemonic::Adc,
+        Mnemonic::Lda,
+        Mnemonic::Cmp,
+        Mnemonic::Sbc,
+        Mnemonic::Bpl,
+        Mnemonic::Bmi,
+        Mnemonic::Bvc,
+        Mnemonic::Bvs,
+        Mnemonic::Bcc,
+        Mnemonic::Bcs,
+        Mnemonic::Bne,
+        Mnemonic::Beq,
+        Mnemonic::Bit,
+        Mnemonic::Cpx,
+        Mnemonic::Cpy,
+        Mnemonic::Inc,
+        Mnemonic::Dec,
+        Mnemonic::Inx,
+        Mnemonic::Iny,
+        Mnemonic::Dex,
+        Mnemonic::Dey,
+        Mnemonic::Brk,
+        Mnemonic::Php,
+        Mnemonic::Rti,
+        Mnemonic::Rts,
+        Mnemonic::Clc,
+        Mnemonic::Plp,
+        Mnemonic::Sec,
+        Mnemonic::Pha,
+        Mnemonic::Cli,
+        Mnemonic::Pla,
+        Mnemonic::Sei,
+        Mnemonic::Tya,
+        Mnemonic::Tay,
+        Mnemonic::Tax,
+        Mnemonic::Tsx,
+        Mnemonic::Txa,
+        Mnemonic::Txs,
+        Mnemonic::Clv,
+        Mnemonic::Cld,
+        Mnemonic::Sed,
+        Mnemonic::Nop,
+        Mnemonic::Jmp,
+        Mnemonic::Jsr,
+        Mnemonic::Ldx,
+        Mnemonic::Ldy,
+        Mnemonic::Asl,
+        Mnemonic::Rol,
+        Mnemonic::Lsr,
+        Mnemonic::Ror,
+        Mnemonic::Sta,
+        Mnemonic::Stx,
+        Mnemonic::Sty,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|mnemonic| mnemonic.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mnemonic::Ora => "ORA",
+            Mnemonic::And => "AND",
+            Mnemonic::Eor => "EOR",
+            Mnemonic::Adc => "ADC",
+            Mnemonic::Lda => "LDA",
+            Mnemonic::Cmp => "CMP",
+            Mnemonic::Sbc => "SBC",
+            Mnemonic::Bpl => "BPL",
+            Mnemonic::Bmi => "BMI",
+            Mnemonic::Bvc => "BVC",
+            Mnemonic::Bvs => "BVS",
+            Mnemonic::Bcc => "BCC",
+            Mnemonic::Bcs => "BCS",
+            Mnemonic::Bne => "BNE",
+            Mnemonic::Beq => "BEQ",
+            Mnemonic::Bit => "BIT",
+            Mnemonic::Cpx => "CPX",
+            Mnemonic::Cpy => "CPY",
+            Mnemonic::Inc => "INC",
+            Mnemonic::Dec => "DEC",
+            Mnemonic::Inx => "INX",
+            Mnemonic::Iny => "INY",
+            Mnemonic::Dex => "DEX",
+            Mnemonic::Dey => "DEY",
+            Mnemonic::Brk => "BRK",
+            Mnemonic::Php => "PHP",
+            Mnemonic::Rti => "RTI",
+            Mnemonic::Rts => "RTS",
+            Mnemonic::Clc => "CLC",
+            Mnemonic::Plp => "PLP",
+            Mnemonic::Sec => "SEC",
+            Mnemonic::Pha => "PHA",
+            Mnemonic::Cli => "CLI",
+            Mnemonic::Pla => "PLA",
+            Mnemonic::Sei => "SEI",
+            Mnemonic::Tya => "TYA",
+            Mnemonic::Tay => "TAY",
+            Mnemonic::Tax => "TAX",
+            Mnemonic::Tsx => "TSX",
+            Mnemonic::Txa => "TXA",
+            Mnemonic::Txs => "TXS",
+            Mnemonic::Clv => "CLV",
+            Mnemonic::Cld => "CLD",
+            Mnemonic::Sed => "SED",
+            Mnemonic::Nop => "NOP",
+            Mnemonic::Jmp => "JMP",
+            Mnemonic::Jsr => "JSR",
+            Mnemonic::Ldx => "LDX",
+            Mnemonic::Ldy => "LDY",
+            Mnemonic::Asl => "ASL",
+            Mnemonic::Rol => "ROL",
+            Mnemonic::Lsr => "LSR",
+            Mnemonic::Ror => "ROR",
+            Mnemonic::Sta => "STA",
+            Mnemonic::Stx => "STX",
+            Mnemonic::Sty => "STY",
+        }
+    }
+
     pub fn instr_type(self) -> Type {
         match self {
             Mnemonic::Ora
@@ -207,6 +339,8 @@ impl Mnemonic {
             | Mnemonic::Txs
             | Mnemonic::Tya
             | Mnemonic::Tay
+            | Mnemonic::Tax
+            | Mnemonic::Tsx
             | Mnemonic::Clv
             | Mnemonic::Cld
             | Mnemonic::Sed
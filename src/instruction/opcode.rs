@@ -26,7 +26,7 @@ pub fn set_bits_b(opcode_bits: u8, value: u8) -> u8 {
 }
 
 pub fn set_bits_c(opcode_bits: u8, value: u8) -> u8 {
-    (opcode_bits & 0xF8) | (value & 0x3)
+    (opcode_bits & 0xFC) | (value & 0x3)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -48,6 +48,67 @@ impl Opcode {
         let addrmode = instr_type.addrmode_to_bits(self.addrmode)?;
         Ok(mnemonic | addrmode)
     }
+
+    pub fn base_cycles(self) -> u8 {
+        match self.mnemonic {
+            Mnemonic::Brk => 7,
+            Mnemonic::Rti | Mnemonic::Rts | Mnemonic::Jsr => 6,
+            Mnemonic::Pla | Mnemonic::Plp => 4,
+            Mnemonic::Pha | Mnemonic::Php => 3,
+            Mnemonic::Jmp => match self.addrmode {
+                AddrMode::Ind => 5,
+                _ => 3,
+            },
+            Mnemonic::Sta | Mnemonic::Stx | Mnemonic::Sty => {
+                match self.addrmode {
+                    AddrMode::Zpg => 3,
+                    AddrMode::ZpgX | AddrMode::ZpgY | AddrMode::Abs => 4,
+                    AddrMode::AbsX | AddrMode::AbsY => 5,
+                    _ => 6,
+                }
+            },
+            Mnemonic::Asl
+            | Mnemonic::Lsr
+            | Mnemonic::Rol
+            | Mnemonic::Ror
+            | Mnemonic::Inc
+            | Mnemonic::Dec => match self.addrmode {
+                AddrMode::Acc => 2,
+                AddrMode::Zpg => 5,
+                AddrMode::ZpgX | AddrMode::Abs => 6,
+                _ => 7,
+            },
+            _ => match self.addrmode {
+                AddrMode::Zpg => 3,
+                AddrMode::ZpgX
+                | AddrMode::ZpgY
+                | AddrMode::Abs
+                | AddrMode::AbsX
+                | AddrMode::AbsY => 4,
+                AddrMode::IndY => 5,
+                AddrMode::XInd => 6,
+                _ => 2,
+            },
+        }
+    }
+
+    pub fn has_page_penalty(self) -> bool {
+        match self.mnemonic {
+            Mnemonic::Ora
+            | Mnemonic::And
+            | Mnemonic::Eor
+            | Mnemonic::Adc
+            | Mnemonic::Sbc
+            | Mnemonic::Cmp
+            | Mnemonic::Lda
+            | Mnemonic::Ldx
+            | Mnemonic::Ldy => matches!(
+                self.addrmode,
+                AddrMode::AbsX | AddrMode::AbsY | AddrMode::IndY
+            ),
+            _ => false,
+        }
+    }
 }
 
 impl Encode for Opcode {
@@ -74,3 +135,241 @@ impl Decode for Opcode {
         Self::from_bits(bits).map_err(MachineError::from).map_err(Into::into)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every documented NMOS opcode; everything else must fail to decode.
+    const DOCUMENTED: &[(u8, &str)] = &[
+        (0x00, "BRK IMPL"),
+        (0x01, "ORA XIND"),
+        (0x05, "ORA ZPG"),
+        (0x06, "ASL ZPG"),
+        (0x08, "PHP IMPL"),
+        (0x09, "ORA IMM"),
+        (0x0A, "ASL ACC"),
+        (0x0D, "ORA ABS"),
+        (0x0E, "ASL ABS"),
+        (0x10, "BPL REL"),
+        (0x11, "ORA INDY"),
+        (0x15, "ORA ZPGX"),
+        (0x16, "ASL ZPGX"),
+        (0x18, "CLC IMPL"),
+        (0x19, "ORA ABSY"),
+        (0x1D, "ORA ABSX"),
+        (0x1E, "ASL ABSX"),
+        (0x20, "JSR ABS"),
+        (0x21, "AND XIND"),
+        (0x24, "BIT ZPG"),
+        (0x25, "AND ZPG"),
+        (0x26, "ROL ZPG"),
+        (0x28, "PLP IMPL"),
+        (0x29, "AND IMM"),
+        (0x2A, "ROL ACC"),
+        (0x2C, "BIT ABS"),
+        (0x2D, "AND ABS"),
+        (0x2E, "ROL ABS"),
+        (0x30, "BMI REL"),
+        (0x31, "AND INDY"),
+        (0x35, "AND ZPGX"),
+        (0x36, "ROL ZPGX"),
+        (0x38, "SEC IMPL"),
+        (0x39, "AND ABSY"),
+        (0x3D, "AND ABSX"),
+        (0x3E, "ROL ABSX"),
+        (0x40, "RTI IMPL"),
+        (0x41, "EOR XIND"),
+        (0x45, "EOR ZPG"),
+        (0x46, "LSR ZPG"),
+        (0x48, "PHA IMPL"),
+        (0x49, "EOR IMM"),
+        (0x4A, "LSR ACC"),
+        (0x4C, "JMP ABS"),
+        (0x4D, "EOR ABS"),
+        (0x4E, "LSR ABS"),
+        (0x50, "BVC REL"),
+        (0x51, "EOR INDY"),
+        (0x55, "EOR ZPGX"),
+        (0x56, "LSR ZPGX"),
+        (0x58, "CLI IMPL"),
+        (0x59, "EOR ABSY"),
+        (0x5D, "EOR ABSX"),
+        (0x5E, "LSR ABSX"),
+        (0x60, "RTS IMPL"),
+        (0x61, "ADC XIND"),
+        (0x65, "ADC ZPG"),
+        (0x66, "ROR ZPG"),
+        (0x68, "PLA IMPL"),
+        (0x69, "ADC IMM"),
+        (0x6A, "ROR ACC"),
+        (0x6C, "JMP IND"),
+        (0x6D, "ADC ABS"),
+        (0x6E, "ROR ABS"),
+        (0x70, "BVS REL"),
+        (0x71, "ADC INDY"),
+        (0x75, "ADC ZPGX"),
+        (0x76, "ROR ZPGX"),
+        (0x78, "SEI IMPL"),
+        (0x79, "ADC ABSY"),
+        (0x7D, "ADC ABSX"),
+        (0x7E, "ROR ABSX"),
+        (0x81, "STA XIND"),
+        (0x84, "STY ZPG"),
+        (0x85, "STA ZPG"),
+        (0x86, "STX ZPG"),
+        (0x88, "DEY IMPL"),
+        (0x8A, "TXA IMPL"),
+        (0x8C, "STY ABS"),
+        (0x8D, "STA ABS"),
+        (0x8E, "STX ABS"),
+        (0x90, "BCC REL"),
+        (0x91, "STA INDY"),
+        (0x94, "STY ZPGX"),
+        (0x95, "STA ZPGX"),
+        (0x96, "STX ZPGY"),
+        (0x98, "TYA IMPL"),
+        (0x99, "STA ABSY"),
+        (0x9A, "TXS IMPL"),
+        (0x9D, "STA ABSX"),
+        (0xA0, "LDY IMM"),
+        (0xA1, "LDA XIND"),
+        (0xA2, "LDX IMM"),
+        (0xA4, "LDY ZPG"),
+        (0xA5, "LDA ZPG"),
+        (0xA6, "LDX ZPG"),
+        (0xA8, "TAY IMPL"),
+        (0xA9, "LDA IMM"),
+        (0xAA, "TAX IMPL"),
+        (0xAC, "LDY ABS"),
+        (0xAD, "LDA ABS"),
+        (0xAE, "LDX ABS"),
+        (0xB0, "BCS REL"),
+        (0xB1, "LDA INDY"),
+        (0xB4, "LDY ZPGX"),
+        (0xB5, "LDA ZPGX"),
+        (0xB6, "LDX ZPGY"),
+        (0xB8, "CLV IMPL"),
+        (0xB9, "LDA ABSY"),
+        (0xBA, "TSX IMPL"),
+        (0xBC, "LDY ABSX"),
+        (0xBD, "LDA ABSX"),
+        (0xBE, "LDX ABSY"),
+        (0xC0, "CPY IMM"),
+        (0xC1, "CMP XIND"),
+        (0xC4, "CPY ZPG"),
+        (0xC5, "CMP ZPG"),
+        (0xC6, "DEC ZPG"),
+        (0xC8, "INY IMPL"),
+        (0xC9, "CMP IMM"),
+        (0xCA, "DEX IMPL"),
+        (0xCC, "CPY ABS"),
+        (0xCD, "CMP ABS"),
+        (0xCE, "DEC ABS"),
+        (0xD0, "BNE REL"),
+        (0xD1, "CMP INDY"),
+        (0xD5, "CMP ZPGX"),
+        (0xD6, "DEC ZPGX"),
+        (0xD8, "CLD IMPL"),
+        (0xD9, "CMP ABSY"),
+        (0xDD, "CMP ABSX"),
+        (0xDE, "DEC ABSX"),
+        (0xE0, "CPX IMM"),
+        (0xE1, "SBC XIND"),
+        (0xE4, "CPX ZPG"),
+        (0xE5, "SBC ZPG"),
+        (0xE6, "INC ZPG"),
+        (0xE8, "INX IMPL"),
+        (0xE9, "SBC IMM"),
+        (0xEA, "NOP IMPL"),
+        (0xEC, "CPX ABS"),
+        (0xED, "SBC ABS"),
+        (0xEE, "INC ABS"),
+        (0xF0, "BEQ REL"),
+        (0xF1, "SBC INDY"),
+        (0xF5, "SBC ZPGX"),
+        (0xF6, "INC ZPGX"),
+        (0xF8, "SED IMPL"),
+        (0xF9, "SBC ABSY"),
+        (0xFD, "SBC ABSX"),
+        (0xFE, "INC ABSX"),
+    ];
+
+    #[test]
+    fn decodes_documented_opcodes() {
+        assert_eq!(DOCUMENTED.len(), 151);
+        for bits in 0..=0xFF {
+            let expected = DOCUMENTED.iter().find(|(b, _)| *b == bits);
+            match (Opcode::from_bits(bits), expected) {
+                (Ok(opcode), Some((_, name))) => assert_eq!(
+                    format!("{} {}", opcode.mnemonic.name(), opcode.addrmode),
+                    *name,
+                    "opcode {:02X}",
+                    bits
+                ),
+                (Err(_), None) => (),
+                (decoded, _) => panic!("opcode {:02X}: {:?}", bits, decoded),
+            }
+        }
+    }
+
+    #[test]
+    fn encodes_back_to_the_same_bits() {
+        for &(bits, _) in DOCUMENTED {
+            let opcode = Opcode::from_bits(bits).unwrap();
+            assert_eq!(opcode.to_bits().unwrap(), bits);
+        }
+    }
+
+    // Base cycles by opcode, one row per high nibble, 0 where undocumented.
+    const CYCLES: [&str; 16] = [
+        "7 6 0 0 0 3 5 0 3 2 2 0 0 4 6 0",
+        "2 5 0 0 0 4 6 0 2 4 0 0 0 4 7 0",
+        "6 6 0 0 3 3 5 0 4 2 2 0 4 4 6 0",
+        "2 5 0 0 0 4 6 0 2 4 0 0 0 4 7 0",
+        "6 6 0 0 0 3 5 0 3 2 2 0 3 4 6 0",
+        "2 5 0 0 0 4 6 0 2 4 0 0 0 4 7 0",
+        "6 6 0 0 0 3 5 0 4 2 2 0 5 4 6 0",
+        "2 5 0 0 0 4 6 0 2 4 0 0 0 4 7 0",
+        "0 6 0 0 3 3 3 0 2 0 2 0 4 4 4 0",
+        "2 6 0 0 4 4 4 0 2 5 2 0 0 5 0 0",
+        "2 6 2 0 3 3 3 0 2 2 2 0 4 4 4 0",
+        "2 5 0 0 4 4 4 0 2 4 2 0 4 4 4 0",
+        "2 6 0 0 3 3 5 0 2 2 2 0 4 4 6 0",
+        "2 5 0 0 0 4 6 0 2 4 0 0 0 4 7 0",
+        "2 6 0 0 3 3 5 0 2 2 2 0 4 4 6 0",
+        "2 5 0 0 0 4 6 0 2 4 0 0 0 4 7 0",
+    ];
+
+    const PAGE_PENALTY: &[u8] = &[
+        0x11, 0x19, 0x1D, 0x31, 0x39, 0x3D, 0x51, 0x59, 0x5D, 0x71, 0x79, 0x7D,
+        0xB1, 0xB9, 0xBC, 0xBD, 0xBE, 0xD1, 0xD9, 0xDD, 0xF1, 0xF9, 0xFD,
+    ];
+
+    #[test]
+    fn base_cycles() {
+        let cycles = CYCLES.iter().flat_map(|row| row.split_whitespace());
+        for (bits, expected) in (0..=0xFF).zip(cycles) {
+            let expected: u8 = expected.parse().unwrap();
+            match Opcode::from_bits(bits) {
+                Ok(opcode) => {
+                    assert_eq!(opcode.base_cycles(), expected, "{:02X}", bits)
+                },
+                Err(_) => assert_eq!(expected, 0, "{:02X}", bits),
+            }
+        }
+    }
+
+    #[test]
+    fn page_penalty() {
+        for &(bits, _) in DOCUMENTED {
+            let opcode = Opcode::from_bits(bits).unwrap();
+            assert_eq!(
+                opcode.has_page_penalty(),
+                PAGE_PENALTY.contains(&bits),
+                "{:02X}",
+                bits
+            );
+        }
+    }
+}
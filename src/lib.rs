@@ -1,6 +1,17 @@
 pub mod error;
 pub mod memory;
+pub mod bus;
+pub mod tia;
+pub mod riot;
+pub mod cartridge;
 pub mod addrmode;
 pub mod instruction;
 pub mod machine;
+pub mod arm;
 pub mod binary;
+pub mod console;
+pub mod syntax;
+pub mod asm;
+pub mod disasm;
+pub mod md5;
+pub mod properties;
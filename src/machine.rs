@@ -1,12 +1,602 @@
+use crate::{
+    addrmode::Operand,
+    binary::{decode::MemoryDecoder, Decoder},
+    bus::Bus,
+    error::{AddrModeError, MachineError},
+    instruction::{Instruction, Mnemonic},
+    memory::Memory,
+};
+
 #[derive(Debug, Clone)]
-pub struct Machine {
-    memory: Box<[u8]>,
+pub struct Machine<B = Memory>
+where
+    B: Bus,
+{
+    bus: B,
     ra: u8,
     rx: u8,
     ry: u8,
     sp: u8,
     sr: Status,
     pc: u16,
+    cycles: u64,
+}
+
+impl<B> Machine<B>
+where
+    B: Bus,
+{
+    pub const ADDRESS_MASK: u16 = 0x1FFF;
+    pub const STACK_PAGE: u16 = 0x0100;
+    pub const RESET_VECTOR: u16 = 0xFFFC;
+    pub const BRK_VECTOR: u16 = 0xFFFE;
+    pub const RESET_CYCLES: u8 = 7;
+
+    pub fn new(bus: B) -> Result<Self, MachineError> {
+        let mut this = Self {
+            bus,
+            ra: 0,
+            rx: 0,
+            ry: 0,
+            sp: 0,
+            sr: Status::zeroed(),
+            pc: 0,
+            cycles: 0,
+        };
+        this.reset()?;
+        Ok(this)
+    }
+
+    // The reset sequence goes through the motions of an interrupt with the
+    // bus in read mode: three stack pushes are skipped (so SP ends up 3
+    // below where it was, $FD on power-on), I is set and the remaining
+    // registers are left as they were.
+    pub fn reset(&mut self) -> Result<(), MachineError> {
+        self.sp = self.sp.wrapping_sub(3);
+        self.sr.set_i(true);
+        self.pc = self.read_vector(Self::RESET_VECTOR)?;
+        self.cycles += u64::from(Self::RESET_CYCLES);
+        Ok(())
+    }
+
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    pub fn into_bus(self) -> B {
+        self.bus
+    }
+
+    pub fn ra(&self) -> u8 {
+        self.ra
+    }
+
+    pub fn set_ra(&mut self, value: u8) {
+        self.ra = value;
+    }
+
+    pub fn rx(&self) -> u8 {
+        self.rx
+    }
+
+    pub fn set_rx(&mut self, value: u8) {
+        self.rx = value;
+    }
+
+    pub fn ry(&self) -> u8 {
+        self.ry
+    }
+
+    pub fn set_ry(&mut self, value: u8) {
+        self.ry = value;
+    }
+
+    pub fn sp(&self) -> u8 {
+        self.sp
+    }
+
+    pub fn set_sp(&mut self, value: u8) {
+        self.sp = value;
+    }
+
+    pub fn sr(&self) -> Status {
+        self.sr
+    }
+
+    pub fn set_sr(&mut self, value: Status) {
+        self.sr = value;
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, value: u16) {
+        self.pc = value;
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn reset_cycles(&mut self) {
+        self.cycles = 0;
+    }
+
+    pub fn fetch(&mut self) -> Result<Instruction, MachineError> {
+        MemoryDecoder::new(&mut self.bus, &mut self.pc).decode()
+    }
+
+    pub fn step(&mut self) -> Result<Instruction, MachineError> {
+        let instruction = self.fetch()?;
+        self.execute(instruction)?;
+        Ok(instruction)
+    }
+
+    pub fn stall(&mut self, cycles: u64) {
+        self.cycles += cycles;
+    }
+
+    // Computes how many cycles the instruction will take in the current
+    // state without touching the bus, so callers can line devices up with
+    // the cycle in which the instruction actually accesses memory.
+    pub fn timing(&self, instruction: Instruction) -> Result<u8, MachineError> {
+        let opcode = instruction.opcode();
+        let mut cycles = opcode.base_cycles();
+
+        if opcode.has_page_penalty() {
+            let (base, index) = match instruction.operand {
+                Operand::AbsX(operand) => (operand.address, self.rx),
+                Operand::AbsY(operand) => (operand.address, self.ry),
+                Operand::IndY(operand) => {
+                    (self.peek_zpg_word(operand.address)?, self.ry)
+                },
+                _ => (0, 0),
+            };
+            if crosses_page(base, base.wrapping_add(u16::from(index))) {
+                cycles += 1;
+            }
+        }
+
+        if let Operand::Rel(operand) = instruction.operand {
+            if self.branch_taken(instruction.mnemonic) {
+                let target =
+                    self.pc.wrapping_add(operand.address as i16 as u16);
+                cycles += 1;
+                if crosses_page(self.pc, target) {
+                    cycles += 1;
+                }
+            }
+        }
+
+        Ok(cycles)
+    }
+
+    pub fn execute(
+        &mut self,
+        instruction: Instruction,
+    ) -> Result<u8, MachineError> {
+        let cycles = self.timing(instruction)?;
+
+        match instruction.mnemonic {
+            Mnemonic::Lda => {
+                self.ra = self.load(instruction)?;
+                self.update_nz(self.ra);
+            },
+            Mnemonic::Ldx => {
+                self.rx = self.load(instruction)?;
+                self.update_nz(self.rx);
+            },
+            Mnemonic::Ldy => {
+                self.ry = self.load(instruction)?;
+                self.update_nz(self.ry);
+            },
+            Mnemonic::Sta => self.store(instruction, self.ra)?,
+            Mnemonic::Stx => self.store(instruction, self.rx)?,
+            Mnemonic::Sty => self.store(instruction, self.ry)?,
+
+            Mnemonic::Ora => {
+                self.ra |= self.load(instruction)?;
+                self.update_nz(self.ra);
+            },
+            Mnemonic::And => {
+                self.ra &= self.load(instruction)?;
+                self.update_nz(self.ra);
+            },
+            Mnemonic::Eor => {
+                self.ra ^= self.load(instruction)?;
+                self.update_nz(self.ra);
+            },
+            Mnemonic::Adc => {
+                let operand = self.load(instruction)?;
+                self.add(operand);
+            },
+            Mnemonic::Sbc => {
+                let operand = self.load(instruction)?;
+                self.subtract(operand);
+            },
+            Mnemonic::Cmp => {
+                let operand = self.load(instruction)?;
+                self.compare(self.ra, operand);
+            },
+            Mnemonic::Cpx => {
+                let operand = self.load(instruction)?;
+                self.compare(self.rx, operand);
+            },
+            Mnemonic::Cpy => {
+                let operand = self.load(instruction)?;
+                self.compare(self.ry, operand);
+            },
+            Mnemonic::Bit => {
+                let operand = self.load(instruction)?;
+                self.sr.set_z(self.ra & operand == 0);
+                self.sr.set_n(operand & 0x80 != 0);
+                self.sr.set_v(operand & 0x40 != 0);
+            },
+
+            Mnemonic::Asl => self.modify(instruction, |this, data| {
+                this.sr.set_c(data & 0x80 != 0);
+                data << 1
+            })?,
+            Mnemonic::Lsr => self.modify(instruction, |this, data| {
+                this.sr.set_c(data & 0x01 != 0);
+                data >> 1
+            })?,
+            Mnemonic::Rol => self.modify(instruction, |this, data| {
+                let carry = u8::from(this.sr.get_c());
+                this.sr.set_c(data & 0x80 != 0);
+                data << 1 | carry
+            })?,
+            Mnemonic::Ror => self.modify(instruction, |this, data| {
+                let carry = u8::from(this.sr.get_c());
+                this.sr.set_c(data & 0x01 != 0);
+                data >> 1 | carry << 7
+            })?,
+            Mnemonic::Inc => {
+                self.modify(instruction, |_, data| data.wrapping_add(1))?
+            },
+            Mnemonic::Dec => {
+                self.modify(instruction, |_, data| data.wrapping_sub(1))?
+            },
+
+            Mnemonic::Inx => {
+                self.rx = self.rx.wrapping_add(1);
+                self.update_nz(self.rx);
+            },
+            Mnemonic::Iny => {
+                self.ry = self.ry.wrapping_add(1);
+                self.update_nz(self.ry);
+            },
+            Mnemonic::Dex => {
+                self.rx = self.rx.wrapping_sub(1);
+                self.update_nz(self.rx);
+            },
+            Mnemonic::Dey => {
+                self.ry = self.ry.wrapping_sub(1);
+                self.update_nz(self.ry);
+            },
+            Mnemonic::Tax => {
+                self.rx = self.ra;
+                self.update_nz(self.rx);
+            },
+            Mnemonic::Tay => {
+                self.ry = self.ra;
+                self.update_nz(self.ry);
+            },
+            Mnemonic::Txa => {
+                self.ra = self.rx;
+                self.update_nz(self.ra);
+            },
+            Mnemonic::Tya => {
+                self.ra = self.ry;
+                self.update_nz(self.ra);
+            },
+            Mnemonic::Tsx => {
+                self.rx = self.sp;
+                self.update_nz(self.rx);
+            },
+            Mnemonic::Txs => self.sp = self.rx,
+
+            Mnemonic::Bpl
+            | Mnemonic::Bmi
+            | Mnemonic::Bvc
+            | Mnemonic::Bvs
+            | Mnemonic::Bcc
+            | Mnemonic::Bcs
+            | Mnemonic::Bne
+            | Mnemonic::Beq => {
+                if self.branch_taken(instruction.mnemonic) {
+                    self.pc = self.address(instruction)?;
+                }
+            },
+
+            Mnemonic::Pha => self.push(self.ra)?,
+            Mnemonic::Php => self.push(self.sr.pushed_bits())?,
+            Mnemonic::Pla => {
+                self.ra = self.pull()?;
+                self.update_nz(self.ra);
+            },
+            Mnemonic::Plp => {
+                let bits = self.pull()?;
+                self.sr = Status::pulled_bits(bits);
+            },
+
+            Mnemonic::Clc => self.sr.set_c(false),
+            Mnemonic::Sec => self.sr.set_c(true),
+            Mnemonic::Cli => self.sr.set_i(false),
+            Mnemonic::Sei => self.sr.set_i(true),
+            Mnemonic::Cld => self.sr.set_d(false),
+            Mnemonic::Sed => self.sr.set_d(true),
+            Mnemonic::Clv => self.sr.set_v(false),
+            Mnemonic::Nop => (),
+
+            Mnemonic::Jmp => self.pc = self.address(instruction)?,
+            Mnemonic::Jsr => {
+                let target = self.address(instruction)?;
+                self.push_word(self.pc.wrapping_sub(1))?;
+                self.pc = target;
+            },
+            Mnemonic::Rts => self.pc = self.pull_word()?.wrapping_add(1),
+            Mnemonic::Rti => {
+                let bits = self.pull()?;
+                self.sr = Status::pulled_bits(bits);
+                self.pc = self.pull_word()?;
+            },
+            Mnemonic::Brk => {
+                self.push_word(self.pc.wrapping_add(1))?;
+                self.push(self.sr.pushed_bits())?;
+                self.sr.set_i(true);
+                self.pc = self.read_vector(Self::BRK_VECTOR)?;
+            },
+        }
+
+        self.cycles += u64::from(cycles);
+        Ok(cycles)
+    }
+
+    fn update_nz(&mut self, data: u8) {
+        self.sr.set_z(data == 0);
+        self.sr.set_n(data & 0x80 != 0);
+    }
+
+    fn add(&mut self, operand: u8) {
+        if self.sr.get_d() {
+            self.add_decimal(operand);
+        } else {
+            self.add_binary(operand);
+        }
+    }
+
+    fn subtract(&mut self, operand: u8) {
+        if self.sr.get_d() {
+            self.subtract_decimal(operand);
+        } else {
+            self.add_binary(!operand);
+        }
+    }
+
+    fn add_binary(&mut self, operand: u8) {
+        let sum = u16::from(self.ra)
+            + u16::from(operand)
+            + u16::from(self.sr.get_c());
+        let result = sum as u8;
+        self.sr.set_c(sum > 0xFF);
+        self.sr.set_v((self.ra ^ result) & (operand ^ result) & 0x80 != 0);
+        self.ra = result;
+        self.update_nz(self.ra);
+    }
+
+    // NMOS decimal addition: each nibble is adjusted separately, Z comes from
+    // the plain binary sum, and N and V come from the intermediate result
+    // after the low nibble fixup but before the high nibble one. Operands
+    // that are not valid BCD produce the same garbage the real chip does.
+    fn add_decimal(&mut self, operand: u8) {
+        let carry = u16::from(self.sr.get_c());
+        let binary = u16::from(self.ra) + u16::from(operand) + carry;
+
+        let mut low = u16::from(self.ra & 0x0F) + u16::from(operand & 0x0F);
+        low += carry;
+        if low >= 0x0A {
+            low = ((low + 0x06) & 0x0F) + 0x10;
+        }
+        let mut sum = u16::from(self.ra & 0xF0) + u16::from(operand & 0xF0);
+        sum += low;
+
+        let partial = sum as u8;
+        self.sr.set_z(binary as u8 == 0);
+        self.sr.set_n(partial & 0x80 != 0);
+        self.sr.set_v((self.ra ^ partial) & (operand ^ partial) & 0x80 != 0);
+
+        if sum >= 0xA0 {
+            sum += 0x60;
+        }
+        self.sr.set_c(sum >= 0x100);
+        self.ra = sum as u8;
+    }
+
+    // NMOS decimal subtraction: all flags are the ones the binary
+    // subtraction would produce, only the accumulator is BCD-adjusted.
+    fn subtract_decimal(&mut self, operand: u8) {
+        let minuend = self.ra;
+        let borrow = 1 - i16::from(self.sr.get_c());
+        self.add_binary(!operand);
+
+        let mut low = i16::from(minuend & 0x0F) - i16::from(operand & 0x0F);
+        low -= borrow;
+        if low < 0 {
+            low = ((low - 0x06) & 0x0F) - 0x10;
+        }
+        let mut difference =
+            i16::from(minuend & 0xF0) - i16::from(operand & 0xF0) + low;
+        if difference < 0 {
+            difference -= 0x60;
+        }
+        self.ra = difference as u8;
+    }
+
+    fn compare(&mut self, register: u8, operand: u8) {
+        self.sr.set_c(register >= operand);
+        self.update_nz(register.wrapping_sub(operand));
+    }
+
+    fn branch_taken(&self, mnemonic: Mnemonic) -> bool {
+        match mnemonic {
+            Mnemonic::Bpl => !self.sr.get_n(),
+            Mnemonic::Bmi => self.sr.get_n(),
+            Mnemonic::Bvc => !self.sr.get_v(),
+            Mnemonic::Bvs => self.sr.get_v(),
+            Mnemonic::Bcc => !self.sr.get_c(),
+            Mnemonic::Bcs => self.sr.get_c(),
+            Mnemonic::Bne => !self.sr.get_z(),
+            Mnemonic::Beq => self.sr.get_z(),
+            _ => false,
+        }
+    }
+
+    fn address(
+        &mut self,
+        instruction: Instruction,
+    ) -> Result<u16, MachineError> {
+        let address = match instruction.operand {
+            Operand::Abs(operand) => operand.address,
+            Operand::AbsX(operand) => {
+                operand.address.wrapping_add(u16::from(self.rx))
+            },
+            Operand::AbsY(operand) => {
+                operand.address.wrapping_add(u16::from(self.ry))
+            },
+            Operand::Zpg(operand) => u16::from(operand.address),
+            Operand::ZpgX(operand) => {
+                u16::from(operand.address.wrapping_add(self.rx))
+            },
+            Operand::ZpgY(operand) => {
+                u16::from(operand.address.wrapping_add(self.ry))
+            },
+            Operand::Ind(operand) => {
+                // The NMOS 6502 does not carry into the high byte when
+                // fetching the pointer, so $xxFF wraps within its page.
+                let low = self.bus.read(operand.address)?;
+                let high_address = (operand.address & 0xFF00)
+                    | (operand.address.wrapping_add(1) & 0x00FF);
+                let high = self.bus.read(high_address)?;
+                u16::from_le_bytes([low, high])
+            },
+            Operand::XInd(operand) => {
+                self.read_zpg_word(operand.address.wrapping_add(self.rx))?
+            },
+            Operand::IndY(operand) => self
+                .read_zpg_word(operand.address)?
+                .wrapping_add(u16::from(self.ry)),
+            Operand::Rel(operand) => {
+                self.pc.wrapping_add(operand.address as i16 as u16)
+            },
+            Operand::Acc(_) | Operand::Imm(_) | Operand::Impl(_) => {
+                return Err(AddrModeError {
+                    mode: instruction.operand.addrmode(),
+                    instr_type: instruction.mnemonic.instr_type(),
+                }
+                .into())
+            },
+        };
+
+        Ok(address)
+    }
+
+    fn load(&mut self, instruction: Instruction) -> Result<u8, MachineError> {
+        match instruction.operand {
+            Operand::Imm(operand) => Ok(operand.bits),
+            Operand::Acc(_) => Ok(self.ra),
+            _ => {
+                let address = self.address(instruction)?;
+                Ok(self.bus.read(address)?)
+            },
+        }
+    }
+
+    fn store(
+        &mut self,
+        instruction: Instruction,
+        data: u8,
+    ) -> Result<(), MachineError> {
+        match instruction.operand {
+            Operand::Acc(_) => self.ra = data,
+            _ => {
+                let address = self.address(instruction)?;
+                self.bus.write(address, data)?;
+            },
+        }
+        Ok(())
+    }
+
+    fn modify<F>(
+        &mut self,
+        instruction: Instruction,
+        operation: F,
+    ) -> Result<(), MachineError>
+    where
+        F: FnOnce(&mut Self, u8) -> u8,
+    {
+        let data = self.load(instruction)?;
+        let result = operation(self, data);
+        self.store(instruction, result)?;
+        self.update_nz(result);
+        Ok(())
+    }
+
+    fn read_word(&mut self, address: u16) -> Result<u16, MachineError> {
+        let low = self.bus.read(address)?;
+        let high = self.bus.read(address.wrapping_add(1))?;
+        Ok(u16::from_le_bytes([low, high]))
+    }
+
+    // The 6507 only has 13 address lines, so the vectors at the top of the
+    // 6502 address space are seen by the cartridge at $1FFA-$1FFF.
+    fn read_vector(&mut self, vector: u16) -> Result<u16, MachineError> {
+        self.read_word(vector & Self::ADDRESS_MASK)
+    }
+
+    fn read_zpg_word(&mut self, address: u8) -> Result<u16, MachineError> {
+        let low = self.bus.read(u16::from(address))?;
+        let high = self.bus.read(u16::from(address.wrapping_add(1)))?;
+        Ok(u16::from_le_bytes([low, high]))
+    }
+
+    fn peek_zpg_word(&self, address: u8) -> Result<u16, MachineError> {
+        let low = self.bus.peek(u16::from(address))?;
+        let high = self.bus.peek(u16::from(address.wrapping_add(1)))?;
+        Ok(u16::from_le_bytes([low, high]))
+    }
+
+    fn push(&mut self, data: u8) -> Result<(), MachineError> {
+        self.bus.write(Self::STACK_PAGE | u16::from(self.sp), data)?;
+        self.sp = self.sp.wrapping_sub(1);
+        Ok(())
+    }
+
+    fn pull(&mut self) -> Result<u8, MachineError> {
+        self.sp = self.sp.wrapping_add(1);
+        Ok(self.bus.read(Self::STACK_PAGE | u16::from(self.sp))?)
+    }
+
+    fn push_word(&mut self, data: u16) -> Result<(), MachineError> {
+        let [low, high] = data.to_le_bytes();
+        self.push(high)?;
+        self.push(low)
+    }
+
+    fn pull_word(&mut self) -> Result<u16, MachineError> {
+        let low = self.pull()?;
+        let high = self.pull()?;
+        Ok(u16::from_le_bytes([low, high]))
+    }
+}
+
+fn crosses_page(from: u16, to: u16) -> bool {
+    from & 0xFF00 != to & 0xFF00
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
@@ -22,11 +612,34 @@ impl Status {
     const INTERRUPT: u8 = 2;
     const ZERO: u8 = 1;
     const CARRY: u8 = 0;
+    const UNUSED: u8 = 5;
 
     pub fn zeroed() -> Self {
         Self::default()
     }
 
+    pub fn from_bits(bits: u8) -> Self {
+        Self { flags: bits }
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.flags
+    }
+
+    fn pushed_bits(&self) -> u8 {
+        let mut pushed = *self;
+        pushed.set(Self::UNUSED, true);
+        pushed.set_b(true);
+        pushed.flags
+    }
+
+    fn pulled_bits(bits: u8) -> Self {
+        let mut pulled = Self::from_bits(bits);
+        pulled.set(Self::UNUSED, false);
+        pulled.set_b(false);
+        pulled
+    }
+
     fn get(&self, flag: u8) -> bool {
         self.flags & (1 << flag) != 0
     }
@@ -92,3 +705,270 @@ impl Status {
         self.set(Self::CARRY, value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        addrmode::Immediate,
+        error::{ReadError, WriteError},
+    };
+
+    // Flat RAM over the whole address space, reset vector at $1000.
+    struct Flat(Vec<u8>);
+
+    impl Bus for Flat {
+        fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+            self.peek(address)
+        }
+
+        fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError> {
+            self.0[usize::from(address)] = data;
+            Ok(())
+        }
+
+        fn peek(&self, address: u16) -> Result<u8, ReadError> {
+            Ok(self.0[usize::from(address)])
+        }
+    }
+
+    fn machine(program: &[u8]) -> Machine<Flat> {
+        let mut memory = vec![0; 0x10000];
+        memory[0x1000..0x1000 + program.len()].copy_from_slice(program);
+        memory[0x1FFC..0x1FFE].copy_from_slice(&[0x00, 0x10]);
+        Machine::new(Flat(memory)).unwrap()
+    }
+
+    // Accumulator and N, V, Z, C after one decimal mode ADC or SBC.
+    fn decimal(
+        mnemonic: Mnemonic,
+        ra: u8,
+        operand: u8,
+        carry: bool,
+    ) -> (u8, [bool; 4]) {
+        let mut machine = machine(&[]);
+        let mut sr = Status::zeroed();
+        sr.set_d(true);
+        sr.set_c(carry);
+        machine.set_sr(sr);
+        machine.set_ra(ra);
+        let operand = Operand::Imm(Immediate { bits: operand });
+        machine.execute(Instruction { mnemonic, operand }).unwrap();
+        let sr = machine.sr();
+        (machine.ra(), [sr.get_n(), sr.get_v(), sr.get_z(), sr.get_c()])
+    }
+
+    fn adc(ra: u8, operand: u8, carry: bool) -> (u8, [bool; 4]) {
+        decimal(Mnemonic::Adc, ra, operand, carry)
+    }
+
+    fn sbc(ra: u8, operand: u8, carry: bool) -> (u8, [bool; 4]) {
+        decimal(Mnemonic::Sbc, ra, operand, carry)
+    }
+
+    #[test]
+    fn decimal_add() {
+        assert_eq!(adc(0x12, 0x34, false).0, 0x46);
+        assert_eq!(adc(0x15, 0x26, false).0, 0x41);
+        assert_eq!(adc(0x58, 0x46, true), (0x05, [true, true, false, true]));
+        assert_eq!(adc(0x81, 0x92, false), (0x73, [false, true, false, true]));
+    }
+
+    #[test]
+    fn decimal_subtract() {
+        assert_eq!(sbc(0x46, 0x12, true), (0x34, [false, false, false, true]));
+        assert_eq!(sbc(0x40, 0x13, true).0, 0x27);
+        assert_eq!(sbc(0x32, 0x02, false).0, 0x29);
+        assert_eq!(sbc(0x12, 0x21, true), (0x91, [true, false, false, false]));
+        assert_eq!(sbc(0x00, 0x01, true), (0x99, [true, false, false, false]));
+    }
+
+    #[test]
+    fn decimal_invalid_nibbles() {
+        assert_eq!(adc(0x0A, 0x00, false).0, 0x10);
+        assert_eq!(adc(0x0F, 0x00, false).0, 0x15);
+        assert_eq!(adc(0xFF, 0xFF, true), (0x55, [true, false, false, true]));
+        assert_eq!(sbc(0x0A, 0x00, true).0, 0x0A);
+        assert_eq!(sbc(0x20, 0x0F, true).0, 0x1B);
+    }
+
+    // Z follows the binary sum and N and V the half-adjusted one, so none
+    // of them need to agree with the BCD result.
+    #[test]
+    fn decimal_nmos_flags() {
+        assert_eq!(adc(0x99, 0x01, false), (0x00, [true, false, false, true]));
+        assert_eq!(adc(0x79, 0x00, true), (0x80, [true, true, false, false]));
+        assert_eq!(adc(0x24, 0x56, false), (0x80, [true, true, false, false]));
+        assert_eq!(adc(0x50, 0x50, false), (0x00, [true, true, false, true]));
+        assert_eq!(sbc(0x01, 0x01, true), (0x00, [false, false, true, true]));
+    }
+
+    fn run(machine: &mut Machine<Flat>, steps: usize) {
+        for _ in 0..steps {
+            machine.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn loads_stores_and_flags() {
+        // LDA #$80; STA $40; LDX #$00; LDY $40
+        let mut machine =
+            machine(&[0xA9, 0x80, 0x85, 0x40, 0xA2, 0x00, 0xA4, 0x40]);
+        run(&mut machine, 2);
+        assert!(machine.sr().get_n());
+        assert_eq!(machine.bus().0[0x40], 0x80);
+        run(&mut machine, 1);
+        assert!(machine.sr().get_z() && !machine.sr().get_n());
+        run(&mut machine, 1);
+        assert_eq!(machine.ry(), 0x80);
+        assert!(machine.sr().get_n() && !machine.sr().get_z());
+        assert_eq!(machine.pc(), 0x1008);
+    }
+
+    #[test]
+    fn subroutine_call_and_return() {
+        // JSR $1010; NOP ... $1010: RTS
+        let mut machine = machine(&[0x20, 0x10, 0x10, 0xEA]);
+        machine.bus_mut().0[0x1010] = 0x60;
+        assert_eq!(machine.sp(), 0xFD);
+        run(&mut machine, 1);
+        assert_eq!(machine.pc(), 0x1010);
+        assert_eq!(machine.sp(), 0xFB);
+        assert_eq!(machine.bus().0[0x01FC..0x01FE], [0x02, 0x10]);
+        run(&mut machine, 1);
+        assert_eq!(machine.pc(), 0x1003);
+        assert_eq!(machine.sp(), 0xFD);
+    }
+
+    #[test]
+    fn indirect_jump_wraps_within_the_page() {
+        // JMP ($10FF) takes its high byte from $1000, not $1100.
+        let mut machine = machine(&[0x6C, 0xFF, 0x10]);
+        machine.bus_mut().0[0x10FF] = 0x34;
+        machine.bus_mut().0[0x1100] = 0x12;
+        run(&mut machine, 1);
+        assert_eq!(machine.pc(), 0x6C34);
+    }
+
+    #[test]
+    fn break_and_return_from_interrupt() {
+        // BRK; padding ... $1020: RTI
+        let mut machine = machine(&[0x00, 0xFF, 0xEA]);
+        machine.bus_mut().0[0x1FFE..0x2000].copy_from_slice(&[0x20, 0x10]);
+        machine.bus_mut().0[0x1020] = 0x40;
+        machine.set_sr(Status::from_bits(0x01));
+        run(&mut machine, 1);
+        assert_eq!(machine.pc(), 0x1020);
+        assert!(machine.sr().get_i());
+        assert_eq!(machine.bus().0[0x01FB..0x01FE], [0x31, 0x02, 0x10]);
+        run(&mut machine, 1);
+        assert_eq!(machine.pc(), 0x1002);
+        assert!(!machine.sr().get_i() && machine.sr().get_c());
+    }
+
+    #[test]
+    fn branch_loop() {
+        // LDX #$03; loop: DEX; BNE loop; NOP
+        let mut machine = machine(&[0xA2, 0x03, 0xCA, 0xD0, 0xFD, 0xEA]);
+        run(&mut machine, 1 + 3 * 2);
+        assert_eq!(machine.rx(), 0);
+        assert_eq!(machine.pc(), 0x1005);
+    }
+
+    #[test]
+    fn rotates_through_carry() {
+        // SEC; LDA #$80; ROL A; ROR A; CLC; ROR A
+        let mut machine = machine(&[0x38, 0xA9, 0x80, 0x2A, 0x6A, 0x18, 0x6A]);
+        run(&mut machine, 3);
+        assert_eq!(machine.ra(), 0x01);
+        assert!(machine.sr().get_c());
+        run(&mut machine, 1);
+        assert_eq!(machine.ra(), 0x80);
+        assert!(machine.sr().get_c() && machine.sr().get_n());
+        run(&mut machine, 2);
+        assert_eq!(machine.ra(), 0x40);
+        assert!(!machine.sr().get_c() && !machine.sr().get_n());
+    }
+
+    #[test]
+    fn compare_sets_carry_zero_and_negative() {
+        // LDA #$40; CMP #$41; CMP #$40; CMP #$C1
+        let mut machine =
+            machine(&[0xA9, 0x40, 0xC9, 0x41, 0xC9, 0x40, 0xC9, 0xC1]);
+        let flags = |machine: &Machine<Flat>| {
+            let sr = machine.sr();
+            [sr.get_n(), sr.get_z(), sr.get_c()]
+        };
+        run(&mut machine, 2);
+        assert_eq!(flags(&machine), [true, false, false]);
+        run(&mut machine, 1);
+        assert_eq!(flags(&machine), [false, true, true]);
+        run(&mut machine, 1);
+        assert_eq!(flags(&machine), [false, false, false]);
+        assert_eq!(machine.ra(), 0x40);
+    }
+
+    #[test]
+    fn cycles_with_page_crossings_and_branches() {
+        let mut machine = machine(&[
+            0xA2, 0x10, // LDX #$10
+            0xBD, 0xF0, 0x10, // LDA $10F0,X
+            0x9D, 0xF0, 0x10, // STA $10F0,X
+            0xA0, 0x10, // LDY #$10
+            0xB1, 0x80, // LDA ($80),Y
+            0xA0, 0x00, // LDY #$00
+            0xB1, 0x80, // LDA ($80),Y
+            0xFE, 0xF0, 0x10, // INC $10F0,X
+            0xF0, 0x00, // BEQ, not taken
+            0xD0, 0x00, // BNE, taken
+            0x4C, 0xF0, 0x10, // JMP $10F0
+        ]);
+        // $10F0: BNE $1112, taken into the next page
+        machine.bus_mut().0[0x10F0..0x10F2].copy_from_slice(&[0xD0, 0x20]);
+        machine.bus_mut().0[0x80..0x82].copy_from_slice(&[0xF0, 0x10]);
+        let mut cycles = Vec::new();
+        while machine.pc() != 0x1112 {
+            let before = machine.cycles();
+            run(&mut machine, 1);
+            cycles.push(machine.cycles() - before);
+        }
+        assert_eq!(cycles, [2, 5, 5, 2, 6, 2, 5, 7, 2, 3, 3, 4]);
+    }
+
+    #[test]
+    fn cycles_without_page_crossing() {
+        // LDX #$01; LDA $1080,X; LDA $80,X; PHA; PLA
+        let mut machine =
+            machine(&[0xA2, 0x01, 0xBD, 0x80, 0x10, 0xB5, 0x80, 0x48, 0x68]);
+        let mut cycles = Vec::new();
+        for _ in 0..5 {
+            let before = machine.cycles();
+            run(&mut machine, 1);
+            cycles.push(machine.cycles() - before);
+        }
+        assert_eq!(cycles, [2, 4, 4, 3, 4]);
+    }
+
+    #[test]
+    fn reset_reads_the_vector_through_the_mirror() {
+        let mut memory = vec![0; 0x10000];
+        memory[0x1FFC..0x1FFE].copy_from_slice(&[0x34, 0xF2]);
+        memory[0xFFFC..0xFFFE].copy_from_slice(&[0xCD, 0xAB]);
+        let mut machine = Machine::new(Flat(memory)).unwrap();
+        assert_eq!(machine.pc(), 0xF234);
+        assert_eq!(machine.sp(), 0xFD);
+        assert!(machine.sr().get_i());
+        assert_eq!(machine.cycles(), u64::from(Machine::<Flat>::RESET_CYCLES));
+
+        // A later reset keeps the registers but skips three more pushes.
+        machine.set_sp(0x80);
+        machine.set_sr(Status::zeroed());
+        machine.set_ra(0x42);
+        machine.reset().unwrap();
+        assert_eq!(machine.pc(), 0xF234);
+        assert_eq!(machine.sp(), 0x7D);
+        assert!(machine.sr().get_i());
+        assert_eq!(machine.ra(), 0x42);
+        assert_eq!(machine.cycles(), 2 * 7);
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Digest {
+    bytes: [u8; 16],
+}
+
+impl Digest {
+    pub fn new(bytes: [u8; 16]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn of(data: &[u8]) -> Self {
+        let mut state = INITIAL_STATE;
+        let length = (data.len() as u64).wrapping_mul(8);
+
+        let mut chunks = data.chunks_exact(64);
+        for block in &mut chunks {
+            compress(&mut state, block);
+        }
+
+        // The tail is padded with a single set bit, zeroes up to 56 bytes
+        // modulo 64, and the message length in bits.
+        let remainder = chunks.remainder();
+        let mut tail = [0; 128];
+        tail[..remainder.len()].copy_from_slice(remainder);
+        tail[remainder.len()] = 0x80;
+        let tail_len = if remainder.len() < 56 { 64 } else { 128 };
+        tail[tail_len - 8..tail_len].copy_from_slice(&length.to_le_bytes());
+        for block in tail[..tail_len].chunks_exact(64) {
+            compress(&mut state, block);
+        }
+
+        let mut bytes = [0; 16];
+        for (chunk, word) in bytes.chunks_exact_mut(4).zip(state.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        Self { bytes }
+    }
+
+    pub fn from_hex(text: &str) -> Option<Self> {
+        let text = text.trim().as_bytes();
+        if text.len() != 32 {
+            return None;
+        }
+        let mut bytes = [0; 16];
+        for (byte, pair) in bytes.iter_mut().zip(text.chunks_exact(2)) {
+            let pair = std::str::from_utf8(pair).ok()?;
+            *byte = u8::from_str_radix(pair, 16).ok()?;
+        }
+        Some(Self { bytes })
+    }
+
+    pub fn bytes(&self) -> [u8; 16] {
+        self.bytes
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.bytes.iter() {
+            write!(fmtr, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+const INITIAL_STATE: [u32; 4] =
+    [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476];
+
+const SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20,
+    5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4,
+    11, 16, 23, 4, 11, 16, 23, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6,
+    10, 15, 21,
+];
+
+// Integer parts of the sines of 1 to 64 (radians) scaled by 2^32.
+const CONSTANTS: [u32; 64] = [
+    0xD76AA478, 0xE8C7B756, 0x242070DB, 0xC1BDCEEE, 0xF57C0FAF, 0x4787C62A,
+    0xA8304613, 0xFD469501, 0x698098D8, 0x8B44F7AF, 0xFFFF5BB1, 0x895CD7BE,
+    0x6B901122, 0xFD987193, 0xA679438E, 0x49B40821, 0xF61E2562, 0xC040B340,
+    0x265E5A51, 0xE9B6C7AA, 0xD62F105D, 0x02441453, 0xD8A1E681, 0xE7D3FBC8,
+    0x21E1CDE6, 0xC33707D6, 0xF4D50D87, 0x455A14ED, 0xA9E3E905, 0xFCEFA3F8,
+    0x676F02D9, 0x8D2A4C8A, 0xFFFA3942, 0x8771F681, 0x6D9D6122, 0xFDE5380C,
+    0xA4BEEA44, 0x4BDECFA9, 0xF6BB4B60, 0xBEBFBC70, 0x289B7EC6, 0xEAA127FA,
+    0xD4EF3085, 0x04881D05, 0xD9D4D039, 0xE6DB99E5, 0x1FA27CF8, 0xC4AC5665,
+    0xF4292244, 0x432AFF97, 0xAB9423A7, 0xFC93A039, 0x655B59C3, 0x8F0CCC92,
+    0xFFEFF47D, 0x85845DD1, 0x6FA87E4F, 0xFE2CE6E0, 0xA3014314, 0x4E0811A1,
+    0xF7537E82, 0xBD3AF235, 0x2AD7D2BB, 0xEB86D391,
+];
+
+fn compress(state: &mut [u32; 4], block: &[u8]) {
+    let mut words = [0; 16];
+    for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+
+    let [mut a, mut b, mut c, mut d] = *state;
+    for round in 0..64 {
+        let (mix, index) = match round / 16 {
+            0 => ((b & c) | (!b & d), round),
+            1 => ((d & b) | (!d & c), (5 * round + 1) % 16),
+            2 => (b ^ c ^ d, (3 * round + 5) % 16),
+            _ => (c ^ (b | !d), (7 * round) % 16),
+        };
+        let rotated = a
+            .wrapping_add(mix)
+            .wrapping_add(CONSTANTS[round])
+            .wrapping_add(words[index])
+            .rotate_left(SHIFTS[round]);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(rotated);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d].iter()) {
+        *word = word.wrapping_add(*value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(data: &[u8]) -> String {
+        Digest::of(data).to_string()
+    }
+
+    // The test suite from RFC 1321, appendix A.5.
+    #[test]
+    fn rfc_1321_suite() {
+        let suite: [(&str, &str); 7] = [
+            ("", "d41d8cd98f00b204e9800998ecf8427e"),
+            ("a", "0cc175b9c0f1b6a831c399e269772661"),
+            ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+            ("message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            (
+                "abcdefghijklmnopqrstuvwxyz",
+                "c3fcd3d76192e4007dfb496cca67e13b",
+            ),
+            (
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "d174ab98d277d9f5a5611c2c9f419d9f",
+            ),
+            (
+                "1234567890123456789012345678901234567890\
+                 1234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
+        ];
+        for (message, digest) in suite {
+            assert_eq!(hex(message.as_bytes()), digest, "{:?}", message);
+        }
+    }
+
+    // Tails of 55 and 56 bytes need one and two padding blocks.
+    #[test]
+    fn padding_boundaries() {
+        assert_eq!(hex(&[b'a'; 55]), "ef1772b6dff9a122358552954ad0df65");
+        assert_eq!(hex(&[b'a'; 56]), "3b0c8ac703f828b04c6c197006d17218");
+        assert_eq!(hex(&[b'a'; 64]), "014842d480b571495a4a0363793f7367");
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let digest = Digest::of(b"abc");
+        assert_eq!(Digest::from_hex(&digest.to_string()), Some(digest));
+        assert_eq!(
+            Digest::from_hex("900150983CD24FB0D6963F7D28E17F72"),
+            Some(digest)
+        );
+        assert_eq!(Digest::from_hex("90015098"), None);
+        assert_eq!(Digest::from_hex(&"g".repeat(32)), None);
+    }
+}
@@ -1,4 +1,10 @@
-use crate::error::{BankError, ReadError, WriteError};
+use crate::{
+    bus::Bus,
+    cartridge::{Cartridge, Mapper},
+    error::{BankError, ReadError, WriteError},
+    riot::Riot,
+    tia::Tia,
+};
 use std::{iter, sync::Arc};
 
 #[derive(Debug, Clone)]
@@ -35,6 +41,12 @@ impl Ram {
     }
 }
 
+impl Default for Ram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RomBank {
     bytes: [u8; Self::SIZE],
@@ -59,6 +71,22 @@ impl RomBank {
         }
     }
 
+    // Images smaller than a bank (2K carts, mostly) leave the upper address
+    // lines unconnected, so they show up repeated across the whole window.
+    pub fn mirrored(slice: &[u8]) -> Option<Self> {
+        let mut bytes = [0; Self::SIZE];
+        if slice.is_empty()
+            || !slice.len().is_power_of_two()
+            || slice.len() > bytes.len()
+        {
+            return None;
+        }
+        for chunk in bytes.chunks_mut(slice.len()) {
+            chunk.copy_from_slice(slice);
+        }
+        Some(Self::new(bytes))
+    }
+
     pub fn read(&self, address: u16) -> Result<u8, ReadError> {
         address
             .checked_sub(Self::OFFSET)
@@ -69,13 +97,40 @@ impl RomBank {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SegmentSize {
+    Bank,
+    Half,
+    Quarter,
+    Eighth,
+}
+
+impl SegmentSize {
+    pub fn bytes(self) -> u16 {
+        RomBank::SIZE as u16 / self.segments() as u16
+    }
+
+    pub fn segments(self) -> usize {
+        match self {
+            SegmentSize::Bank => 1,
+            SegmentSize::Half => 2,
+            SegmentSize::Quarter => 4,
+            SegmentSize::Eighth => 8,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Rom {
     banks: Arc<[RomBank]>,
     selected: u8,
+    segment_size: SegmentSize,
+    segments: [u8; Rom::MAX_SEGMENTS],
 }
 
 impl Rom {
+    pub const MAX_SEGMENTS: usize = 8;
+
     pub fn new<I>(default_bank: RomBank, additional_banks: I) -> Self
     where
         I: IntoIterator<Item = RomBank>,
@@ -84,7 +139,14 @@ impl Rom {
             .chain(additional_banks)
             .collect::<Vec<_>>();
 
-        Self { banks: banks.into(), selected: 0 }
+        let mut this = Self {
+            banks: banks.into(),
+            selected: 0,
+            segment_size: SegmentSize::Bank,
+            segments: [0; Self::MAX_SEGMENTS],
+        };
+        this.map_selected_bank();
+        this
     }
 
     pub fn banks(&self) -> usize {
@@ -98,6 +160,7 @@ impl Rom {
     pub fn select_bank(&mut self, bank: u8) -> Result<(), BankError> {
         if usize::from(bank) < self.banks.len() {
             self.selected = bank;
+            self.map_selected_bank();
             Ok(())
         } else {
             Err(BankError { bank })
@@ -108,43 +171,258 @@ impl Rom {
         &self.banks[usize::from(self.selected)]
     }
 
+    pub fn segment_size(&self) -> SegmentSize {
+        self.segment_size
+    }
+
+    // Splits the 4K window into equally sized segments, each of which can
+    // then show any segment-sized chunk of the whole image. The segments
+    // start out mapped to the selected bank.
+    pub fn set_segment_size(&mut self, segment_size: SegmentSize) {
+        self.segment_size = segment_size;
+        self.map_selected_bank();
+    }
+
+    pub fn chunks(&self) -> usize {
+        self.banks.len() * self.segment_size.segments()
+    }
+
+    pub fn selected_chunk(&self, segment: usize) -> u8 {
+        self.segments[segment]
+    }
+
+    pub fn select_chunk(
+        &mut self,
+        segment: usize,
+        chunk: u8,
+    ) -> Result<(), BankError> {
+        if segment < self.segment_size.segments()
+            && usize::from(chunk) < self.chunks()
+        {
+            self.segments[segment] = chunk;
+            Ok(())
+        } else {
+            Err(BankError { bank: chunk })
+        }
+    }
+
     pub fn read(&self, address: u16) -> Result<u8, ReadError> {
-        self.selected_bank().read(address)
+        let offset = address
+            .checked_sub(RomBank::OFFSET)
+            .filter(|&offset| usize::from(offset) < RomBank::SIZE)
+            .ok_or(ReadError { address })?;
+        let size = usize::from(self.segment_size.bytes());
+        let offset = usize::from(offset);
+        let chunk = usize::from(self.segments[offset / size]);
+        let absolute = chunk * size + offset % size;
+        let bank = &self.banks[absolute / RomBank::SIZE];
+        bank.read(RomBank::OFFSET + (absolute % RomBank::SIZE) as u16)
+    }
+
+    fn map_selected_bank(&mut self) {
+        let segments = self.segment_size.segments();
+        let first = usize::from(self.selected) * segments;
+        for (segment, chunk) in
+            self.segments.iter_mut().take(segments).enumerate()
+        {
+            *chunk = (first + segment) as u8;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Region {
+    Tia,
+    Ram,
+    Riot,
+    Rom,
+}
+
+impl Region {
+    // Only A12, A9 and A7 take part in chip selection, every other line is
+    // either ignored or used by the selected chip itself.
+    pub fn of(address: u16) -> Self {
+        if address & 0x1000 != 0 {
+            Region::Rom
+        } else if address & 0x0080 == 0 {
+            Region::Tia
+        } else if address & 0x0200 == 0 {
+            Region::Ram
+        } else {
+            Region::Riot
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Memory {
-    ram: Ram,
-    rom: Rom,
+    riot: Riot,
+    tia: Tia,
+    cartridge: Cartridge,
 }
 
 impl Memory {
-    pub fn new(ram: Ram, rom: Rom) -> Self {
-        Self { ram, rom }
+    pub const ADDRESS_MASK: u16 = 0x1FFF;
+
+    pub fn new<C>(ram: Ram, cartridge: C) -> Self
+    where
+        C: Into<Cartridge>,
+    {
+        Self {
+            riot: Riot::new(ram),
+            tia: Tia::new(),
+            cartridge: cartridge.into(),
+        }
+    }
+
+    pub fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    pub fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    pub fn riot(&self) -> &Riot {
+        &self.riot
+    }
+
+    pub fn riot_mut(&mut self) -> &mut Riot {
+        &mut self.riot
+    }
+
+    pub fn tia(&self) -> &Tia {
+        &self.tia
+    }
+
+    pub fn tia_mut(&mut self) -> &mut Tia {
+        &mut self.tia
     }
 
     pub fn rom(&self) -> Rom {
-        self.rom.clone()
+        self.cartridge.rom().clone()
     }
 
     pub fn banks(&self) -> usize {
-        self.rom.banks()
+        self.cartridge.rom().banks()
     }
 
     pub fn selected_bank(&self) -> u8 {
-        self.rom.selected_index()
+        self.cartridge.rom().selected_index()
     }
 
     pub fn select_bank(&mut self, bank: u8) -> Result<(), BankError> {
-        self.rom.select_bank(bank)
+        self.cartridge.rom_mut().select_bank(bank)
     }
+}
 
-    pub fn read(&self, address: u16) -> Result<u8, ReadError> {
-        self.ram.read(address).or_else(|_| self.rom.read(address))
+impl Bus for Memory {
+    fn read(&mut self, address: u16) -> Result<u8, ReadError> {
+        let address = address & Self::ADDRESS_MASK;
+        let data = match Region::of(address) {
+            Region::Riot => self.riot.read(address),
+            Region::Rom => self.cartridge.read(address)?,
+            _ => self.peek(address)?,
+        };
+        self.cartridge.snoop_read(address, data);
+        Ok(data)
     }
 
-    pub fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError> {
-        self.ram.write(address, data)
+    fn write(&mut self, address: u16, data: u8) -> Result<(), WriteError> {
+        let address = address & Self::ADDRESS_MASK;
+        self.cartridge.snoop(address, data);
+        match Region::of(address) {
+            Region::Ram => {
+                self.riot.ram_mut().write(Ram::OFFSET | (address & 0x7F), data)
+            },
+            Region::Tia => {
+                self.tia.write(address, data);
+                Ok(())
+            },
+            Region::Riot => {
+                self.riot.write(address, data);
+                Ok(())
+            },
+            Region::Rom => self.cartridge.write(address, data),
+        }
+    }
+
+    fn peek(&self, address: u16) -> Result<u8, ReadError> {
+        let address = address & Self::ADDRESS_MASK;
+        match Region::of(address) {
+            Region::Ram => self.riot.ram().read(Ram::OFFSET | (address & 0x7F)),
+            Region::Rom => self.cartridge.peek(address),
+            Region::Tia => Ok(self.tia.read(address)),
+            Region::Riot => Ok(self.riot.peek(address)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory() -> Memory {
+        let image = (0..0x1000).map(|n| n as u8).collect::<Vec<_>>();
+        let (cartridge, _) = Cartridge::from_bytes(&image, None).unwrap();
+        Memory::new(Ram::new(), cartridge)
+    }
+
+    #[test]
+    fn regions() {
+        assert_eq!(Region::of(0x0000), Region::Tia);
+        assert_eq!(Region::of(0x0040), Region::Tia);
+        assert_eq!(Region::of(0x0080), Region::Ram);
+        assert_eq!(Region::of(0x0180), Region::Ram);
+        assert_eq!(Region::of(0x0280), Region::Riot);
+        assert_eq!(Region::of(0x0380), Region::Riot);
+        assert_eq!(Region::of(0x0300), Region::Tia);
+        assert_eq!(Region::of(0x1000), Region::Rom);
+    }
+
+    #[test]
+    fn only_thirteen_address_lines_are_decoded() {
+        let mut memory = memory();
+        memory.write(0xE080, 0x55).unwrap();
+        assert_eq!(memory.read(0x0080).unwrap(), 0x55);
+        assert_eq!(memory.read(0x2080).unwrap(), 0x55);
+        assert_eq!(memory.peek(0xFFFF).unwrap(), 0xFF);
+        assert_eq!(memory.peek(0x1FFF).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn ram_ignores_a8() {
+        let mut memory = memory();
+        memory.write(0x0080, 0x12).unwrap();
+        memory.write(0x01FF, 0x34).unwrap();
+        assert_eq!(memory.read(0x0180).unwrap(), 0x12);
+        assert_eq!(memory.read(0x00FF).unwrap(), 0x34);
+        // A9 selects the RIOT registers instead of its RAM.
+        assert_eq!(memory.read(0x0282).unwrap(), memory.riot().port_b_output());
+        assert_eq!(memory.read(0x0382).unwrap(), memory.riot().port_b_output());
+    }
+
+    #[test]
+    fn rom_is_mirrored_in_every_upper_window() {
+        let mut memory = memory();
+        for &base in &[0x1000, 0x3000, 0x5000, 0xD000, 0xF000] {
+            assert_eq!(memory.read(base).unwrap(), 0x00);
+            assert_eq!(memory.read(base + 0x123).unwrap(), 0x23);
+        }
+    }
+
+    #[test]
+    fn tia_is_mirrored_at_0x40() {
+        let mut memory = memory();
+        memory.write(0x0080, 0x77).unwrap();
+        memory.write(0x0042, 0x00).unwrap();
+        assert!(memory.tia().wsync());
+        assert_eq!(memory.read(0x0080).unwrap(), 0x77);
+
+        memory.tia_mut().set_input(4, true);
+        assert_eq!(memory.read(0x000C).unwrap() & 0x80, 0x80);
+        assert_eq!(memory.read(0x004C).unwrap() & 0x80, 0x80);
+        memory.tia_mut().set_input(4, false);
+        assert_eq!(memory.read(0x004C).unwrap() & 0x80, 0x00);
     }
 }
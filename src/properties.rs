@@ -0,0 +1,336 @@
+use crate::{
+    cartridge::Scheme,
+    console::{Controller, Difficulty, Settings},
+    error::PropertiesError,
+    md5::Digest,
+    tia::TvFormat,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::Path,
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Properties {
+    entries: BTreeMap<String, String>,
+}
+
+impl Properties {
+    pub const MD5: &'static str = "Cart.MD5";
+    pub const NAME: &'static str = "Cart.Name";
+    pub const TYPE: &'static str = "Cart.Type";
+    pub const LEFT_DIFFICULTY: &'static str = "Console.LeftDifficulty";
+    pub const RIGHT_DIFFICULTY: &'static str = "Console.RightDifficulty";
+    pub const TELEVISION_TYPE: &'static str = "Console.TelevisionType";
+    pub const LEFT_CONTROLLER: &'static str = "Controller.Left";
+    pub const RIGHT_CONTROLLER: &'static str = "Controller.Right";
+    pub const FORMAT: &'static str = "Display.Format";
+    pub const START_LINE: &'static str = "Display.YStart";
+    pub const HEIGHT: &'static str = "Display.Height";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    pub fn set<K, V>(&mut self, key: K, value: V) -> Option<String>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.entries.insert(key.into(), value.into())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    pub fn md5(&self) -> Option<Digest> {
+        self.get(Self::MD5).and_then(Digest::from_hex)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.get(Self::NAME)
+    }
+
+    // "AUTO" and types this crate has no mapper for leave detection to the
+    // usual heuristics.
+    pub fn scheme(&self) -> Option<Scheme> {
+        self.get(Self::TYPE).and_then(Scheme::from_name)
+    }
+
+    pub fn format(&self) -> Option<TvFormat> {
+        match self.get(Self::FORMAT)?.to_ascii_uppercase().as_str() {
+            "NTSC" | "NTSC50" => Some(TvFormat::Ntsc),
+            "PAL" | "PAL60" => Some(TvFormat::Pal),
+            "SECAM" | "SECAM60" => Some(TvFormat::Secam),
+            _ => None,
+        }
+    }
+
+    // Players and ports are numbered from the left; there are only two.
+    pub fn difficulty(&self, player: usize) -> Option<Difficulty> {
+        let key =
+            *[Self::LEFT_DIFFICULTY, Self::RIGHT_DIFFICULTY].get(player)?;
+        match self.get(key)?.trim() {
+            "A" | "a" => Some(Difficulty::A),
+            "B" | "b" => Some(Difficulty::B),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self) -> Option<bool> {
+        match self.get(Self::TELEVISION_TYPE)?.to_ascii_uppercase().as_str() {
+            "COLOR" => Some(true),
+            "BW" | "BLACKANDWHITE" => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn controller(&self, port: usize) -> Option<Controller> {
+        let key = *[Self::LEFT_CONTROLLER, Self::RIGHT_CONTROLLER].get(port)?;
+        let name = self.get(key)?.to_ascii_uppercase();
+        let controller = match name.as_str() {
+            "JOYSTICK" => Controller::Joystick,
+            "PADDLES" | "PADDLES_IAXIS" | "PADDLES_IAXDR" => {
+                Controller::Paddles
+            },
+            "DRIVING" => Controller::Driving,
+            "KEYBOARD" => Controller::Keyboard,
+            "BOOSTERGRIP" => Controller::BoosterGrip,
+            _ => Controller::Other,
+        };
+        Some(controller)
+    }
+
+    pub fn display_start(&self) -> Option<usize> {
+        self.get(Self::START_LINE)?.trim().parse().ok()
+    }
+
+    pub fn display_height(&self) -> Option<usize> {
+        self.get(Self::HEIGHT)?.trim().parse().ok().filter(|&height| height > 0)
+    }
+
+    // Anything the entry leaves out keeps the default console settings.
+    pub fn settings(&self) -> Settings {
+        let defaults = Settings::default();
+        Settings {
+            format: self.format().unwrap_or(defaults.format),
+            color: self.color().unwrap_or(defaults.color),
+            difficulties: [
+                self.difficulty(0).unwrap_or(defaults.difficulties[0]),
+                self.difficulty(1).unwrap_or(defaults.difficulties[1]),
+            ],
+            controllers: [
+                self.controller(0).unwrap_or(defaults.controllers[0]),
+                self.controller(1).unwrap_or(defaults.controllers[1]),
+            ],
+            display_start: self.display_start().or(defaults.display_start),
+            display_height: self.display_height().or(defaults.display_height),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Database {
+    entries: HashMap<Digest, Properties>,
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Entries are lines of quoted key and value pairs, each entry closed by
+    // a line holding a lone empty string. Quotes and backslashes inside the
+    // strings are escaped with a backslash.
+    pub fn parse(text: &str) -> Result<Self, PropertiesError> {
+        let mut database = Self::new();
+        let mut properties = Properties::new();
+        let mut start = 1;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let error = PropertiesError { line: line_number };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            let strings = split_strings(line).ok_or(error.clone())?;
+            match strings.as_slice() {
+                [end] if end.is_empty() => {
+                    if !properties.entries.is_empty() {
+                        let entry = std::mem::take(&mut properties);
+                        database
+                            .insert(entry)
+                            .ok_or(PropertiesError { line: start })?;
+                    }
+                    start = line_number + 1;
+                },
+                [key, value] => {
+                    properties.set(key.as_str(), value.as_str());
+                },
+                _ => return Err(error),
+            }
+        }
+
+        if !properties.entries.is_empty() {
+            database
+                .insert(properties)
+                .ok_or(PropertiesError { line: start })?;
+        }
+        Ok(database)
+    }
+
+    pub fn from_path<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let text = fs::read_to_string(path)?;
+        Ok(Self::parse(&text)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Returns the digest the entry was filed under, or nothing if it lacks
+    // a valid MD5 key. An entry for the same ROM replaces the previous one.
+    pub fn insert(&mut self, properties: Properties) -> Option<Digest> {
+        let digest = properties.md5()?;
+        self.entries.insert(digest, properties);
+        Some(digest)
+    }
+
+    pub fn get(&self, digest: &Digest) -> Option<&Properties> {
+        self.entries.get(digest)
+    }
+
+    pub fn lookup(&self, image: &[u8]) -> Option<&Properties> {
+        self.get(&Digest::of(image))
+    }
+}
+
+fn split_strings(line: &str) -> Option<Vec<String>> {
+    let mut strings = Vec::new();
+    let mut chars = line.chars();
+    loop {
+        match chars.by_ref().find(|ch| !ch.is_whitespace()) {
+            None => break Some(strings),
+            Some('"') => (),
+            Some(_) => break None,
+        }
+
+        let mut string = String::new();
+        loop {
+            match chars.next()? {
+                '"' => break,
+                '\\' => string.push(chars.next()?),
+                ch => string.push(ch),
+            }
+        }
+        strings.push(string);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MD5_A: &str = "0123456789abcdef0123456789abcdef";
+    const MD5_B: &str = "fedcba9876543210fedcba9876543210";
+
+    fn name<'a>(database: &'a Database, md5: &str) -> Option<&'a str> {
+        database.get(&Digest::from_hex(md5).unwrap())?.name()
+    }
+
+    fn error_line(text: &str) -> usize {
+        Database::parse(text).unwrap_err().line
+    }
+
+    #[test]
+    fn quoted_strings_unescape() {
+        let text = format!(
+            "\"Cart.MD5\" \"{}\"\n\"Cart.Name\" \"A \\\"B\\\" \\\\ C\"\n\"\"\n",
+            MD5_A
+        );
+        let database = Database::parse(&text).unwrap();
+        assert_eq!(name(&database, MD5_A), Some("A \"B\" \\ C"));
+    }
+
+    #[test]
+    fn entries_end_with_an_empty_string() {
+        let text = format!(
+            "; comment\n\n\"Cart.MD5\" \"{}\"\n\"Cart.Name\" \"One\"\n\"\"\n\
+             \"\"\n\"Cart.MD5\" \"{}\"\n\"Cart.Name\" \"Two\"\n",
+            MD5_A, MD5_B
+        );
+        let database = Database::parse(&text).unwrap();
+        assert_eq!(database.len(), 2);
+        assert_eq!(name(&database, MD5_A), Some("One"));
+        assert_eq!(name(&database, MD5_B), Some("Two"));
+    }
+
+    #[test]
+    fn later_entries_replace_earlier_ones() {
+        let text = format!(
+            "\"Cart.MD5\" \"{0}\"\n\"Cart.Name\" \"Old\"\n\"\"\n\
+             \"Cart.MD5\" \"{0}\"\n\"Cart.Name\" \"New\"\n\"\"\n",
+            MD5_A
+        );
+        let database = Database::parse(&text).unwrap();
+        assert_eq!(database.len(), 1);
+        assert_eq!(name(&database, MD5_A), Some("New"));
+    }
+
+    #[test]
+    fn bad_lines_report_their_line() {
+        let entry = format!("\"Cart.MD5\" \"{}\"\n", MD5_A);
+        assert_eq!(error_line(&format!("{}\"Cart.Name\" \"open\n", entry)), 2);
+        assert_eq!(error_line(&format!("\n{}\"A\" \"B\" \"C\"\n", entry)), 3);
+        assert_eq!(error_line(&format!("{}\"\"\nCart.Name\n", entry)), 3);
+        // An entry without a usable MD5 is reported where it starts.
+        let text = format!("{}\"\"\n\"Cart.Name\" \"X\"\n\"\"\n", entry);
+        assert_eq!(error_line(&text), 3);
+        assert_eq!(error_line("\"Cart.MD5\" \"xyz\"\n"), 1);
+    }
+
+    #[test]
+    fn settings_from_entries() {
+        let mut properties = Properties::new();
+        properties.set(Properties::FORMAT, "PAL60");
+        properties.set(Properties::TELEVISION_TYPE, "BW");
+        properties.set(Properties::RIGHT_DIFFICULTY, "A");
+        properties.set(Properties::LEFT_CONTROLLER, "PADDLES");
+        properties.set(Properties::HEIGHT, "0");
+        let settings = properties.settings();
+        assert_eq!(settings.format, TvFormat::Pal);
+        assert!(!settings.color);
+        assert_eq!(settings.difficulties, [Difficulty::B, Difficulty::A]);
+        assert_eq!(
+            settings.controllers,
+            [Controller::Paddles, Controller::Joystick]
+        );
+        assert_eq!(settings.display_height, None);
+    }
+
+    #[test]
+    fn players_past_the_right_are_absent() {
+        let mut properties = Properties::new();
+        properties.set(Properties::RIGHT_DIFFICULTY, "A");
+        properties.set(Properties::RIGHT_CONTROLLER, "DRIVING");
+        assert_eq!(properties.difficulty(1), Some(Difficulty::A));
+        assert_eq!(properties.difficulty(2), None);
+        assert_eq!(properties.controller(1), Some(Controller::Driving));
+        assert_eq!(properties.controller(2), None);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::memory::Ram;
+
+pub const SWCHA: u16 = 0x280;
+pub const SWACNT: u16 = 0x281;
+pub const SWCHB: u16 = 0x282;
+pub const SWBCNT: u16 = 0x283;
+pub const INTIM: u16 = 0x284;
+pub const TIMINT: u16 = 0x285;
+pub const TIM1T: u16 = 0x294;
+pub const TIM8T: u16 = 0x295;
+pub const TIM64T: u16 = 0x296;
+pub const T1024T: u16 = 0x297;
+
+#[derive(Debug, Clone)]
+pub struct Riot {
+    ram: Ram,
+    timer: u8,
+    interval: u16,
+    divider: u16,
+    expired: bool,
+    timer_flag: bool,
+    port_a: Port,
+    port_b: Port,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Port {
+    input: u8,
+    output: u8,
+    direction: u8,
+}
+
+impl Port {
+    fn read(&self) -> u8 {
+        (self.output & self.direction) | (self.input & !self.direction)
+    }
+}
+
+impl Default for Riot {
+    fn default() -> Self {
+        Self::new(Ram::new())
+    }
+}
+
+impl Riot {
+    pub fn new(ram: Ram) -> Self {
+        Self {
+            ram,
+            timer: 0,
+            interval: 1024,
+            divider: 0,
+            expired: false,
+            timer_flag: false,
+            port_a: Port { input: 0xFF, output: 0, direction: 0 },
+            port_b: Port { input: 0x0B, output: 0, direction: 0 },
+        }
+    }
+
+    pub fn ram(&self) -> &Ram {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut Ram {
+        &mut self.ram
+    }
+
+    pub fn timer(&self) -> u8 {
+        self.timer
+    }
+
+    pub fn set_port_a(&mut self, input: u8) {
+        self.port_a.input = input;
+    }
+
+    pub fn set_port_b(&mut self, input: u8) {
+        self.port_b.input = input;
+    }
+
+    pub fn port_a_input(&self) -> u8 {
+        self.port_a.input
+    }
+
+    pub fn port_b_input(&self) -> u8 {
+        self.port_b.input
+    }
+
+    pub fn port_a_output(&self) -> u8 {
+        self.port_a.read()
+    }
+
+    pub fn port_b_output(&self) -> u8 {
+        self.port_b.read()
+    }
+
+    // The timer decrements once right after being written and then once
+    // every interval. When it wraps past zero the flag is raised and it
+    // keeps counting down once per cycle until it is written again, which
+    // is what lets kernels tell how late they are.
+    pub fn clock(&mut self) {
+        if self.divider > 0 {
+            self.divider -= 1;
+            return;
+        }
+
+        let (timer, wrapped) = self.timer.overflowing_sub(1);
+        self.timer = timer;
+        if wrapped {
+            self.expired = true;
+            self.timer_flag = true;
+        }
+        self.divider = if self.expired { 0 } else { self.interval - 1 };
+    }
+
+    pub fn read(&mut self, address: u16) -> u8 {
+        let data = self.peek(address);
+        if address & 0x05 == 0x04 {
+            self.timer_flag = false;
+        }
+        data
+    }
+
+    pub fn peek(&self, address: u16) -> u8 {
+        if address & 0x04 == 0 {
+            match address & 0x03 {
+                0 => self.port_a.read(),
+                1 => self.port_a.direction,
+                2 => self.port_b.read(),
+                _ => self.port_b.direction,
+            }
+        } else if address & 0x01 == 0 {
+            self.timer
+        } else {
+            u8::from(self.timer_flag) << 7
+        }
+    }
+
+    pub fn write(&mut self, address: u16, data: u8) {
+        if address & 0x04 == 0 {
+            match address & 0x03 {
+                0 => self.port_a.output = data,
+                1 => self.port_a.direction = data,
+                2 => self.port_b.output = data,
+                _ => self.port_b.direction = data,
+            }
+        } else if address & 0x10 != 0 {
+            self.timer = data;
+            self.interval = match address & 0x03 {
+                0 => 1,
+                1 => 8,
+                2 => 64,
+                _ => 1024,
+            };
+            self.divider = 0;
+            self.expired = false;
+            self.timer_flag = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(riot: &mut Riot, cycles: u16) {
+        for _ in 0..cycles {
+            riot.clock();
+        }
+    }
+
+    #[test]
+    fn timer_intervals() {
+        for &(address, interval) in
+            &[(TIM1T, 1), (TIM8T, 8), (TIM64T, 64), (T1024T, 1024)]
+        {
+            let mut riot = Riot::default();
+            riot.write(address, 10);
+            assert_eq!(riot.timer(), 10);
+            // The first decrement follows the write immediately.
+            clock(&mut riot, 1);
+            assert_eq!(riot.timer(), 9);
+            clock(&mut riot, interval - 1);
+            assert_eq!(riot.timer(), 9);
+            clock(&mut riot, 1);
+            assert_eq!(riot.timer(), 8);
+            clock(&mut riot, interval * 8);
+            assert_eq!(riot.timer(), 0);
+            assert_eq!(riot.peek(TIMINT), 0x00);
+        }
+    }
+
+    #[test]
+    fn intim_reads_the_timer() {
+        let mut riot = Riot::default();
+        riot.write(TIM8T, 0x40);
+        clock(&mut riot, 1 + 8 * 3);
+        assert_eq!(riot.read(INTIM), 0x3C);
+        // INTIM is mirrored at every even timer address.
+        assert_eq!(riot.read(0x286), 0x3C);
+        assert_eq!(riot.read(0x29C), 0x3C);
+    }
+
+    #[test]
+    fn timer_counts_every_cycle_after_underflow() {
+        let mut riot = Riot::default();
+        riot.write(TIM64T, 1);
+        clock(&mut riot, 1);
+        assert_eq!(riot.timer(), 0);
+        clock(&mut riot, 63);
+        assert_eq!(riot.timer(), 0);
+        clock(&mut riot, 1);
+        assert_eq!(riot.timer(), 0xFF);
+        clock(&mut riot, 1);
+        assert_eq!(riot.timer(), 0xFE);
+        clock(&mut riot, 2);
+        assert_eq!(riot.timer(), 0xFC);
+
+        // Writing the timer restores the interval.
+        riot.write(TIM64T, 5);
+        clock(&mut riot, 2);
+        assert_eq!(riot.timer(), 4);
+    }
+
+    #[test]
+    fn timint_flag() {
+        let mut riot = Riot::default();
+        riot.write(TIM1T, 1);
+        clock(&mut riot, 1);
+        assert_eq!(riot.read(TIMINT), 0x00);
+        clock(&mut riot, 1);
+        assert_eq!(riot.read(TIMINT), 0x80);
+        // Reading TIMINT leaves the flag alone; reading INTIM clears it.
+        assert_eq!(riot.read(TIMINT), 0x80);
+        riot.read(INTIM);
+        assert_eq!(riot.read(TIMINT), 0x00);
+
+        clock(&mut riot, 0x100);
+        assert_eq!(riot.peek(TIMINT), 0x80);
+        riot.write(TIM1T, 1);
+        assert_eq!(riot.peek(TIMINT), 0x00);
+    }
+
+    #[test]
+    fn port_direction_masks_outputs() {
+        let mut riot = Riot::default();
+        riot.set_port_a(0xF0);
+        riot.write(SWCHA, 0x05);
+        // All pins are inputs until SWACNT makes some of them outputs.
+        assert_eq!(riot.read(SWCHA), 0xF0);
+        riot.write(SWACNT, 0x0F);
+        assert_eq!(riot.read(SWACNT), 0x0F);
+        assert_eq!(riot.read(SWCHA), 0xF5);
+        riot.write(SWCHA, 0xFA);
+        assert_eq!(riot.read(SWCHA), 0xFA);
+        riot.set_port_a(0x00);
+        assert_eq!(riot.read(SWCHA), 0x0A);
+        assert_eq!(riot.port_a_output(), 0x0A);
+    }
+}
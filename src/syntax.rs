@@ -0,0 +1,232 @@
+use crate::{
+    addrmode::Operand,
+    instruction::{Instruction, Mnemonic},
+};
+use std::fmt::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HexPrefix {
+    #[default]
+    Dollar,
+    ZeroX,
+    Suffix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Case {
+    #[default]
+    Upper,
+    Lower,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Syntax {
+    pub prefix: HexPrefix,
+    pub case: Case,
+}
+
+impl Syntax {
+    pub fn new(prefix: HexPrefix, case: Case) -> Self {
+        Self { prefix, case }
+    }
+
+    // Suffixed numbers must not start with a letter, or they would read as
+    // labels, hence the extra zero.
+    pub fn hex(self, value: u16, digits: usize) -> String {
+        let digits = match self.case {
+            Case::Upper => format!("{:01$X}", value, digits),
+            Case::Lower => format!("{:01$x}", value, digits),
+        };
+        match self.prefix {
+            HexPrefix::Dollar => format!("${}", digits),
+            HexPrefix::ZeroX => format!("0x{}", digits),
+            HexPrefix::Suffix if digits.starts_with(char::is_alphabetic) => {
+                format!("0{}{}", digits, self.letter('H'))
+            },
+            HexPrefix::Suffix => format!("{}{}", digits, self.letter('H')),
+        }
+    }
+
+    pub fn byte(self, value: u8) -> String {
+        self.hex(u16::from(value), 2)
+    }
+
+    pub fn word(self, value: u16) -> String {
+        self.hex(value, 4)
+    }
+
+    pub fn letter(self, letter: char) -> char {
+        match self.case {
+            Case::Upper => letter.to_ascii_uppercase(),
+            Case::Lower => letter.to_ascii_lowercase(),
+        }
+    }
+
+    pub fn text(self, text: &str) -> String {
+        text.chars().map(|letter| self.letter(letter)).collect()
+    }
+}
+
+pub trait Format {
+    fn format(&self, syntax: Syntax, fmtr: &mut fmt::Formatter) -> fmt::Result;
+
+    fn with_syntax(&self, syntax: Syntax) -> Formatted<'_, Self> {
+        Formatted { value: self, syntax }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Formatted<'value, T>
+where
+    T: ?Sized,
+{
+    value: &'value T,
+    syntax: Syntax,
+}
+
+impl<'value, T> fmt::Display for Formatted<'value, T>
+where
+    T: Format + ?Sized,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        self.value.format(self.syntax, fmtr)
+    }
+}
+
+impl Format for Mnemonic {
+    fn format(&self, syntax: Syntax, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "{}", syntax.text(self.name()))
+    }
+}
+
+// Branch offsets are shown relative to the branch itself, which is what
+// `*` stands for; the offset counts from the following instruction.
+impl Format for Operand {
+    fn format(&self, syntax: Syntax, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        let x = syntax.letter('X');
+        let y = syntax.letter('Y');
+        match self {
+            Operand::Acc(_) => fmtr.write_char(syntax.letter('A')),
+            Operand::Impl(_) => Ok(()),
+            Operand::Imm(data) => write!(fmtr, "#{}", syntax.byte(data.bits)),
+            Operand::Abs(data) => write!(fmtr, "{}", syntax.word(data.address)),
+            Operand::AbsX(data) => {
+                write!(fmtr, "{},{}", syntax.word(data.address), x)
+            },
+            Operand::AbsY(data) => {
+                write!(fmtr, "{},{}", syntax.word(data.address), y)
+            },
+            Operand::Ind(data) => {
+                write!(fmtr, "({})", syntax.word(data.address))
+            },
+            Operand::XInd(data) => {
+                write!(fmtr, "({},{})", syntax.byte(data.address), x)
+            },
+            Operand::IndY(data) => {
+                write!(fmtr, "({}),{}", syntax.byte(data.address), y)
+            },
+            Operand::Rel(data) => {
+                write!(fmtr, "*{:+}", i16::from(data.address) + 2)
+            },
+            Operand::Zpg(data) => write!(fmtr, "{}", syntax.byte(data.address)),
+            Operand::ZpgX(data) => {
+                write!(fmtr, "{},{}", syntax.byte(data.address), x)
+            },
+            Operand::ZpgY(data) => {
+                write!(fmtr, "{},{}", syntax.byte(data.address), y)
+            },
+        }
+    }
+}
+
+impl Format for Instruction {
+    fn format(&self, syntax: Syntax, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        self.mnemonic.format(syntax, fmtr)?;
+        match self.operand {
+            Operand::Impl(_) => Ok(()),
+            operand => {
+                fmtr.write_char(' ')?;
+                operand.format(syntax, fmtr)
+            },
+        }
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        self.format(Syntax::default(), fmtr)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        self.format(Syntax::default(), fmtr)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        self.format(Syntax::default(), fmtr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::addrmode::{
+        Absolute, Accumulator, Immediate, IndirectY, Relative, ZeropageX,
+    };
+
+    fn show(syntax: Syntax, mnemonic: Mnemonic, operand: Operand) -> String {
+        Instruction { mnemonic, operand }.with_syntax(syntax).to_string()
+    }
+
+    #[test]
+    fn hex_prefixes() {
+        let dollar = Syntax::default();
+        let zero_x = Syntax::new(HexPrefix::ZeroX, Case::Upper);
+        let suffix = Syntax::new(HexPrefix::Suffix, Case::Upper);
+        assert_eq!(dollar.byte(0x0A), "$0A");
+        assert_eq!(zero_x.byte(0x0A), "0x0A");
+        assert_eq!(zero_x.word(0xF000), "0xF000");
+        assert_eq!(suffix.byte(0x0A), "0AH");
+        assert_eq!(suffix.word(0x1234), "1234H");
+    }
+
+    #[test]
+    fn suffixed_numbers_start_with_a_digit() {
+        let suffix = Syntax::new(HexPrefix::Suffix, Case::Upper);
+        assert_eq!(suffix.byte(0xA0), "0A0H");
+        assert_eq!(suffix.word(0xF000), "0F000H");
+        assert_eq!(suffix.word(0x9FFF), "9FFFH");
+        let lower = Syntax::new(HexPrefix::Suffix, Case::Lower);
+        assert_eq!(lower.word(0xBEEF), "0beefh");
+    }
+
+    #[test]
+    fn lower_case() {
+        let lower = Syntax::new(HexPrefix::Dollar, Case::Lower);
+        let operand = Operand::IndY(IndirectY { address: 0xAB });
+        assert_eq!(show(lower, Mnemonic::Lda, operand), "lda ($ab),y");
+        let operand = Operand::ZpgX(ZeropageX { address: 0xFE });
+        assert_eq!(show(lower, Mnemonic::Sta, operand), "sta $fe,x");
+        let operand = Operand::Acc(Accumulator);
+        assert_eq!(show(lower, Mnemonic::Asl, operand), "asl a");
+        let operand = Operand::Abs(Absolute { address: 0xF00D });
+        let zero_x = Syntax::new(HexPrefix::ZeroX, Case::Lower);
+        assert_eq!(show(zero_x, Mnemonic::Jmp, operand), "jmp 0xf00d");
+    }
+
+    #[test]
+    fn relative_operands_count_from_the_branch() {
+        let syntax = Syntax::default();
+        let rel = |address| Operand::Rel(Relative { address });
+        assert_eq!(show(syntax, Mnemonic::Bne, rel(0)), "BNE *+2");
+        assert_eq!(show(syntax, Mnemonic::Bne, rel(-2)), "BNE *+0");
+        assert_eq!(show(syntax, Mnemonic::Beq, rel(-5)), "BEQ *-3");
+        assert_eq!(show(syntax, Mnemonic::Bcc, rel(127)), "BCC *+129");
+        assert_eq!(show(syntax, Mnemonic::Bcs, rel(-128)), "BCS *-126");
+        let operand = Operand::Imm(Immediate { bits: 0x10 });
+        assert_eq!(show(syntax, Mnemonic::Lda, operand), "LDA #$10");
+    }
+}
@@ -0,0 +1,268 @@
+pub mod audio;
+pub mod palette;
+pub mod video;
+
+pub use audio::Audio;
+pub use video::{Frame, Video};
+
+use palette::Rgb;
+
+pub const VSYNC: u8 = 0x00;
+pub const VBLANK: u8 = 0x01;
+pub const WSYNC: u8 = 0x02;
+pub const RSYNC: u8 = 0x03;
+pub const NUSIZ0: u8 = 0x04;
+pub const NUSIZ1: u8 = 0x05;
+pub const COLUP0: u8 = 0x06;
+pub const COLUP1: u8 = 0x07;
+pub const COLUPF: u8 = 0x08;
+pub const COLUBK: u8 = 0x09;
+pub const CTRLPF: u8 = 0x0A;
+pub const REFP0: u8 = 0x0B;
+pub const REFP1: u8 = 0x0C;
+pub const PF0: u8 = 0x0D;
+pub const PF1: u8 = 0x0E;
+pub const PF2: u8 = 0x0F;
+pub const RESP0: u8 = 0x10;
+pub const RESP1: u8 = 0x11;
+pub const RESM0: u8 = 0x12;
+pub const RESM1: u8 = 0x13;
+pub const RESBL: u8 = 0x14;
+pub const AUDC0: u8 = 0x15;
+pub const AUDC1: u8 = 0x16;
+pub const AUDF0: u8 = 0x17;
+pub const AUDF1: u8 = 0x18;
+pub const AUDV0: u8 = 0x19;
+pub const AUDV1: u8 = 0x1A;
+pub const GRP0: u8 = 0x1B;
+pub const GRP1: u8 = 0x1C;
+pub const ENAM0: u8 = 0x1D;
+pub const ENAM1: u8 = 0x1E;
+pub const ENABL: u8 = 0x1F;
+pub const HMP0: u8 = 0x20;
+pub const HMP1: u8 = 0x21;
+pub const HMM0: u8 = 0x22;
+pub const HMM1: u8 = 0x23;
+pub const HMBL: u8 = 0x24;
+pub const VDELP0: u8 = 0x25;
+pub const VDELP1: u8 = 0x26;
+pub const VDELBL: u8 = 0x27;
+pub const RESMP0: u8 = 0x28;
+pub const RESMP1: u8 = 0x29;
+pub const HMOVE: u8 = 0x2A;
+pub const HMCLR: u8 = 0x2B;
+pub const CXCLR: u8 = 0x2C;
+
+pub const CXM0P: u8 = 0x00;
+pub const CXM1P: u8 = 0x01;
+pub const CXP0FB: u8 = 0x02;
+pub const CXP1FB: u8 = 0x03;
+pub const CXM0FB: u8 = 0x04;
+pub const CXM1FB: u8 = 0x05;
+pub const CXBLPF: u8 = 0x06;
+pub const CXPPMM: u8 = 0x07;
+pub const INPT0: u8 = 0x08;
+pub const INPT1: u8 = 0x09;
+pub const INPT2: u8 = 0x0A;
+pub const INPT3: u8 = 0x0B;
+pub const INPT4: u8 = 0x0C;
+pub const INPT5: u8 = 0x0D;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum TvFormat {
+    #[default]
+    Ntsc,
+    Pal,
+    Secam,
+}
+
+impl TvFormat {
+    pub fn color_clock_rate(self) -> f64 {
+        match self {
+            TvFormat::Ntsc => 3_579_545.0,
+            TvFormat::Pal | TvFormat::Secam => 3_546_894.0,
+        }
+    }
+
+    pub fn rgb(self, color: u8) -> Rgb {
+        match self {
+            TvFormat::Ntsc => palette::ntsc(color),
+            TvFormat::Pal => palette::pal(color),
+            TvFormat::Secam => palette::secam(color),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tia {
+    video: Video,
+    audio: Audio,
+    wsync: bool,
+    inputs: [bool; 6],
+    latches: [bool; 2],
+    latched: bool,
+    dumped: bool,
+}
+
+impl Default for Tia {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tia {
+    pub const WRITE_MASK: u16 = 0x3F;
+    pub const READ_MASK: u16 = 0x0F;
+
+    pub fn new() -> Self {
+        Self {
+            video: Video::new(),
+            audio: Audio::default(),
+            wsync: false,
+            inputs: [true; 6],
+            latches: [true; 2],
+            latched: false,
+            dumped: false,
+        }
+    }
+
+    pub fn video(&self) -> &Video {
+        &self.video
+    }
+
+    pub fn audio(&self) -> &Audio {
+        &self.audio
+    }
+
+    pub fn audio_mut(&mut self) -> &mut Audio {
+        &mut self.audio
+    }
+
+    pub fn format(&self) -> TvFormat {
+        self.video.format()
+    }
+
+    // Audio is clocked twice per line, so its rate follows the colour clock.
+    pub fn set_format(&mut self, format: TvFormat) {
+        self.video.set_format(format);
+        let lines =
+            format.color_clock_rate() / f64::from(Video::CLOCKS_PER_LINE) * 2.0;
+        self.audio.set_clock_rate(lines);
+    }
+
+    pub fn read_samples(&mut self, buffer: &mut [i16]) -> usize {
+        self.audio.read_samples(buffer)
+    }
+
+    pub fn take_frame(&mut self) -> Option<Frame> {
+        self.video.take_frame()
+    }
+
+    pub fn wsync(&self) -> bool {
+        self.wsync
+    }
+
+    pub fn set_input(&mut self, input: usize, level: bool) {
+        self.inputs[input] = level;
+        if input >= 4 && self.latched && !level {
+            self.latches[input - 4] = false;
+        }
+    }
+
+    // The audio circuit is clocked twice per line, each clock split in two
+    // phases at fixed points of the horizontal counter.
+    pub fn clock(&mut self) -> bool {
+        match self.video.hpos() {
+            9 | 81 => self.audio.phase0(),
+            37 | 149 => self.audio.phase1(),
+            _ => (),
+        }
+
+        let line_ended = self.video.clock();
+        if line_ended {
+            self.wsync = false;
+        }
+        line_ended
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        let register = (address & Self::READ_MASK) as u8;
+        match register {
+            CXM0P..=CXPPMM => self.video.collision(register),
+            INPT0..=INPT3 => {
+                let level =
+                    !self.dumped && self.inputs[usize::from(register - INPT0)];
+                u8::from(level) << 7
+            },
+            INPT4 | INPT5 => {
+                let index = usize::from(register - INPT4);
+                let mut level = self.inputs[4 + index];
+                if self.latched {
+                    level &= self.latches[index];
+                }
+                u8::from(level) << 7
+            },
+            _ => 0,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, data: u8) {
+        let register = (address & Self::WRITE_MASK) as u8;
+        match register {
+            VSYNC => self.video.set_vsync(data),
+            VBLANK => {
+                self.video.set_vblank(data);
+                self.dumped = data & 0x80 != 0;
+                let latched = data & 0x40 != 0;
+                if latched && !self.latched {
+                    self.latches = [self.inputs[4], self.inputs[5]];
+                }
+                self.latched = latched;
+            },
+            WSYNC => self.wsync = true,
+            RSYNC => self.video.rsync(),
+            NUSIZ0 => self.video.set_nusiz(0, data),
+            NUSIZ1 => self.video.set_nusiz(1, data),
+            COLUP0 => self.video.set_colup0(data),
+            COLUP1 => self.video.set_colup1(data),
+            COLUPF => self.video.set_colupf(data),
+            COLUBK => self.video.set_colubk(data),
+            CTRLPF => self.video.set_ctrlpf(data),
+            REFP0 => self.video.set_reflect(0, data),
+            REFP1 => self.video.set_reflect(1, data),
+            PF0 => self.video.set_playfield(0, data),
+            PF1 => self.video.set_playfield(1, data),
+            PF2 => self.video.set_playfield(2, data),
+            RESP0 => self.video.reset_player(0),
+            RESP1 => self.video.reset_player(1),
+            RESM0 => self.video.reset_missile(0),
+            RESM1 => self.video.reset_missile(1),
+            RESBL => self.video.reset_ball(),
+            AUDC0 => self.audio.set_audc(0, data),
+            AUDC1 => self.audio.set_audc(1, data),
+            AUDF0 => self.audio.set_audf(0, data),
+            AUDF1 => self.audio.set_audf(1, data),
+            AUDV0 => self.audio.set_audv(0, data),
+            AUDV1 => self.audio.set_audv(1, data),
+            GRP0 => self.video.set_graphics(0, data),
+            GRP1 => self.video.set_graphics(1, data),
+            ENAM0 => self.video.enable_missile(0, data),
+            ENAM1 => self.video.enable_missile(1, data),
+            ENABL => self.video.enable_ball(data),
+            HMP0 => self.video.set_player_motion(0, data),
+            HMP1 => self.video.set_player_motion(1, data),
+            HMM0 => self.video.set_missile_motion(0, data),
+            HMM1 => self.video.set_missile_motion(1, data),
+            HMBL => self.video.set_ball_motion(data),
+            VDELP0 => self.video.set_player_delay(0, data),
+            VDELP1 => self.video.set_player_delay(1, data),
+            VDELBL => self.video.set_ball_delay(data),
+            RESMP0 => self.video.lock_missile(0, data),
+            RESMP1 => self.video.lock_missile(1, data),
+            HMOVE => self.video.hmove(),
+            HMCLR => self.video.hmclr(),
+            CXCLR => self.video.cxclr(),
+            _ => (),
+        }
+    }
+}
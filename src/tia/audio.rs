@@ -0,0 +1,306 @@
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Channel {
+    audc: u8,
+    audf: u8,
+    audv: u8,
+    divider: u8,
+    clock_enable: bool,
+    noise_counter: u8,
+    noise_bit: bool,
+    noise_feedback: bool,
+    pulse_counter: u8,
+    pulse_hold: bool,
+}
+
+impl Channel {
+    // First half of an audio clock: the frequency divider decides whether
+    // the counters shift, and the 5-bit noise counter computes its feedback.
+    fn phase0(&mut self) {
+        if self.clock_enable {
+            self.noise_bit = self.noise_counter & 0x01 != 0;
+
+            self.pulse_hold = match self.audc & 0x03 {
+                0x02 => self.noise_counter & 0x1E != 0x02,
+                0x03 => !self.noise_bit,
+                _ => false,
+            };
+
+            self.noise_feedback = match self.audc & 0x03 {
+                0x00 => {
+                    (self.pulse_counter ^ self.noise_counter) & 0x01 != 0
+                        || !(self.noise_counter != 0
+                            || self.pulse_counter != 0x0A)
+                        || self.audc & 0x0C == 0
+                },
+                _ => {
+                    (self.noise_counter & 0x04 != 0)
+                        ^ (self.noise_counter & 0x01 != 0)
+                        || self.noise_counter == 0
+                },
+            };
+        }
+
+        self.clock_enable = self.divider == self.audf;
+        if self.divider == self.audf || self.divider == 0x1F {
+            self.divider = 0;
+        } else {
+            self.divider += 1;
+        }
+    }
+
+    // Second half: the 4-bit pulse counter shifts according to the mode in
+    // the high bits of AUDC, and its lowest bit gates the volume.
+    fn phase1(&mut self) -> u8 {
+        if self.clock_enable {
+            let pulse_feedback = match self.audc >> 2 {
+                0x00 => {
+                    (self.pulse_counter & 0x02 != 0)
+                        ^ (self.pulse_counter & 0x01 != 0)
+                        && self.pulse_counter != 0x0A
+                        && self.audc & 0x03 != 0
+                },
+                0x01 => self.pulse_counter & 0x08 == 0,
+                0x02 => !self.noise_bit,
+                _ => {
+                    !(self.pulse_counter & 0x02 != 0
+                        || self.pulse_counter & 0x0E == 0)
+                },
+            };
+
+            self.noise_counter >>= 1;
+            if self.noise_feedback {
+                self.noise_counter |= 0x10;
+            }
+
+            if !self.pulse_hold {
+                self.pulse_counter = !(self.pulse_counter >> 1) & 0x07;
+                if pulse_feedback {
+                    self.pulse_counter |= 0x08;
+                }
+            }
+        }
+
+        (self.pulse_counter & 0x01) * self.audv
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Audio {
+    channels: [Channel; 2],
+    clock_rate: f64,
+    output_rate: f64,
+    window: f64,
+    filled: f64,
+    accumulated: f64,
+    samples: VecDeque<i16>,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_OUTPUT_RATE)
+    }
+}
+
+impl Audio {
+    pub const NTSC_CLOCK_RATE: f64 = 3_579_545.0 / 114.0;
+    pub const DEFAULT_OUTPUT_RATE: u32 = 44_100;
+    pub const MAX_VOLUME: u8 = 15;
+
+    pub fn new(output_rate: u32) -> Self {
+        let mut this = Self {
+            channels: [Channel::default(); 2],
+            clock_rate: Self::NTSC_CLOCK_RATE,
+            output_rate: 0.0,
+            window: 0.0,
+            filled: 0.0,
+            accumulated: 0.0,
+            samples: VecDeque::new(),
+        };
+        this.set_output_rate(output_rate);
+        this
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate as u32
+    }
+
+    pub fn set_output_rate(&mut self, output_rate: u32) {
+        self.output_rate = f64::from(output_rate.max(1));
+        self.update_window();
+    }
+
+    pub fn clock_rate(&self) -> f64 {
+        self.clock_rate
+    }
+
+    // A rate that is not positive would leave the resampler with an empty
+    // window, so it is clamped to one clock per second.
+    pub fn set_clock_rate(&mut self, clock_rate: f64) {
+        self.clock_rate = clock_rate.max(1.0);
+        self.update_window();
+    }
+
+    pub fn set_audc(&mut self, channel: usize, data: u8) {
+        self.channels[channel].audc = data & 0x0F;
+    }
+
+    pub fn set_audf(&mut self, channel: usize, data: u8) {
+        self.channels[channel].audf = data & 0x1F;
+    }
+
+    pub fn set_audv(&mut self, channel: usize, data: u8) {
+        self.channels[channel].audv = data & 0x0F;
+    }
+
+    pub fn phase0(&mut self) {
+        for channel in &mut self.channels {
+            channel.phase0();
+        }
+    }
+
+    pub fn phase1(&mut self) {
+        let level = self.channels[0].phase1() + self.channels[1].phase1();
+        let sample = f64::from(level) / f64::from(2 * Self::MAX_VOLUME);
+        self.resample(sample);
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn read_samples(&mut self, buffer: &mut [i16]) -> usize {
+        let count = buffer.len().min(self.samples.len());
+        for (output, sample) in
+            buffer.iter_mut().zip(self.samples.drain(..count))
+        {
+            *output = sample;
+        }
+        count
+    }
+
+    fn update_window(&mut self) {
+        self.window = self.clock_rate / self.output_rate;
+        self.filled = 0.0;
+        self.accumulated = 0.0;
+    }
+
+    // The TIA output holds each level until the next audio clock, so every
+    // output sample is the exact average of the levels over its interval.
+    // This box filter handles both up- and downsampling.
+    fn resample(&mut self, sample: f64) {
+        let mut remaining = 1.0;
+        while self.filled + remaining >= self.window {
+            let taken = self.window - self.filled;
+            self.accumulated += sample * taken;
+            remaining -= taken;
+            let average = self.accumulated / self.window;
+            self.push(average);
+            self.accumulated = 0.0;
+            self.filled = 0.0;
+        }
+        self.accumulated += sample * remaining;
+        self.filled += remaining;
+    }
+
+    fn push(&mut self, sample: f64) {
+        if self.samples.len() >= self.output_rate as usize {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((sample * f64::from(i16::MAX)) as i16);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_rate_stays_positive() {
+        for &rate in &[0.0, -1.0, f64::NAN] {
+            let mut audio = Audio::new(100);
+            audio.set_clock_rate(rate);
+            assert_eq!(audio.clock_rate(), 1.0);
+            audio.phase1();
+            assert!(audio.buffered() > 0);
+        }
+    }
+
+    // The channel output, one bit per audio clock, at the fastest AUDF.
+    fn output(audc: u8, clocks: usize) -> String {
+        let mut channel = Channel { audc, audv: 1, ..Channel::default() };
+        (0..clocks)
+            .map(|_| {
+                channel.phase0();
+                if channel.phase1() == 1 {
+                    '1'
+                } else {
+                    '0'
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pure_tone_divides_by_two() {
+        assert_eq!(output(0x04, 8), "10101010");
+        assert_eq!(output(0x0C, 12), "101110001110");
+    }
+
+    #[test]
+    fn poly4_sequence() {
+        let pattern = "101111000100110";
+        assert_eq!(output(0x01, 45), pattern.repeat(3));
+    }
+
+    #[test]
+    fn poly5_sequence() {
+        // The first clock shifts the all-zero counter into the sequence.
+        let pattern = "1111100011011101010000100101100";
+        let bits = output(0x07, 63);
+        assert_eq!(&bits[1..32], pattern);
+        assert_eq!(&bits[32..], pattern);
+    }
+
+    #[test]
+    fn div31_sequence() {
+        let pattern = format!("{}{}", "1".repeat(18), "0".repeat(13));
+        assert_eq!(output(0x06, 62), pattern.repeat(2));
+    }
+
+    #[test]
+    fn audf_divides_the_clock() {
+        // AUDF 2 holds each level for three clocks.
+        let mut channel =
+            Channel { audc: 0x04, audf: 2, audv: 1, ..Channel::default() };
+        let bits = (0..12)
+            .map(|_| {
+                channel.phase0();
+                channel.phase1()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(bits, [0, 0, 1, 1, 1, 0, 0, 0, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn samples_average_the_output() {
+        let mut audio = Audio::new(1000);
+        audio.set_clock_rate(2000.0);
+        audio.set_audc(0, 0x04);
+        audio.set_audv(0, 0x0F);
+        for _ in 0..100 {
+            audio.phase0();
+            audio.phase1();
+        }
+        assert_eq!(audio.buffered(), 50);
+
+        // Each sample covers one high and one low clock of a full-volume
+        // channel: a quarter of the range.
+        let mut buffer = [0; 64];
+        assert_eq!(audio.read_samples(&mut buffer[..10]), 10);
+        assert_eq!(audio.read_samples(&mut buffer), 40);
+        assert!(buffer[..40].iter().all(|&sample| sample == i16::MAX / 4));
+        assert_eq!(audio.buffered(), 0);
+    }
+}
@@ -0,0 +1,71 @@
+pub type Rgb = [u8; 3];
+
+pub fn ntsc(color: u8) -> Rgb {
+    rgb(NTSC[usize::from(color >> 1)])
+}
+
+// PAL alternates the phase of the colour burst, so consecutive hues walk
+// around the colour wheel in opposite directions from yellow, and the four
+// hues at the ends of the range carry no colour at all.
+pub fn pal(color: u8) -> Rgb {
+    rgb(PAL[usize::from(color >> 1)])
+}
+
+// SECAM only decodes the luminance bits, each as one of eight fixed colours.
+pub fn secam(color: u8) -> Rgb {
+    rgb(SECAM[usize::from((color >> 1) & 0x07)])
+}
+
+fn rgb(value: u32) -> Rgb {
+    let [_, red, green, blue] = value.to_be_bytes();
+    [red, green, blue]
+}
+
+const SECAM: [u32; 8] = [
+    0x000000, 0x2121FF, 0xF03C79, 0xFF50FF, 0x7FFF00, 0x7FFFFF, 0xFFFF3F,
+    0xFFFFFF,
+];
+
+const NTSC: [u32; 128] = [
+    0x000000, 0x4A4A4A, 0x6F6F6F, 0x8E8E8E, 0xAAAAAA, 0xC0C0C0, 0xD6D6D6,
+    0xECECEC, 0x484800, 0x69690F, 0x86861D, 0xA2A22A, 0xBBBB35, 0xD2D240,
+    0xE8E84A, 0xFCFC54, 0x7C2C00, 0x904811, 0xA26221, 0xB47A30, 0xC3903D,
+    0xD2A44A, 0xDFB755, 0xECC860, 0x901C00, 0xA33915, 0xB55328, 0xC66C3A,
+    0xD5824A, 0xE39759, 0xF0AA67, 0xFCBC74, 0x940000, 0xA71A1A, 0xB83232,
+    0xC84848, 0xD65C5C, 0xE46F6F, 0xF08080, 0xFC9090, 0x840064, 0x97197A,
+    0xA8308F, 0xB846A2, 0xC659B3, 0xD46CC3, 0xE07CD2, 0xEC8CE0, 0x500084,
+    0x68199A, 0x7D30AD, 0x9246C0, 0xA459D0, 0xB56CE0, 0xC57CEE, 0xD48CFC,
+    0x140090, 0x331AA3, 0x4E32B5, 0x6848C6, 0x7F5CD5, 0x956FE3, 0xA980F0,
+    0xBC90FC, 0x000094, 0x181AA7, 0x2D32B8, 0x4248C8, 0x545CD6, 0x656FE4,
+    0x7580F0, 0x8490FC, 0x001C88, 0x183B9D, 0x2D57B0, 0x4272C2, 0x548AD2,
+    0x65A0E1, 0x75B5EF, 0x84C8FC, 0x003064, 0x185080, 0x2D6D98, 0x4288B0,
+    0x54A0C5, 0x65B7D9, 0x75CCEB, 0x84E0FC, 0x004030, 0x18624E, 0x2D8169,
+    0x429E82, 0x54B899, 0x65D1AE, 0x75E7C2, 0x84FCD4, 0x004400, 0x1A661A,
+    0x328432, 0x48A048, 0x5CBA5C, 0x6FD26F, 0x80E880, 0x90FC90, 0x143C00,
+    0x355F18, 0x527E2D, 0x6E9C42, 0x87B754, 0x9ED065, 0xB4E775, 0xC8FC84,
+    0x303800, 0x505916, 0x6D762B, 0x88923E, 0xA0AB4F, 0xB7C25F, 0xCCD86E,
+    0xE0EC7C, 0x482C00, 0x694D14, 0x866A26, 0xA28638, 0xBB9F47, 0xD2B656,
+    0xE8CC63, 0xFCE070,
+];
+
+const PAL: [u32; 128] = [
+    0x000000, 0x2B2B2B, 0x525252, 0x767676, 0x979797, 0xB6B6B6, 0xD2D2D2,
+    0xECECEC, 0x000000, 0x2B2B2B, 0x525252, 0x767676, 0x979797, 0xB6B6B6,
+    0xD2D2D2, 0xECECEC, 0x414100, 0x5B5B00, 0x747400, 0x8E8E10, 0xA7A72A,
+    0xC1C143, 0xDADA5D, 0xF4F476, 0x205200, 0x3A6B00, 0x538500, 0x6D9E12,
+    0x86B82C, 0xA0D145, 0xB9EB5F, 0xD3FF78, 0x5F2D00, 0x784600, 0x926013,
+    0xAB792C, 0xC59346, 0xDEAC5F, 0xF8C679, 0xFFDF92, 0x045A00, 0x1E7300,
+    0x378D18, 0x51A632, 0x6AC04B, 0x84D965, 0x9DF37E, 0xB7FF98, 0x701A12,
+    0x8A342B, 0xA34D45, 0xBD675E, 0xD68078, 0xF09A91, 0xFFB3AB, 0xFFCDC4,
+    0x005819, 0x0E7133, 0x288B4C, 0x41A466, 0x5BBE7F, 0x74D799, 0x8EF1B2,
+    0xA7FFCC, 0x710E4D, 0x8B2866, 0xA44180, 0xBE5B99, 0xD774B3, 0xF18ECC,
+    0xFFA7E6, 0xFFC1FF, 0x004C54, 0x0F656E, 0x297F87, 0x4298A1, 0x5CB2BA,
+    0x75CBD4, 0x8FE5ED, 0xA8FEFF, 0x620C81, 0x7B269A, 0x953FB4, 0xAE59CD,
+    0xC872E7, 0xE18CFF, 0xFBA5FF, 0xFFBFFF, 0x073986, 0x2153A0, 0x3A6CB9,
+    0x5486D3, 0x6D9FEC, 0x87B9FF, 0xA0D2FF, 0xBAECFF, 0x4614A0, 0x5F2EBA,
+    0x7947D3, 0x9261ED, 0xAC7AFF, 0xC594FF, 0xDFADFF, 0xF8C7FF, 0x2525A2,
+    0x3E3EBC, 0x5858D5, 0x7171EF, 0x8B8BFF, 0xA4A4FF, 0xBEBEFF, 0xD7D7FF,
+    0x000000, 0x2B2B2B, 0x525252, 0x767676, 0x979797, 0xB6B6B6, 0xD2D2D2,
+    0xECECEC, 0x000000, 0x2B2B2B, 0x525252, 0x767676, 0x979797, 0xB6B6B6,
+    0xD2D2D2, 0xECECEC,
+];
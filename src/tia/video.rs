@@ -0,0 +1,700 @@
+use crate::tia::{palette::Rgb, TvFormat};
+
+const COPIES: [&[u16]; 8] = [
+    &[0],
+    &[0, 16],
+    &[0, 32],
+    &[0, 16, 32],
+    &[0, 64],
+    &[0],
+    &[0, 32, 64],
+    &[0],
+];
+
+const P0: u8 = 1 << 0;
+const P1: u8 = 1 << 1;
+const M0: u8 = 1 << 2;
+const M1: u8 = 1 << 3;
+const BL: u8 = 1 << 4;
+const PF: u8 = 1 << 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Frame {
+    pub const WIDTH: usize = 160;
+
+    pub fn width(&self) -> usize {
+        Self::WIDTH
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Rgb] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Rgb> {
+        if x < Self::WIDTH {
+            self.pixels.get(y * Self::WIDTH + x).copied()
+        } else {
+            None
+        }
+    }
+
+    pub fn into_pixels(self) -> Vec<Rgb> {
+        self.pixels
+    }
+
+    pub fn crop(mut self, start: usize, height: usize) -> Self {
+        let start = start.min(self.height);
+        let height = height.min(self.height - start);
+        self.pixels.truncate((start + height) * Self::WIDTH);
+        self.pixels.drain(..start * Self::WIDTH);
+        Self { height, pixels: self.pixels }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Player {
+    position: u16,
+    motion: u8,
+    nusiz: u8,
+    reflect: bool,
+    graphics: u8,
+    old_graphics: u8,
+    delay: bool,
+}
+
+impl Player {
+    fn scale(&self) -> u16 {
+        match self.nusiz & 0x7 {
+            5 => 2,
+            7 => 4,
+            _ => 1,
+        }
+    }
+
+    fn pixel(&self, x: u16) -> bool {
+        let graphics =
+            if self.delay { self.old_graphics } else { self.graphics };
+        let scale = self.scale();
+        let distance = (x + Video::WIDTH - self.position) % Video::WIDTH;
+
+        COPIES[usize::from(self.nusiz & 0x7)].iter().any(|&offset| {
+            let bit = distance.wrapping_sub(offset) / scale;
+            bit < 8 && {
+                let mask = if self.reflect { 1 << bit } else { 0x80 >> bit };
+                graphics & mask != 0
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Missile {
+    position: u16,
+    motion: u8,
+    enabled: bool,
+    locked: bool,
+}
+
+impl Missile {
+    fn pixel(&self, x: u16, nusiz: u8) -> bool {
+        if !self.enabled || self.locked {
+            return false;
+        }
+        let width = 1 << ((nusiz >> 4) & 0x3);
+        let distance = (x + Video::WIDTH - self.position) % Video::WIDTH;
+        let copies = match nusiz & 0x7 {
+            5 | 7 => COPIES[0],
+            mode => COPIES[usize::from(mode)],
+        };
+        copies.iter().any(|&offset| distance.wrapping_sub(offset) < width)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Ball {
+    position: u16,
+    motion: u8,
+    enabled: bool,
+    old_enabled: bool,
+    delay: bool,
+}
+
+impl Ball {
+    fn pixel(&self, x: u16, ctrlpf: u8) -> bool {
+        let enabled = if self.delay { self.old_enabled } else { self.enabled };
+        let width = 1 << ((ctrlpf >> 4) & 0x3);
+        let distance = (x + Video::WIDTH - self.position) % Video::WIDTH;
+        enabled && distance < width
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Video {
+    hpos: u16,
+    line: usize,
+    vsync: bool,
+    vblank: bool,
+    hmove_blank: bool,
+    colup0: u8,
+    colup1: u8,
+    colupf: u8,
+    colubk: u8,
+    ctrlpf: u8,
+    playfield: [u8; 3],
+    players: [Player; 2],
+    missiles: [Missile; 2],
+    ball: Ball,
+    collisions: [u8; 8],
+    colors: Vec<u8>,
+    frame: Option<Frame>,
+    frames: u64,
+    format: TvFormat,
+}
+
+impl Default for Video {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Video {
+    pub const WIDTH: u16 = 160;
+    pub const CLOCKS_PER_LINE: u16 = 228;
+    pub const HBLANK_CLOCKS: u16 = 68;
+    pub const MAX_LINES: usize = 320;
+
+    pub fn new() -> Self {
+        Self {
+            hpos: 0,
+            line: 0,
+            vsync: false,
+            vblank: false,
+            hmove_blank: false,
+            colup0: 0,
+            colup1: 0,
+            colupf: 0,
+            colubk: 0,
+            ctrlpf: 0,
+            playfield: [0; 3],
+            players: [Player::default(); 2],
+            missiles: [Missile::default(); 2],
+            ball: Ball::default(),
+            collisions: [0; 8],
+            colors: Vec::new(),
+            frame: None,
+            frames: 0,
+            format: TvFormat::default(),
+        }
+    }
+
+    pub fn hpos(&self) -> u16 {
+        self.hpos
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn format(&self) -> TvFormat {
+        self.format
+    }
+
+    pub fn set_format(&mut self, format: TvFormat) {
+        self.format = format;
+    }
+
+    pub fn vblank(&self) -> bool {
+        self.vblank
+    }
+
+    pub fn take_frame(&mut self) -> Option<Frame> {
+        self.frame.take()
+    }
+
+    pub fn collision(&self, register: u8) -> u8 {
+        self.collisions[usize::from(register & 0x7)]
+    }
+
+    pub fn clock(&mut self) -> bool {
+        if self.hpos >= Self::HBLANK_CLOCKS {
+            self.draw(self.hpos - Self::HBLANK_CLOCKS);
+        }
+
+        self.hpos += 1;
+        if self.hpos < Self::CLOCKS_PER_LINE {
+            return false;
+        }
+
+        self.hpos = 0;
+        self.hmove_blank = false;
+        self.line += 1;
+        if self.line >= Self::MAX_LINES {
+            self.finish_frame();
+        }
+        true
+    }
+
+    pub fn set_vsync(&mut self, data: u8) {
+        let vsync = data & 0x02 != 0;
+        if vsync && !self.vsync {
+            self.finish_frame();
+        }
+        self.vsync = vsync;
+    }
+
+    pub fn rsync(&mut self) {
+        self.hpos = Self::CLOCKS_PER_LINE - 1;
+    }
+
+    pub fn set_vblank(&mut self, data: u8) {
+        self.vblank = data & 0x02 != 0;
+    }
+
+    pub fn set_nusiz(&mut self, player: usize, data: u8) {
+        self.players[player].nusiz = data;
+    }
+
+    pub fn set_colup0(&mut self, data: u8) {
+        self.colup0 = data;
+    }
+
+    pub fn set_colup1(&mut self, data: u8) {
+        self.colup1 = data;
+    }
+
+    pub fn set_colupf(&mut self, data: u8) {
+        self.colupf = data;
+    }
+
+    pub fn set_colubk(&mut self, data: u8) {
+        self.colubk = data;
+    }
+
+    pub fn set_ctrlpf(&mut self, data: u8) {
+        self.ctrlpf = data;
+    }
+
+    pub fn set_reflect(&mut self, player: usize, data: u8) {
+        self.players[player].reflect = data & 0x08 != 0;
+    }
+
+    pub fn set_playfield(&mut self, register: usize, data: u8) {
+        self.playfield[register] = data;
+    }
+
+    // Objects reset during horizontal blank appear at the left edge; during
+    // the visible part of the line they show up a few pixels to the right
+    // of the beam, because the start signal takes some clocks to decode.
+    pub fn reset_player(&mut self, player: usize) {
+        self.players[player].position = self.reset_position(3, 5);
+    }
+
+    pub fn reset_missile(&mut self, missile: usize) {
+        self.missiles[missile].position = self.reset_position(2, 4);
+    }
+
+    pub fn reset_ball(&mut self) {
+        self.ball.position = self.reset_position(2, 4);
+    }
+
+    // Writing one player's graphics also latches the other player's (and,
+    // for GRP1, the ball's) delayed copy; vertical delay picks the latch.
+    pub fn set_graphics(&mut self, player: usize, data: u8) {
+        self.players[player].graphics = data;
+        let other = &mut self.players[1 - player];
+        other.old_graphics = other.graphics;
+        if player == 1 {
+            self.ball.old_enabled = self.ball.enabled;
+        }
+    }
+
+    pub fn enable_missile(&mut self, missile: usize, data: u8) {
+        self.missiles[missile].enabled = data & 0x02 != 0;
+    }
+
+    pub fn enable_ball(&mut self, data: u8) {
+        self.ball.enabled = data & 0x02 != 0;
+    }
+
+    pub fn set_player_motion(&mut self, player: usize, data: u8) {
+        self.players[player].motion = data;
+    }
+
+    pub fn set_missile_motion(&mut self, missile: usize, data: u8) {
+        self.missiles[missile].motion = data;
+    }
+
+    pub fn set_ball_motion(&mut self, data: u8) {
+        self.ball.motion = data;
+    }
+
+    pub fn set_player_delay(&mut self, player: usize, data: u8) {
+        self.players[player].delay = data & 0x01 != 0;
+    }
+
+    pub fn set_ball_delay(&mut self, data: u8) {
+        self.ball.delay = data & 0x01 != 0;
+    }
+
+    // A locked missile is hidden; it is centred on its player's current
+    // position only when the lock is released.
+    pub fn lock_missile(&mut self, missile: usize, data: u8) {
+        let locked = data & 0x02 != 0;
+        if self.missiles[missile].locked && !locked {
+            let player = &self.players[missile];
+            let center = match player.nusiz & 0x7 {
+                5 => 6,
+                7 => 10,
+                _ => 3,
+            };
+            self.missiles[missile].position =
+                (player.position + center) % Self::WIDTH;
+        }
+        self.missiles[missile].locked = locked;
+    }
+
+    pub fn hmove(&mut self) {
+        if self.hpos < Self::HBLANK_CLOCKS {
+            self.hmove_blank = true;
+        }
+        for player in &mut self.players {
+            player.position = Self::shift(player.position, player.motion);
+        }
+        for missile in &mut self.missiles {
+            missile.position = Self::shift(missile.position, missile.motion);
+        }
+        self.ball.position = Self::shift(self.ball.position, self.ball.motion);
+    }
+
+    pub fn hmclr(&mut self) {
+        for player in &mut self.players {
+            player.motion = 0;
+        }
+        for missile in &mut self.missiles {
+            missile.motion = 0;
+        }
+        self.ball.motion = 0;
+    }
+
+    pub fn cxclr(&mut self) {
+        self.collisions = [0; 8];
+    }
+
+    fn reset_position(&self, blank: u16, delay: u16) -> u16 {
+        if self.hpos < Self::HBLANK_CLOCKS {
+            blank
+        } else {
+            (self.hpos - Self::HBLANK_CLOCKS + delay) % Self::WIDTH
+        }
+    }
+
+    // Motion registers hold a signed nibble in their high bits, positive
+    // values moving the object to the left.
+    fn shift(position: u16, motion: u8) -> u16 {
+        let motion = i16::from(motion as i8 >> 4);
+        let width = Self::WIDTH as i16;
+        ((position as i16 - motion).rem_euclid(width)) as u16
+    }
+
+    fn playfield_pixel(&self, x: u16) -> bool {
+        let mut column = x / 4;
+        if column >= 20 {
+            column -= 20;
+            if self.ctrlpf & 0x01 != 0 {
+                column = 19 - column;
+            }
+        }
+        match column {
+            0..=3 => self.playfield[0] & (0x10 << column) != 0,
+            4..=11 => self.playfield[1] & (0x80 >> (column - 4)) != 0,
+            _ => self.playfield[2] & (0x01 << (column - 12)) != 0,
+        }
+    }
+
+    fn draw(&mut self, x: u16) {
+        let mut objects = 0;
+        if self.players[0].pixel(x) {
+            objects |= P0;
+        }
+        if self.players[1].pixel(x) {
+            objects |= P1;
+        }
+        if self.missiles[0].pixel(x, self.players[0].nusiz) {
+            objects |= M0;
+        }
+        if self.missiles[1].pixel(x, self.players[1].nusiz) {
+            objects |= M1;
+        }
+        if self.ball.pixel(x, self.ctrlpf) {
+            objects |= BL;
+        }
+        if self.playfield_pixel(x) {
+            objects |= PF;
+        }
+
+        if objects.count_ones() > 1 {
+            self.collide(objects);
+        }
+
+        let color = if self.vblank || (self.hmove_blank && x < 8) {
+            0
+        } else {
+            self.color(x, objects)
+        };
+
+        let index = self.line * usize::from(Self::WIDTH) + usize::from(x);
+        if self.colors.len() <= index {
+            self.colors.resize(index + 1, 0);
+        }
+        self.colors[index] = color;
+    }
+
+    fn color(&self, x: u16, objects: u8) -> u8 {
+        let score = self.ctrlpf & 0x02 != 0;
+        let priority = self.ctrlpf & 0x04 != 0;
+
+        let playfield_color = if score && !priority {
+            if x < Self::WIDTH / 2 {
+                self.colup0
+            } else {
+                self.colup1
+            }
+        } else {
+            self.colupf
+        };
+
+        if priority && objects & PF != 0 {
+            playfield_color
+        } else if priority && objects & BL != 0 {
+            self.colupf
+        } else if objects & (P0 | M0) != 0 {
+            self.colup0
+        } else if objects & (P1 | M1) != 0 {
+            self.colup1
+        } else if objects & PF != 0 {
+            playfield_color
+        } else if objects & BL != 0 {
+            self.colupf
+        } else {
+            self.colubk
+        }
+    }
+
+    fn collide(&mut self, objects: u8) {
+        let pairs = [
+            (0, 0x80, M0 | P1),
+            (0, 0x40, M0 | P0),
+            (1, 0x80, M1 | P0),
+            (1, 0x40, M1 | P1),
+            (2, 0x80, P0 | PF),
+            (2, 0x40, P0 | BL),
+            (3, 0x80, P1 | PF),
+            (3, 0x40, P1 | BL),
+            (4, 0x80, M0 | PF),
+            (4, 0x40, M0 | BL),
+            (5, 0x80, M1 | PF),
+            (5, 0x40, M1 | BL),
+            (6, 0x80, BL | PF),
+            (7, 0x80, P0 | P1),
+            (7, 0x40, M0 | M1),
+        ];
+        for &(register, bit, pair) in &pairs {
+            if objects & pair == pair {
+                self.collisions[register] |= bit;
+            }
+        }
+    }
+
+    fn finish_frame(&mut self) {
+        let width = usize::from(Self::WIDTH);
+        let height = self.line.min(Self::MAX_LINES);
+        self.colors.resize(height * width, 0);
+        let format = self.format;
+        let pixels =
+            self.colors.drain(..).map(|color| format.rgb(color)).collect();
+        self.frame = Some(Frame { height, pixels });
+        self.frames += 1;
+        self.line = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = Video::WIDTH as usize;
+
+    // Clocks to the end of the current line and returns its colours.
+    fn render(video: &mut Video) -> Vec<u8> {
+        while !video.clock() {}
+        let start = (video.line - 1) * WIDTH;
+        video.colors[start..start + WIDTH].to_vec()
+    }
+
+    fn lit(line: &[u8], color: u8) -> Vec<usize> {
+        (0..WIDTH).filter(|&x| line[x] == color).collect()
+    }
+
+    fn video() -> Video {
+        let mut video = Video::new();
+        video.set_colup0(0x40);
+        video.set_colup1(0x80);
+        video.set_colupf(0x1E);
+        video.set_colubk(0x02);
+        video
+    }
+
+    #[test]
+    fn playfield_repeats_or_reflects() {
+        let mut video = video();
+        video.set_playfield(0, 0x10);
+        let line = render(&mut video);
+        assert_eq!(lit(&line, 0x1E), vec![0, 1, 2, 3, 80, 81, 82, 83]);
+
+        video.set_ctrlpf(0x01);
+        let line = render(&mut video);
+        assert_eq!(lit(&line, 0x1E), vec![0, 1, 2, 3, 156, 157, 158, 159]);
+    }
+
+    #[test]
+    fn score_mode_colours_each_half() {
+        let mut video = video();
+        video.set_ctrlpf(0x02);
+        video.set_playfield(0, 0x10);
+        let line = render(&mut video);
+        assert_eq!(lit(&line, 0x40), vec![0, 1, 2, 3]);
+        assert_eq!(lit(&line, 0x80), vec![80, 81, 82, 83]);
+    }
+
+    #[test]
+    fn priority_puts_playfield_over_players() {
+        let mut video = video();
+        video.set_playfield(0, 0x10);
+        video.reset_player(0);
+        video.set_graphics(0, 0xFF);
+        let line = render(&mut video);
+        assert_eq!(
+            &line[..12],
+            &[
+                0x1E, 0x1E, 0x1E, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40,
+                0x40, 0x02
+            ]
+        );
+
+        video.set_ctrlpf(0x04);
+        let line = render(&mut video);
+        assert_eq!(
+            &line[..12],
+            &[
+                0x1E, 0x1E, 0x1E, 0x1E, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40,
+                0x40, 0x02
+            ]
+        );
+    }
+
+    #[test]
+    fn nusiz_copies_and_stretches_players() {
+        let mut video = video();
+        video.reset_player(0);
+        video.set_graphics(0, 0x80);
+        video.set_nusiz(0, 0x03);
+        assert_eq!(lit(&render(&mut video), 0x40), vec![3, 19, 35]);
+        video.set_nusiz(0, 0x06);
+        assert_eq!(lit(&render(&mut video), 0x40), vec![3, 35, 67]);
+        video.set_nusiz(0, 0x05);
+        assert_eq!(lit(&render(&mut video), 0x40), vec![3, 4]);
+        video.set_nusiz(0, 0x07);
+        assert_eq!(lit(&render(&mut video), 0x40), vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn reflect_mirrors_player_graphics() {
+        let mut video = video();
+        video.reset_player(1);
+        video.set_graphics(1, 0xC0);
+        assert_eq!(lit(&render(&mut video), 0x80), vec![3, 4]);
+        video.set_reflect(1, 0x08);
+        assert_eq!(lit(&render(&mut video), 0x80), vec![9, 10]);
+    }
+
+    #[test]
+    fn missiles_follow_nusiz_width_and_copies() {
+        let mut video = video();
+        video.reset_missile(0);
+        video.enable_missile(0, 0x02);
+        video.set_nusiz(0, 0x20);
+        assert_eq!(lit(&render(&mut video), 0x40), vec![2, 3, 4, 5]);
+        video.set_nusiz(0, 0x01);
+        assert_eq!(lit(&render(&mut video), 0x40), vec![2, 18]);
+        // Stretched players do not copy their missile.
+        video.set_nusiz(0, 0x05);
+        assert_eq!(lit(&render(&mut video), 0x40), vec![2]);
+    }
+
+    #[test]
+    fn locked_missile_is_centred_on_release() {
+        let mut video = video();
+        video.reset_player(1);
+        video.reset_missile(1);
+        video.enable_missile(1, 0x02);
+        video.lock_missile(1, 0x02);
+        assert!(lit(&render(&mut video), 0x80).is_empty());
+
+        // The player moves while the missile is locked; release centres
+        // the missile on where the player is now.
+        while video.hpos() < Video::HBLANK_CLOCKS + 50 {
+            video.clock();
+        }
+        video.reset_player(1);
+        video.lock_missile(1, 0x00);
+        render(&mut video);
+        assert_eq!(lit(&render(&mut video), 0x80), vec![58]);
+
+        video.set_nusiz(1, 0x07);
+        video.lock_missile(1, 0x02);
+        video.lock_missile(1, 0x00);
+        assert_eq!(lit(&render(&mut video), 0x80), vec![65]);
+    }
+
+    #[test]
+    fn ball_width_and_delay() {
+        let mut video = video();
+        video.set_ctrlpf(0x20);
+        video.reset_ball();
+        video.enable_ball(0x02);
+        assert_eq!(lit(&render(&mut video), 0x1E), vec![2, 3, 4, 5]);
+
+        // With vertical delay the ball waits for the next GRP1 write.
+        video.enable_ball(0x00);
+        video.set_ball_delay(0x01);
+        video.enable_ball(0x02);
+        assert!(lit(&render(&mut video), 0x1E).is_empty());
+        video.set_graphics(1, 0x00);
+        assert_eq!(lit(&render(&mut video), 0x1E), vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn overlapping_objects_collide() {
+        let mut video = video();
+        video.set_playfield(0, 0x10);
+        video.reset_ball();
+        video.enable_ball(0x02);
+        render(&mut video);
+        assert_eq!(video.collision(6), 0x80);
+        video.cxclr();
+        assert_eq!(video.collision(6), 0x00);
+    }
+}